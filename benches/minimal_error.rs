@@ -0,0 +1,94 @@
+//! Compares a parser built from combinators using `MinimalError` against an
+//! equivalent hand-written parser.
+//!
+//! Both parsers sum a comma-separated list of unsigned integers. With
+//! `MinimalError`, the combinator version should perform no more work than the
+//! hand-written loop.
+//!
+//! Both run on the same input in the `sum` group. The comparison to check is
+//! `sum/combinators` against `sum/hand-written`, whose throughputs should be
+//! close.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use recoil::prelude::*;
+use recoil::{AResult, Parser};
+
+fn digit(input: &[u8]) -> AResult<&[u8], u8, MinimalError> {
+    satisfy("digit", |b: u8| b.is_ascii_digit())(input)
+}
+
+fn number(mut input: &[u8]) -> AResult<&[u8], u64, MinimalError> {
+    let (rest, first) = digit(input)?;
+    let mut value = u64::from(first - b'0');
+    input = rest;
+    while let Ok((rest, next)) = digit(input) {
+        value = value * 10 + u64::from(next - b'0');
+        input = rest;
+    }
+    Ok((input, value))
+}
+
+fn sum_combinators(input: &[u8]) -> Result<u64, MinimalError> {
+    let mut separator = alt((char(','), char(';')));
+    let (mut input, mut sum) = number(input)?;
+    while !input.is_empty() {
+        let (rest, _) = separator.exec(input)?;
+        let (rest, value) = number(rest)?;
+        sum += value;
+        input = rest;
+    }
+    Ok(sum)
+}
+
+fn sum_by_hand(input: &[u8]) -> Result<u64, usize> {
+    let mut sum = 0;
+    let mut value = 0;
+    let mut digits = 0;
+    for (index, &byte) in input.iter().enumerate() {
+        match byte {
+            b'0'..=b'9' => {
+                value = value * 10 + u64::from(byte - b'0');
+                digits += 1;
+            }
+            b',' | b';' if digits > 0 => {
+                sum += value;
+                value = 0;
+                digits = 0;
+            }
+            _ => return Err(index),
+        }
+    }
+    if digits == 0 {
+        return Err(input.len());
+    }
+    Ok(sum + value)
+}
+
+fn numbers() -> Vec<u8> {
+    let mut input = Vec::new();
+    for n in 0..10_000u64 {
+        if n > 0 {
+            input.push(if n % 2 == 0 { b',' } else { b';' });
+        }
+        input.extend_from_slice((n * 7919).to_string().as_bytes());
+    }
+    input
+}
+
+fn bench_sum(c: &mut Criterion) {
+    let input = numbers();
+    assert_eq!(sum_combinators(&input).ok(), sum_by_hand(&input).ok());
+
+    let mut group = c.benchmark_group("sum");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("combinators", |b| {
+        b.iter(|| sum_combinators(black_box(&input)))
+    });
+    group.bench_function("hand-written", |b| {
+        b.iter(|| sum_by_hand(black_box(&input)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_sum);
+criterion_main!(benches);
@@ -0,0 +1,183 @@
+//! This module contains checksum algorithms and the `checksummed`
+//! combinator, which verifies the checksum of the bytes a parser consumed.
+
+use crate::error::{Error, ErrorKind};
+use crate::span::Span;
+use crate::{with_error, AResult, Parser};
+
+/// Checksum algorithms used by common binary formats.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Checksum {
+    /// CRC-32 (ISO-HDLC), as used by PNG, zip and gzip.
+    Crc32,
+    /// CRC-32C (Castagnoli), as used by iSCSI, ext4 and SCTP.
+    Crc32c,
+    /// Adler-32, as used by zlib.
+    Adler32,
+    /// Fletcher-16, whose result fits in the low 16 bits.
+    Fletcher16,
+}
+
+impl Checksum {
+    /// Computes the checksum of `data`.
+    pub fn compute(self, data: &[u8]) -> u32 {
+        match self {
+            Checksum::Crc32 => crc32(&CRC32, data),
+            Checksum::Crc32c => crc32(&CRC32C, data),
+            Checksum::Adler32 => adler32(data),
+            Checksum::Fletcher16 => fletcher16(data),
+        }
+    }
+}
+
+/// Lookup tables of the reflected CRC-32 polynomials.
+static CRC32: [u32; 256] = crc32_table(0xEDB8_8320);
+static CRC32C: [u32; 256] = crc32_table(0x82F6_3B78);
+
+const fn crc32_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+fn crc32(table: &[u32; 256], data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        table[usize::from(crc as u8 ^ byte)] ^ crc >> 8
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    // The largest number of bytes summed before `b` may overflow.
+    const BLOCK: usize = 5552;
+    let (mut a, mut b) = (1, 0);
+    for block in data.chunks(BLOCK) {
+        for &byte in block {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    b << 16 | a
+}
+
+fn fletcher16(data: &[u8]) -> u32 {
+    let (mut low, mut high) = (0u32, 0u32);
+    for &byte in data {
+        low = (low + u32::from(byte)) % 255;
+        high = (high + low) % 255;
+    }
+    high << 8 | low
+}
+
+/// Runs `parser`, then parses a checksum with `checksum` and verifies it
+/// against the `algorithm` checksum of the bytes `parser` consumed.
+///
+/// On a mismatch, fails with a `MalformedData` error at the checksum.
+///
+/// ```
+/// use recoil::prelude::*;
+/// use recoil::AResult;
+///
+/// fn payload(input: &[u8]) -> AResult<&[u8], &[u8], MinimalError> {
+///     Ok((&input[9..], &input[..9]))
+/// }
+///
+/// let mut verified = checksummed(payload, Checksum::Crc32, be_u32);
+/// let input = b"123456789\xCB\xF4\x39\x26";
+/// assert_eq!(verified(&input[..]), Ok((&b""[..], &b"123456789"[..])));
+///
+/// let error = verified(b"123456780\xCB\xF4\x39\x26").unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::MalformedData);
+/// ```
+pub fn checksummed<I, O, N, E, P, C>(
+    mut parser: P,
+    algorithm: Checksum,
+    mut checksum: C,
+) -> impl FnMut(I) -> AResult<I, O, E>
+where
+    I: Span<RefSlice = I, Member = u8> + Copy,
+    N: Into<u64>,
+    E: Error<I>,
+    P: Parser<I, O, E>,
+    C: Parser<I, N, E>,
+{
+    move |input: I| {
+        let (rest, output) = parser.exec(input)?;
+        let consumed = input.byte_len() - rest.byte_len();
+        let computed = algorithm.compute(&input.as_byte_slice()[..consumed]);
+        let (after, expected) = checksum.exec(rest)?;
+        if expected.into() != u64::from(computed) {
+            let message = with_error!(ErrorKind::MalformedData, "checksum mismatch");
+            return Err(E::from_message(rest, message));
+        }
+        Ok((after, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinator::{be_u16, le_u32};
+    use crate::error::MinimalError;
+    use crate::located::Located;
+
+    #[test]
+    fn known_values() {
+        let check = b"123456789";
+        assert_eq!(Checksum::Crc32.compute(check), 0xCBF4_3926);
+        assert_eq!(Checksum::Crc32c.compute(check), 0xE306_9283);
+        assert_eq!(Checksum::Adler32.compute(check), 0x091E_01DE);
+        assert_eq!(Checksum::Adler32.compute(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(Checksum::Fletcher16.compute(b"abcde"), 0xC8F0);
+        assert_eq!(Checksum::Fletcher16.compute(b"abcdef"), 0x2057);
+        assert_eq!(Checksum::Crc32.compute(b""), 0);
+        assert_eq!(Checksum::Adler32.compute(b""), 1);
+
+        // Long enough for the sums to be reduced several times.
+        let long = [0xFF; 20_000];
+        assert_eq!(Checksum::Adler32.compute(&long), 0x9F51_D664);
+    }
+
+    #[test]
+    fn verifies_consumed_bytes() {
+        type In = Located<&'static [u8]>;
+        let word =
+            |input: In| -> AResult<In, In> { Ok((input.over(5..input.byte_len()), input.to(5))) };
+        let mut verified = checksummed(word, Checksum::Fletcher16, be_u16);
+
+        let input = Located::new(&b"abcde\xC8\xF0tail"[..]);
+        let (rest, matched) = verified(input).unwrap();
+        assert_eq!((matched.fragment(), rest.offset()), (&b"abcde"[..], 7));
+
+        let error = verified(Located::new(&b"abcdf\xC8\xF0"[..])).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MalformedData);
+        assert_eq!(error.offset(), Some((5, 5)));
+        assert_eq!(error.to_string(), "checksum mismatch");
+
+        let error = verified(Located::new(&b"abcde\xC8"[..])).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::EndOfInput);
+
+        let failing = |input: &'static [u8]| -> AResult<&[u8], (), MinimalError> {
+            Err(MinimalError::from_error_kind(input, ErrorKind::MissingData))
+        };
+        let mut verified = checksummed(failing, Checksum::Adler32, le_u32);
+        let error = verified(&b"\x01\0\0\0"[..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MissingData);
+    }
+}
@@ -102,9 +102,11 @@ pub trait Input<I>: Span {
     /// Individual member of the input.
     type Token;
 
+    /// The slice type produced when viewing the whole input.
     type Slice: Collection + Span;
 
-    fn as_slice(&self) -> Self::RefSlice;
+    /// Returns a view over the entire input.
+    fn as_slice(&self) -> Self::Slice;
 }
 
 impl<'a> Input<char> for &'a str {
@@ -112,9 +114,7 @@ impl<'a> Input<char> for &'a str {
     type Slice = &'a str;
 
     fn as_slice(&self) -> Self::Slice {
-        let start = self.as_ptr() as usize;
-        let end = self.len().max(self.as_ptr() as usize * self.len());
-        self.over(start..end)
+        self
     }
 }
 
@@ -123,9 +123,7 @@ impl<'slice> Input<u8> for &'slice [u8] {
     type Slice = &'slice [u8];
 
     fn as_slice(&self) -> Self::Slice {
-        let start = self.as_ptr() as usize;
-        let end = self.len().max(self.as_ptr() as usize * self.len());
-        self.over(start..end)
+        self
     }
 }
 
@@ -0,0 +1,253 @@
+//! This module contains general-purpose parser subroutines and combinators.
+
+use crate::error::{Error, ErrorKind, Expected};
+use crate::span::Span;
+use crate::{AResult, Parser};
+
+/// Recognizes the literal `tag` at the start of the input.
+///
+/// Returns the matched slice. On failure, the error expects [`Expected::Tag`].
+pub fn tag<I, E>(tag: &'static str) -> impl FnMut(I) -> AResult<I, I, E>
+where
+    I: Span<RefSlice = I>,
+    E: Error<I>,
+{
+    move |input: I| {
+        if input.as_byte_slice().starts_with(tag.as_bytes()) {
+            Ok((input.over(tag.len()..input.byte_len()), input.to(tag.len())))
+        } else {
+            Err(E::from_expected(input, Expected::Tag(tag)))
+        }
+    }
+}
+
+/// Recognizes the single character `c` at the start of the input.
+///
+/// On failure, the error expects [`Expected::Char`].
+pub fn char<I, E>(c: char) -> impl FnMut(I) -> AResult<I, char, E>
+where
+    I: Span<RefSlice = I>,
+    E: Error<I>,
+{
+    move |input: I| {
+        let mut buffer = [0; 4];
+        let encoded = c.encode_utf8(&mut buffer).as_bytes();
+        if input.as_byte_slice().starts_with(encoded) {
+            Ok((input.over(encoded.len()..input.byte_len()), c))
+        } else {
+            Err(E::from_expected(input, Expected::Char(c)))
+        }
+    }
+}
+
+/// Recognizes a single item belonging to the character `class`.
+///
+/// The `class` name, such as `"digit"`, is used when reporting the error as
+/// [`Expected::Class`].
+pub fn satisfy<I, E, F>(
+    class: &'static str,
+    predicate: F,
+) -> impl FnMut(I) -> AResult<I, I::Item, E>
+where
+    I: Span<RefSlice = I>,
+    E: Error<I>,
+    F: Fn(I::Item) -> bool,
+{
+    move |input: I| {
+        let mut items = input.as_enum();
+        match items.next() {
+            Some((start, item)) if predicate(item.clone()) => {
+                let width = items
+                    .next()
+                    .map_or(input.byte_len(), |(next, _)| next - start);
+                Ok((input.over(width..input.byte_len()), item))
+            }
+            _ => Err(E::from_expected(input, Expected::Class(class))),
+        }
+    }
+}
+
+macro_rules! integer {
+    ($(#[$doc:meta])* $name:ident, $type:ty, $from:ident) => {
+        $(#[$doc])*
+        ///
+        /// Fails with an `EndOfInput` error if the input is too short.
+        pub fn $name<I, E>(input: I) -> AResult<I, $type, E>
+        where
+            I: Span<RefSlice = I, Member = u8>,
+            E: Error<I>,
+        {
+            const SIZE: usize = core::mem::size_of::<$type>();
+            match input.as_byte_slice().first_chunk::<SIZE>() {
+                Some(bytes) => Ok((input.over(SIZE..input.byte_len()), <$type>::$from(*bytes))),
+                None => Err(E::from_error_kind(input, ErrorKind::EndOfInput)),
+            }
+        }
+    };
+}
+
+integer!(
+    /// Parses a big-endian `u16`.
+    be_u16, u16, from_be_bytes
+);
+integer!(
+    /// Parses a big-endian `u32`.
+    be_u32, u32, from_be_bytes
+);
+integer!(
+    /// Parses a little-endian `u16`.
+    le_u16, u16, from_le_bytes
+);
+integer!(
+    /// Parses a little-endian `u32`.
+    le_u32, u32, from_le_bytes
+);
+
+/// A choice between several parsers, implemented for tuples of parsers.
+pub trait Alt<I, O, E> {
+    /// Tries each parser in order, returning the first successful result.
+    fn choice(&mut self, input: I) -> AResult<I, O, E>;
+}
+
+/// Tries each parser of the tuple `alternatives` in order.
+///
+/// If every alternative fails, their errors are combined with [`Error::or`],
+/// which allows errors to report every item expected at the furthest failure
+/// position.
+pub fn alt<I, O, E, L>(mut alternatives: L) -> impl FnMut(I) -> AResult<I, O, E>
+where
+    L: Alt<I, O, E>,
+{
+    move |input: I| alternatives.choice(input)
+}
+
+macro_rules! alt_tuple {
+    ($first:ident $($rest:ident)+) => {
+        impl<I, O, E, $first, $($rest),+> Alt<I, O, E> for ($first, $($rest),+)
+        where
+            I: Clone,
+            E: Error<I>,
+            $first: Parser<I, O, E>,
+            $($rest: Parser<I, O, E>),+
+        {
+            #[allow(non_snake_case)]
+            fn choice(&mut self, input: I) -> AResult<I, O, E> {
+                let ($first, $($rest),+) = self;
+                let mut error = match $first.exec(input.clone()) {
+                    Ok(result) => return Ok(result),
+                    Err(error) => error,
+                };
+                $(
+                    match $rest.exec(input.clone()) {
+                        Ok(result) => return Ok(result),
+                        Err(other) => error = error.or(other),
+                    }
+                )+
+                Err(error)
+            }
+        }
+    };
+}
+
+alt_tuple!(A B);
+alt_tuple!(A B C);
+alt_tuple!(A B C D);
+alt_tuple!(A B C D F);
+alt_tuple!(A B C D F G);
+alt_tuple!(A B C D F G H);
+alt_tuple!(A B C D F G H J);
+
+/// Parser subroutine that reports failures as a named grammar rule.
+///
+/// See [`Parser::named`].
+#[derive(Clone, Copy, Debug)]
+pub struct Named<P> {
+    pub(crate) parser: P,
+    pub(crate) name: &'static str,
+}
+
+impl<I, O, E, P> Parser<I, O, E> for Named<P>
+where
+    I: Clone,
+    E: Error<I>,
+    P: Parser<I, O, E>,
+{
+    fn exec(&mut self, input: I) -> AResult<I, O, E> {
+        self.parser
+            .exec(input.clone())
+            .map_err(|error| error.label(input, Expected::Rule(self.name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, ErrorWithContext, MinimalError};
+    use crate::located::Located;
+
+    type In = Located<&'static str>;
+    type ParseError = ErrorWithContext<In>;
+
+    #[test]
+    fn alternatives_merge_expected_items() {
+        let input = Located::new("[1 }").over(3..4);
+        let mut separator = alt((char::<In, ParseError>(','), char(']')));
+        let error = separator.exec(input).unwrap_err();
+        assert_eq!(error.offset(), Some((3, 3)));
+        // Without `alloc`, errors keep a single expected item.
+        #[cfg(feature = "alloc")]
+        {
+            assert_eq!(error.expected().len(), 2);
+            assert_eq!(error.to_string(), "expected one of `,`, `]`, found `}`");
+        }
+        #[cfg(not(feature = "alloc"))]
+        assert_eq!(error.to_string(), "expected `,`, found `}`");
+    }
+
+    #[test]
+    fn named_rules_replace_expected_items() {
+        let mut boolean = alt((tag::<In, ParseError>("true"), tag("false"))).named("boolean");
+        let (rest, matched) = boolean.exec(Located::new("false!")).unwrap();
+        assert_eq!((matched.fragment(), rest.offset()), ("false", 5));
+
+        let error = boolean.exec(Located::new("yes")).unwrap_err();
+        assert_eq!(error.to_string(), "expected boolean, found `y`");
+
+        let mut digit = satisfy::<In, ParseError, _>("digit", |c: char| c.is_ascii_digit());
+        let error = digit.exec(Located::new("")).unwrap_err();
+        assert_eq!(error.to_string(), "expected digit, found end of input");
+    }
+
+    #[test]
+    fn furthest_failure_wins() {
+        let input = Located::new("abc");
+        let near = ParseError::from_expected(input, Expected::Tag("x"));
+        let far = ParseError::from_expected(input.over(2..3), Expected::Tag("y"));
+        let or = <ParseError as Error<In>>::or;
+        assert_eq!(
+            or(near.clone(), far.clone()).expected().as_slice(),
+            &[Expected::Tag("y")]
+        );
+        assert_eq!(or(far, near).expected().as_slice(), &[Expected::Tag("y")]);
+
+        let error = ParseError::from_error_kind(input, ErrorKind::MalformedData);
+        assert_eq!(error.to_string(), ErrorKind::MalformedData.as_str());
+    }
+
+    #[test]
+    fn minimal_error_works_with_every_combinator() {
+        let input = &b"true,7"[..];
+        let mut boolean = alt((tag("true"), tag("false")))
+            .named("boolean")
+            .map(|matched: &[u8]| matched == b"true");
+        let (rest, value) = boolean.exec(input).unwrap();
+        let (rest, _) = char::<_, MinimalError>(',')(rest).unwrap();
+        let (rest, digit) =
+            satisfy::<_, MinimalError, _>("digit", |b: u8| b.is_ascii_digit())(rest).unwrap();
+        assert_eq!((value, digit, rest), (true, b'7', &b""[..]));
+
+        let error: MinimalError = boolean.exec(&input[5..]).unwrap_err();
+        assert_eq!(error.offset(&input), 5);
+        assert_eq!(error.kind(), ErrorKind::MissingData);
+    }
+}
@@ -1,22 +1,133 @@
-use crate::collection::Input;
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::borrow::{Borrow, BorrowMut};
+use core::fmt;
 
+use crate::collection::Collection;
+use crate::located::Location;
+use crate::span::Span;
+use crate::stateful::Stateful;
+
+/// Numeric code identifying the kind of an error.
+///
+/// Codes below [`StatusCode::FIRST_CUSTOM`] are reserved for the built-in
+/// [`ErrorKind`] variants. Applications may define their own codes from
+/// `FIRST_CUSTOM` upwards with [`StatusCode::custom`] and describe them in a
+/// [`Registry`].
+///
+/// [`Registry`]: crate::registry::Registry
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct StatusCode(u16);
 
+impl StatusCode {
+    /// The first code available to application-defined errors.
+    pub const FIRST_CUSTOM: u16 = 1000;
+
+    /// Creates a status code from its numeric value.
+    pub(crate) const fn new(code: u16) -> Self {
+        Self(code)
+    }
+
+    /// Creates an application-defined status code, or returns `None` if
+    /// `code` is below [`FIRST_CUSTOM`] and reserved for built-in kinds.
+    ///
+    /// [`FIRST_CUSTOM`]: StatusCode::FIRST_CUSTOM
+    pub const fn custom(code: u16) -> Option<Self> {
+        match code >= Self::FIRST_CUSTOM {
+            true => Some(Self(code)),
+            false => None,
+        }
+    }
+
+    /// Returns the numeric value of the code.
+    pub const fn get(&self) -> u16 {
+        self.0
+    }
+
+    /// Returns `true` if the code belongs to the application-defined range.
+    pub const fn is_custom(&self) -> bool {
+        self.0 >= Self::FIRST_CUSTOM
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}", self.0)
+    }
+}
+
 /// Error type with minimal contextual information.
 ///
 /// This type should be preferred if the only thing that matters is performance.
+/// It is the size of a pointer plus a `u16`, is `Copy`, and does not depend on
+/// the input type, so it can be used as the error type of any parser.
+///
+/// Rather than a reference to the input, the error records how many bytes of
+/// input remained when it occurred. The position of the error is recovered
+/// from the original input with [`MinimalError::offset`].
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct MinimalError<I: Input<I>> {
-    /// Position of the error within the given input.
-    input: I,
+pub struct MinimalError {
+    /// Number of bytes remaining in the input at the position of the error.
+    remaining: usize,
     /// Error code represented as a u16. Used to look up error by code.
     status_code: StatusCode,
 }
 
-impl<I: Input<I>> MinimalError<I> {
-    pub fn new(input: I, status_code: StatusCode) -> Self {
-        Self { input, status_code }
+impl MinimalError {
+    pub const fn new(remaining: usize, status_code: StatusCode) -> Self {
+        Self {
+            remaining,
+            status_code,
+        }
+    }
+
+    /// Returns the number of bytes remaining in the input at the error.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns the byte offset of the error within `input`, the input that was
+    /// originally given to the parser.
+    pub fn offset<I: Span>(&self, input: &I) -> usize {
+        input.byte_len().saturating_sub(self.remaining)
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::from(self.status_code)
+    }
+}
+
+impl<I: Span> Error<I> for MinimalError {
+    #[inline]
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        Self::new(input.byte_len(), kind.as_code())
+    }
+
+    #[inline]
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keeps the error that occurred furthest into the input.
+    #[inline]
+    fn or(self, other: Self) -> Self {
+        if other.remaining < self.remaining {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Display for MinimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.kind().as_str())
     }
 }
 
@@ -42,12 +153,15 @@ impl<'a> ErrorSpan<'a> {
         }
     }
 
+    /// Returns the start offset of the error, or zero if it is unknown.
     pub fn start(&self) -> usize {
-        self.input.as_ptr() as usize
+        self.offset.map_or(0, |(start, _)| start)
     }
 
+    /// Returns the end offset of the error, or the input length if it is
+    /// unknown.
     pub fn end(&self) -> usize {
-        self.input.as_ref().len()
+        self.offset.map_or(self.input.len(), |(_, end)| end)
     }
 }
 
@@ -56,14 +170,38 @@ impl<'a> ErrorSpan<'a> {
 /// `Error` contains contextual information, such as the error span and
 /// code. This data is used to construct `ParserError` instances containing
 /// useful information.
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct ErrorWithContext<I: Input<I>> {
+///
+/// Unlike [`MinimalError`], this type is `Clone` but not `Copy`: with the
+/// `alloc` feature its expected items and message are boxed, and messages may
+/// be owned.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ErrorWithContext<I> {
     input: I,
     offset: Option<(usize, usize)>,
     code: u16,
+    /// Expected items and message, if any were recorded.
+    details: Option<DetailsPtr>,
+}
+
+/// Additional information attached to an [`ErrorWithContext`].
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Details {
+    /// Items that would have allowed parsing to continue at the error.
+    expected: ExpectedSet,
+    /// Message describing the error, taking precedence over `expected`.
+    message: Option<MessageText>,
 }
 
-impl<I: Input<I>> ErrorWithContext<I> {
+/// Details are boxed when the `alloc` feature is enabled, which keeps errors
+/// small enough to be returned cheaply.
+#[cfg(feature = "alloc")]
+type DetailsPtr = Box<Details>;
+#[cfg(not(feature = "alloc"))]
+type DetailsPtr = Details;
+
+static NOTHING_EXPECTED: ExpectedSet = ExpectedSet::new();
+
+impl<I> ErrorWithContext<I> {
     pub fn new(input: I, offset: Option<(usize, usize)>, code: u16) -> Self {
         if let Some((start, end)) = offset {
             debug_assert!(start <= end);
@@ -71,27 +209,94 @@ impl<I: Input<I>> ErrorWithContext<I> {
                 input,
                 offset: Some((start, end)),
                 code,
+                details: None,
             }
         } else {
             Self {
                 input,
                 offset: None,
                 code,
+                details: None,
             }
         }
     }
 
+    /// Returns the input at which the error occurred.
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns the absolute `(start, end)` offsets of the error, if known.
+    pub fn offset(&self) -> Option<(usize, usize)> {
+        self.offset
+    }
+
+    /// Returns the set of items that were expected at the error position.
+    pub fn expected(&self) -> &ExpectedSet {
+        self.details()
+            .map_or(&NOTHING_EXPECTED, |details| &details.expected)
+    }
+
+    /// Returns the message attached to the error, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.details()?.message.as_ref().map(text)
+    }
+
+    /// Attaches `message` to the error, replacing its kind with the kind of
+    /// the message.
+    pub fn with_message(mut self, message: ErrorMessage) -> Self {
+        self.code = message.kind.as_code().0;
+        self.details_mut().message = Some(message.message);
+        self
+    }
+
+    fn details(&self) -> Option<&Details> {
+        self.details.as_ref().map(Borrow::borrow)
+    }
+
+    fn details_mut(&mut self) -> &mut Details {
+        self.details
+            .get_or_insert_with(|| DetailsPtr::from(Details::default()))
+            .borrow_mut()
+    }
+
+    fn expected_mut(&mut self) -> &mut ExpectedSet {
+        &mut self.details_mut().expected
+    }
+
+    /// Merges two errors, keeping the one that occurred furthest into the
+    /// input. Errors at the same position combine their expected items.
+    fn merge(mut self, other: Self) -> Self {
+        let start = |error: &Self| error.offset.map(|(start, _)| start);
+        match (start(&self), start(&other)) {
+            (Some(this), Some(that)) if this > that => self,
+            (Some(this), Some(that)) if this < that => other,
+            _ => {
+                if !other.expected().is_empty() {
+                    self.expected_mut().extend(other.expected());
+                }
+                self
+            }
+        }
+    }
+
+    /// Replaces the expected items with the named `rule`, provided the error
+    /// occurred at the start of the rule's input.
+    fn relabel(mut self, location: Option<usize>, rule: Expected) -> Self {
+        if self.offset.map(|(start, _)| start) == location {
+            *self.expected_mut() = ExpectedSet::from(rule);
+        }
+        self
+    }
+
+    /// Returns the status code of the error.
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode(self.code)
+    }
+
     pub fn kind(&self) -> ErrorKind {
-        use ErrorKind::*;
         // Use the error's `code` to match against `ErrorKind` discriminant.
-        match self.code {
-            0 => EndOfInput,
-            1 => IncompatibleTypes,
-            2 => MalformedData,
-            3 => MissingData,
-            4 => Unknown,
-            _ => ErrorKind::default(),
-        }
+        ErrorKind::from(self.status_code())
     }
 }
 
@@ -108,11 +313,25 @@ pub enum ErrorKind {
     /// An unknown or explicitly unspecified error has occurred.
     #[default]
     Unknown,
+    /// An application-defined error, identified by its status code.
+    ///
+    /// Public codes are created with [`StatusCode::custom`], so they never
+    /// collide with the codes of the built-in variants.
+    ///
+    /// The code should be described in a [`Registry`] to provide a name,
+    /// message and severity.
+    ///
+    /// [`Registry`]: crate::registry::Registry
+    Custom(StatusCode),
 }
 
 impl ErrorKind {
     /// Returns the string representation of the error kind.
-    pub(crate) fn as_str(&self) -> &'static str {
+    ///
+    /// Messages of custom error kinds are looked up in a [`Registry`].
+    ///
+    /// [`Registry`]: crate::registry::Registry
+    pub fn as_str(&self) -> &'static str {
         use ErrorKind::*;
         match *self {
             EndOfInput => "Reached end of input, or EOF, while waiting on data.",
@@ -120,19 +339,13 @@ impl ErrorKind {
             MalformedData => "Received invalid or malformed data.",
             MissingData => "Received incomplete or missing data.",
             Unknown => "Failure caused by unknown or unexpected error.",
+            Custom(_) => "Application-defined error.",
         }
     }
 
     /// Returns the status code of the error kind.
-    pub(crate) fn as_code(&self) -> StatusCode {
-        use ErrorKind::*;
-        match *self {
-            EndOfInput => StatusCode(0),
-            IncompatibleTypes => StatusCode(1),
-            MalformedData => StatusCode(2),
-            MissingData => StatusCode(3),
-            Unknown => StatusCode(4),
-        }
+    pub fn as_code(&self) -> StatusCode {
+        StatusCode::from(*self)
     }
 }
 
@@ -144,6 +357,7 @@ impl From<StatusCode> for ErrorKind {
             StatusCode(2) => ErrorKind::MalformedData,
             StatusCode(3) => ErrorKind::MissingData,
             StatusCode(4) => ErrorKind::Unknown,
+            code if code.is_custom() => ErrorKind::Custom(code),
             _ => ErrorKind::default(),
         }
     }
@@ -157,6 +371,7 @@ impl From<ErrorKind> for StatusCode {
             ErrorKind::MalformedData => StatusCode(2),
             ErrorKind::MissingData => StatusCode(3),
             ErrorKind::Unknown => StatusCode(4),
+            ErrorKind::Custom(code) => code,
         }
     }
 }
@@ -164,25 +379,292 @@ impl From<ErrorKind> for StatusCode {
 pub trait Error<I>: Sized {
     fn from_error_kind(input: I, kind: ErrorKind) -> Self;
 
+    /// Creates an error at `input` recording the item that was `expected`.
+    ///
+    /// Error types that do not track expected items fall back to a
+    /// `MissingData` error.
+    fn from_expected(input: I, expected: Expected) -> Self {
+        let _ = expected;
+        Self::from_error_kind(input, ErrorKind::MissingData)
+    }
+
+    /// Creates an error at `input` from an [`ErrorMessage`], typically built
+    /// with the [`with_error!`] macro.
+    ///
+    /// Error types that do not store messages keep only the message's kind.
+    ///
+    /// [`with_error!`]: crate::with_error
+    fn from_message(input: I, message: ErrorMessage) -> Self {
+        Self::from_error_kind(input, message.kind())
+    }
+
     fn append(input: I, kind: ErrorKind, other: Self) -> Self;
 
+    /// Combines the errors of two alternatives that both failed.
     fn or(self, other: Self) -> Self {
         other
     }
+
+    /// Labels an error raised by the named `rule` that started at `input`.
+    fn label(self, input: I, rule: Expected) -> Self {
+        let _ = (input, rule);
+        self
+    }
 }
 
-#[derive(Debug, Default, Eq, PartialEq, PartialOrd, Ord)]
+impl<I: Location> Error<I> for ErrorWithContext<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        let offset = input.location().map(|start| (start, start));
+        Self::new(input, offset, kind.as_code().0)
+    }
+
+    fn from_expected(input: I, expected: Expected) -> Self {
+        let mut error = Self::from_error_kind(input, ErrorKind::MissingData);
+        *error.expected_mut() = ExpectedSet::from(expected);
+        error
+    }
+
+    fn from_message(input: I, message: ErrorMessage) -> Self {
+        Self::from_error_kind(input, message.kind()).with_message(message)
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        self.merge(other)
+    }
+
+    fn label(self, input: I, rule: Expected) -> Self {
+        self.relabel(input.location(), rule)
+    }
+}
+
+/// Errors raised on [`Stateful`] input discard the state, keeping only the
+/// wrapped input.
+impl<I: Location, S> Error<Stateful<I, S>> for ErrorWithContext<I> {
+    fn from_error_kind(input: Stateful<I, S>, kind: ErrorKind) -> Self {
+        let (input, _) = input.into_parts();
+        Error::from_error_kind(input, kind)
+    }
+
+    fn from_expected(input: Stateful<I, S>, expected: Expected) -> Self {
+        let (input, _) = input.into_parts();
+        Error::from_expected(input, expected)
+    }
+
+    fn from_message(input: Stateful<I, S>, message: ErrorMessage) -> Self {
+        let (input, _) = input.into_parts();
+        Error::from_message(input, message)
+    }
+
+    fn append(_input: Stateful<I, S>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        self.merge(other)
+    }
+
+    fn label(self, input: Stateful<I, S>, rule: Expected) -> Self {
+        self.relabel(input.location(), rule)
+    }
+}
+
+/// Renders the error's message if it has one. Otherwise, renders the expected
+/// items as `expected one of `,`, `]`, found `}``, falling back to the
+/// description of the error kind when nothing was expected.
+impl<I> fmt::Display for ErrorWithContext<I>
+where
+    I: Collection,
+    I::Item: Into<char>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(message) = self.message() {
+            return f.write_str(message);
+        }
+        let found = self.input.as_iter().next().map(Into::into);
+        match self.expected().as_slice() {
+            [] => return f.write_str(self.kind().as_str()),
+            [expected] => write!(f, "expected {expected}")?,
+            [first, rest @ ..] => {
+                write!(f, "expected one of {first}")?;
+                for expected in rest {
+                    write!(f, ", {expected}")?;
+                }
+            }
+        }
+        match found {
+            Some(found) => write!(f, ", found `{found}`"),
+            None => f.write_str(", found end of input"),
+        }
+    }
+}
+
+/// An item that a parser expected to find.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Expected {
+    /// A literal tag, such as a keyword or punctuation.
+    Tag(&'static str),
+    /// A single literal character.
+    Char(char),
+    /// A class of characters, such as `digit` or `whitespace`.
+    Class(&'static str),
+    /// A named grammar rule, such as `value` or `string`.
+    Rule(&'static str),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Tag(tag) => write!(f, "`{tag}`"),
+            Expected::Char(c) => write!(f, "`{c}`"),
+            Expected::Class(name) | Expected::Rule(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Small, inline set of [`Expected`] items.
+///
+/// The set holds at most [`ExpectedSet::CAPACITY`] distinct items, in the order
+/// they were added. Further items are silently dropped, which keeps errors
+/// cheap to copy and free of allocations.
+///
+/// Without the `alloc` feature, error details are stored inline rather than
+/// boxed, so the set holds a single item to keep errors small.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ExpectedSet {
+    /// Items of the set. Slots past `len` always hold `ExpectedSet::VACANT`.
+    items: [Expected; ExpectedSet::CAPACITY],
+    len: u8,
+}
+
+impl ExpectedSet {
+    #[cfg(feature = "alloc")]
+    pub const CAPACITY: usize = 8;
+    #[cfg(not(feature = "alloc"))]
+    pub const CAPACITY: usize = 1;
+
+    const VACANT: Expected = Expected::Tag("");
+
+    pub const fn new() -> Self {
+        Self {
+            items: [Self::VACANT; Self::CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Adds `item` to the set, ignoring duplicates.
+    pub fn insert(&mut self, item: Expected) {
+        let len = self.len as usize;
+        if len < Self::CAPACITY && !self.contains(&item) {
+            self.items[len] = item;
+            self.len += 1;
+        }
+    }
+
+    /// Adds every item of `other` to the set.
+    pub fn extend(&mut self, other: &ExpectedSet) {
+        for item in other.as_slice() {
+            self.insert(*item);
+        }
+    }
+
+    pub fn contains(&self, item: &Expected) -> bool {
+        self.as_slice().contains(item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the items of the set, in insertion order.
+    pub fn as_slice(&self) -> &[Expected] {
+        &self.items[..self.len()]
+    }
+}
+
+impl Default for ExpectedSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Expected> for ExpectedSet {
+    fn from(item: Expected) -> Self {
+        let mut set = Self::new();
+        set.insert(item);
+        set
+    }
+}
+
+/// Text of an error message. Messages are owned when the `alloc` feature is
+/// enabled, and static otherwise.
+#[cfg(feature = "alloc")]
+type MessageText = Cow<'static, str>;
+#[cfg(not(feature = "alloc"))]
+type MessageText = &'static str;
+
+fn text(message: &MessageText) -> &str {
+    message
+}
+
+/// An error kind paired with a human-readable message.
+///
+/// Messages are usually created with the [`with_error!`] macro and attached to
+/// errors using [`Error::from_message`].
+///
+/// [`with_error!`]: crate::with_error
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub struct ErrorMessage {
     kind: ErrorKind,
-    message: &'static str,
+    message: MessageText,
 }
 
 impl ErrorMessage {
     pub const DEFAULT_MESSAGE: &str = "Unknown error caused by a parser failure has occurred.";
 
     pub const fn new(kind: ErrorKind, message: &'static str) -> Self {
+        Self::from_static_message(kind, message)
+    }
+
+    /// Creates an error message from a constant `message`.
+    pub const fn from_static_message(kind: ErrorKind, message: &'static str) -> Self {
+        #[cfg(feature = "alloc")]
+        let message = Cow::Borrowed(message);
         Self { kind, message }
     }
+
+    /// Creates an error message from formatting arguments.
+    ///
+    /// Arguments without any runtime values are stored without allocating.
+    #[cfg(feature = "alloc")]
+    pub fn from_args(kind: ErrorKind, args: fmt::Arguments<'_>) -> Self {
+        let message = match args.as_str() {
+            Some(message) => Cow::Borrowed(message),
+            None => Cow::Owned(alloc::fmt::format(args)),
+        };
+        Self { kind, message }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        text(&self.message)
+    }
+}
+
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
 }
 
 #[cfg(test)]
@@ -203,4 +685,57 @@ mod tests {
             "error message should contain a valid `kind`."
         );
     }
+
+    #[test]
+    fn minimal_error_is_pointer_sized() {
+        assert_eq!(
+            core::mem::size_of::<MinimalError>(),
+            core::mem::size_of::<(usize, u16)>()
+        );
+
+        let input = "key = value";
+        let error = <MinimalError as Error<&str>>::from_error_kind(&input[4..], MalformedData);
+        assert_eq!(error.offset(&input), 4);
+        assert_eq!(error.kind(), MalformedData);
+
+        let near = MinimalError::new(10, MissingData.as_code());
+        let far = MinimalError::new(2, EndOfInput.as_code());
+        assert_eq!(Error::<&str>::or(near, far), far);
+        assert_eq!(Error::<&str>::or(far, near), far);
+    }
+
+    #[test]
+    fn minimal_error_fits_an_offset_and_a_code() {
+        use core::mem::{align_of, size_of};
+        let bound = (size_of::<usize>() + size_of::<u16>()).next_multiple_of(align_of::<usize>());
+        assert!(size_of::<MinimalError>() <= bound);
+    }
+
+    #[test]
+    fn errors_with_context_stay_small() {
+        // Details are inline without `alloc`, so this also bounds their size.
+        let size = core::mem::size_of::<ErrorWithContext<crate::located::Located<&[u8]>>>();
+        assert!(size <= 128, "{size} bytes");
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn formatted_messages_become_part_of_errors() {
+        let (expected, found) = ('}', ']');
+        let message = crate::with_error!(
+            MalformedData,
+            "expected `{}` but found `{}`",
+            expected,
+            found
+        );
+        assert_eq!(message.message(), "expected `}` but found `]`");
+
+        let error = ErrorWithContext::from_message("]", message);
+        assert_eq!(error.kind(), MalformedData);
+        assert_eq!(error.to_string(), "expected `}` but found `]`");
+
+        let error = ErrorWithContext::from_message("", crate::with_error!(EndOfInput, "unclosed"));
+        let details = error.details().unwrap();
+        assert!(matches!(details.message, Some(Cow::Borrowed("unclosed"))));
+    }
 }
@@ -0,0 +1,618 @@
+//! This module contains a decoder for CBOR, as specified by RFC 8949.
+//!
+//! [`decode`] decodes one data item into a tree borrowing strings from the
+//! input. It works on partial input: while the item has not been received in
+//! full, it returns [`Status::Incomplete`] and should be called again once
+//! more data has arrived.
+//!
+//! Tags are kept as [`Value::Tag`] around the item they enclose, without
+//! interpreting them. Indefinite-length arrays and maps are marked as such.
+//! An indefinite-length string is decoded as the concatenation of its
+//! chunks, which is the only case where the tree does not borrow from the
+//! input.
+//!
+//! Declared lengths and nesting, including nested tags, are checked against
+//! [`Limits`] before any memory is reserved.
+//!
+//! ```
+//! use recoil::formats::cbor::{self, Limits, Value};
+//! use recoil::formats::Status;
+//!
+//! // 1(1363896240), a tagged epoch-based date.
+//! let input = b"\xC1\x1A\x51\x4B\x67\xB0";
+//! let Ok(Status::Complete(value, _)) = cbor::decode(input, &Limits::default()) else {
+//!     panic!("the item is complete");
+//! };
+//! let Value::Tag(1, date) = value else {
+//!     panic!("a tagged date");
+//! };
+//! assert_eq!(date.as_integer(), Some(1_363_896_240));
+//!
+//! // ["a", _ "b" "c"], where the second string is indefinite.
+//! let input = b"\x82\x61a\x7F\x61b\x61c\xFF";
+//! let Ok(Status::Complete(value, _)) = cbor::decode(input, &Limits::default()) else {
+//!     panic!("the item is complete");
+//! };
+//! let strings: Vec<_> = value.as_array().unwrap().iter().map(Value::as_str).collect();
+//! assert_eq!(strings, [Some("a"), Some("bc")]);
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str;
+
+use super::{spanning, Status};
+use crate::error::{ErrorKind, ErrorMessage, ErrorWithContext};
+use crate::located::Located;
+use crate::with_error;
+
+/// Error returned when decoding fails, spanning the offending bytes.
+pub type CborError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+/// Major types of the initial byte of a data item.
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+
+/// The initial byte that ends an indefinite-length item.
+const BREAK: u8 = 0xFF;
+
+/// A decoded data item.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'a> {
+    /// An unsigned or negative integer, which ranges from -2⁶⁴ to 2⁶⁴ - 1.
+    Integer(i128),
+    Bytes(Cow<'a, [u8]>),
+    Text(Cow<'a, str>),
+    Array {
+        items: Vec<Value<'a>>,
+        indefinite: bool,
+    },
+    /// The entries of a map, in the order they were encoded.
+    Map {
+        entries: Vec<(Value<'a>, Value<'a>)>,
+        indefinite: bool,
+    },
+    /// A tag number and the item it encloses.
+    Tag(u64, Box<Value<'a>>),
+    Boolean(bool),
+    Null,
+    Undefined,
+    /// A simple value other than `false`, `true`, `null` and `undefined`.
+    Simple(u8),
+    /// A float, widened to `f64` if it was encoded with half or single
+    /// precision.
+    Float(f64),
+}
+
+impl<'a> Value<'a> {
+    pub fn as_integer(&self) -> Option<i128> {
+        match *self {
+            Value::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Value::Array { items, .. } => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the first entry of a map whose key is the text
+    /// string `key`.
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        let Value::Map { entries, .. } = self else {
+            return None;
+        };
+        entries
+            .iter()
+            .find(|(name, _)| name.as_str() == Some(key))
+            .map(|(_, value)| value)
+    }
+}
+
+/// Limits enforced while decoding, guarding against hostile input.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Limits {
+    /// Maximum nesting of arrays, maps and tags.
+    pub max_depth: usize,
+    /// Maximum length of a string in bytes, and of an array or map in
+    /// items, including indefinite-length ones.
+    pub max_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_length: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Decodes the data item at the start of `input`.
+pub fn decode<'a>(input: &'a [u8], limits: &Limits) -> Result<Status<Value<'a>>, CborError<'a>> {
+    let mut decoder = Decoder {
+        source: input,
+        at: 0,
+        limits,
+        depth: 0,
+    };
+    match decoder.value() {
+        Ok(value) => Ok(Status::Complete(value, decoder.at)),
+        Err(Stop::Incomplete) => Ok(Status::Incomplete),
+        Err(Stop::Error(error)) => Err(error),
+    }
+}
+
+/// Reason to stop decoding.
+enum Stop<'a> {
+    Incomplete,
+    Error(CborError<'a>),
+}
+
+/// The initial byte of a data item and its argument, which is `None` for
+/// indefinite lengths.
+struct Head {
+    major: u8,
+    info: u8,
+    argument: Option<u64>,
+}
+
+struct Decoder<'a, 'l> {
+    source: &'a [u8],
+    at: usize,
+    limits: &'l Limits,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a, '_> {
+    fn error(&self, start: usize, end: usize, message: ErrorMessage) -> Stop<'a> {
+        Stop::Error(spanning(Located::new(self.source), start, end, message))
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Stop<'a>> {
+        let end = self.at.checked_add(length).ok_or(Stop::Incomplete)?;
+        let bytes = self.source.get(self.at..end).ok_or(Stop::Incomplete)?;
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, Stop<'a>> {
+        self.source.get(self.at).copied().ok_or(Stop::Incomplete)
+    }
+
+    /// Consumes the break ending an indefinite-length item, if it is next.
+    fn at_break(&mut self) -> Result<bool, Stop<'a>> {
+        let found = self.peek()? == BREAK;
+        self.at += usize::from(found);
+        Ok(found)
+    }
+
+    fn head(&mut self) -> Result<Head, Stop<'a>> {
+        let start = self.at;
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1F);
+        let size = match info {
+            0..=23 => 0,
+            24..=27 => 1 << (info - 24),
+            31 => {
+                return Ok(Head {
+                    major,
+                    info,
+                    argument: None,
+                })
+            }
+            _ => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "reserved additional information {info}"
+                );
+                return Err(self.error(start, self.at, message));
+            }
+        };
+        let argument = match size {
+            0 => u64::from(info),
+            _ => self
+                .take(size)?
+                .iter()
+                .fold(0, |value, &b| value << 8 | u64::from(b)),
+        };
+        Ok(Head {
+            major,
+            info,
+            argument: Some(argument),
+        })
+    }
+
+    /// Checks a length counted from the item starting at `start`.
+    fn length(&self, start: usize, length: u64) -> Result<usize, Stop<'a>> {
+        let max = self.limits.max_length;
+        match usize::try_from(length) {
+            Ok(length) if length <= max => Ok(length),
+            _ => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "length {length} exceeds the limit of {max}"
+                );
+                Err(self.error(start, self.at, message))
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value<'a>, Stop<'a>> {
+        let start = self.at;
+        let Head {
+            major,
+            info,
+            argument,
+        } = self.head()?;
+        let value = match (major, argument) {
+            (0, Some(value)) => Value::Integer(value.into()),
+            (1, Some(value)) => Value::Integer(-1 - i128::from(value)),
+            (BYTES, Some(length)) => Value::Bytes(Cow::Borrowed(self.data(start, length)?)),
+            (BYTES, None) => Value::Bytes(Cow::Owned(self.chunks(start, BYTES)?)),
+            (TEXT, Some(length)) => Value::Text(Cow::Borrowed(self.text(start, length)?)),
+            (TEXT, None) => {
+                let bytes = self.chunks(start, TEXT)?;
+                // Each chunk has been checked to be valid UTF-8 on its own.
+                Value::Text(Cow::Owned(String::from_utf8(bytes).expect("valid chunks")))
+            }
+            (4, length) => {
+                let length = length
+                    .map(|length| self.length(start, length))
+                    .transpose()?;
+                self.enter(start)?;
+                let mut items = Vec::with_capacity(self.capacity(length, 1));
+                while self.more(start, items.len(), length)? {
+                    items.push(self.value()?);
+                }
+                self.depth -= 1;
+                let indefinite = length.is_none();
+                Value::Array { items, indefinite }
+            }
+            (5, length) => {
+                let length = length
+                    .map(|length| self.length(start, length))
+                    .transpose()?;
+                self.enter(start)?;
+                let mut entries = Vec::with_capacity(self.capacity(length, 2));
+                while self.more(start, entries.len(), length)? {
+                    let key = self.value()?;
+                    entries.push((key, self.value()?));
+                }
+                self.depth -= 1;
+                let indefinite = length.is_none();
+                Value::Map {
+                    entries,
+                    indefinite,
+                }
+            }
+            (6, Some(tag)) => {
+                self.enter(start)?;
+                let value = self.value()?;
+                self.depth -= 1;
+                Value::Tag(tag, Box::new(value))
+            }
+            (7, argument) => return self.simple(start, info, argument),
+            (_, None) => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "major type {major} cannot have an indefinite length"
+                );
+                return Err(self.error(start, self.at, message));
+            }
+            (8.., _) => unreachable!("the major type has three bits"),
+        };
+        Ok(value)
+    }
+
+    fn data(&mut self, start: usize, length: u64) -> Result<&'a [u8], Stop<'a>> {
+        let length = self.length(start, length)?;
+        self.take(length)
+    }
+
+    fn text(&mut self, start: usize, length: u64) -> Result<&'a str, Stop<'a>> {
+        let data_start = self.at;
+        let data = self.data(start, length)?;
+        str::from_utf8(data).map_err(|error| {
+            let at = data_start + error.valid_up_to();
+            let message = with_error!(ErrorKind::MalformedData, "invalid UTF-8 in text string");
+            self.error(at, at + error.error_len().unwrap_or(1), message)
+        })
+    }
+
+    /// Decodes the chunks of an indefinite-length string of type `major`.
+    fn chunks(&mut self, start: usize, major: u8) -> Result<Vec<u8>, Stop<'a>> {
+        let mut bytes = Vec::new();
+        while !self.at_break()? {
+            let chunk = self.at;
+            match self.head()? {
+                Head {
+                    major: found,
+                    argument: Some(length),
+                    ..
+                } if found == major => {
+                    let total = (bytes.len() as u64).saturating_add(length);
+                    self.length(start, total)?;
+                    let data = match major {
+                        TEXT => self.text(chunk, length)?.as_bytes(),
+                        _ => self.data(chunk, length)?,
+                    };
+                    bytes.extend_from_slice(data);
+                }
+                _ => {
+                    let message = with_error!(
+                        ErrorKind::MalformedData,
+                        "chunks of an indefinite-length string must be definite strings of the same type"
+                    );
+                    return Err(self.error(chunk, self.at, message));
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Returns `true` if an array or map holding `count` items has more,
+    /// consuming the break that ends an indefinite-length one.
+    fn more(
+        &mut self,
+        start: usize,
+        count: usize,
+        length: Option<usize>,
+    ) -> Result<bool, Stop<'a>> {
+        match length {
+            Some(length) => Ok(count < length),
+            None if self.at_break()? => Ok(false),
+            None => self.length(start, count as u64 + 1).map(|_| true),
+        }
+    }
+
+    fn simple(
+        &mut self,
+        start: usize,
+        info: u8,
+        argument: Option<u64>,
+    ) -> Result<Value<'a>, Stop<'a>> {
+        let value = match (info, argument) {
+            (20, _) => Value::Boolean(false),
+            (21, _) => Value::Boolean(true),
+            (22, _) => Value::Null,
+            (23, _) => Value::Undefined,
+            (0..=19, _) => Value::Simple(info),
+            (24, Some(value)) if value >= 32 => Value::Simple(value as u8),
+            (25, Some(bits)) => Value::Float(half(bits as u16)),
+            (26, Some(bits)) => Value::Float(f32::from_bits(bits as u32).into()),
+            (27, Some(bits)) => Value::Float(f64::from_bits(bits)),
+            (24, _) => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "simple values below 32 must be encoded in the initial byte"
+                );
+                return Err(self.error(start, self.at, message));
+            }
+            _ => {
+                let message = with_error!(ErrorKind::MalformedData, "unexpected break");
+                return Err(self.error(start, self.at, message));
+            }
+        };
+        Ok(value)
+    }
+
+    /// Enters an array, map or tag starting at `start`.
+    fn enter(&mut self, start: usize) -> Result<(), Stop<'a>> {
+        if self.depth == self.limits.max_depth {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "exceeded the maximum nesting depth of {}",
+                self.limits.max_depth
+            );
+            return Err(self.error(start, self.at, message));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Returns the capacity to reserve for `length` items, each taking at
+    /// least `size` bytes of the remaining input.
+    fn capacity(&self, length: Option<usize>, size: usize) -> usize {
+        length
+            .unwrap_or(0)
+            .min((self.source.len() - self.at) / size)
+    }
+}
+
+/// Converts a half-precision float to `f64`, which represents it exactly.
+fn half(bits: u16) -> f64 {
+    let sign = u64::from(bits >> 15) << 63;
+    let exponent = u64::from(bits >> 10 & 0x1F);
+    let mantissa = u64::from(bits & 0x3FF);
+    let magnitude = match exponent {
+        // Subnormal numbers are multiples of 2⁻²⁴.
+        0 => mantissa as f64 * f64::from_bits((1023 - 24) << 52),
+        0x1F => f64::from_bits(0x7FF << 52 | mantissa << 42),
+        _ => f64::from_bits((exponent + 1023 - 15) << 52 | mantissa << 42),
+    };
+    f64::from_bits(magnitude.to_bits() | sign)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(input: &[u8]) -> Value<'_> {
+        match decode(input, &Limits::default()) {
+            Ok(Status::Complete(value, length)) => {
+                assert_eq!(length, input.len());
+                value
+            }
+            status => panic!("{status:?}"),
+        }
+    }
+
+    fn failure(input: &[u8], limits: &Limits) -> (String, (usize, usize)) {
+        let error = decode(input, limits).unwrap_err();
+        (error.to_string(), error.offset().unwrap())
+    }
+
+    #[test]
+    fn decodes_scalars() {
+        // Examples from appendix A of RFC 8949.
+        let cases: [(&[u8], Value<'_>); 15] = [
+            (b"\x17", Value::Integer(23)),
+            (b"\x18\x18", Value::Integer(24)),
+            (
+                b"\x1B\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                Value::Integer(u64::MAX.into()),
+            ),
+            (
+                b"\x3B\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                Value::Integer(-(1 << 64)),
+            ),
+            (b"\x38\x63", Value::Integer(-100)),
+            (b"\xF9\x3C\x00", Value::Float(1.0)),
+            (b"\xF9\x00\x01", Value::Float(5.960464477539063e-8)),
+            (b"\xF9\xC4\x00", Value::Float(-4.0)),
+            (b"\xF9\x7C\x00", Value::Float(f64::INFINITY)),
+            (b"\xFA\x47\xC3\x50\x00", Value::Float(100000.0)),
+            (b"\xFB\x3F\xF1\x99\x99\x99\x99\x99\x9A", Value::Float(1.1)),
+            (b"\xF4", Value::Boolean(false)),
+            (b"\xF7", Value::Undefined),
+            (b"\xF8\xFF", Value::Simple(255)),
+            (b"\x64\x49\x45\x54\x46", Value::Text(Cow::Borrowed("IETF"))),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(complete(input), expected, "{input:x?}");
+        }
+        match complete(b"\xF9\x7E\x00") {
+            Value::Float(value) => assert!(value.is_nan()),
+            value => panic!("{value:?}"),
+        }
+        assert_eq!(
+            complete(b"\x44\x01\x02\x03\x04").as_bytes(),
+            Some(&[1, 2, 3, 4][..])
+        );
+    }
+
+    #[test]
+    fn decodes_containers_and_tags() {
+        let value = complete(b"\xA2\x61a\x01\x61b\x82\x02\x03");
+        assert_eq!(value.get("a"), Some(&Value::Integer(1)));
+        assert_eq!(
+            value.get("b").and_then(Value::as_array).map(<[_]>::len),
+            Some(2)
+        );
+
+        let value = complete(b"\xC0\x74\x32\x30\x31\x33\x2D\x30\x33\x2D\x32\x31\x54\x32\x30\x3A\x30\x34\x3A\x30\x30\x5A");
+        let Value::Tag(0, date) = value else {
+            panic!("{value:?}");
+        };
+        assert_eq!(date.as_str(), Some("2013-03-21T20:04:00Z"));
+    }
+
+    #[test]
+    fn decodes_indefinite_lengths() {
+        let value = complete(b"\x5F\x42\x01\x02\x43\x03\x04\x05\xFF");
+        assert_eq!(value, Value::Bytes(Cow::Owned(Vec::from([1, 2, 3, 4, 5]))));
+
+        let value = complete(b"\x9F\x01\x82\x02\x03\x9F\x04\x05\xFF\xFF");
+        let Value::Array { items, indefinite } = value else {
+            panic!("{value:?}");
+        };
+        assert!(indefinite);
+        assert_eq!(
+            items[1],
+            Value::Array {
+                items: Vec::from([Value::Integer(2), Value::Integer(3)]),
+                indefinite: false,
+            }
+        );
+
+        let value = complete(b"\xBF\x63Fun\xF5\x63Amt\x21\xFF");
+        assert_eq!(value.get("Amt"), Some(&Value::Integer(-2)));
+        assert!(matches!(
+            value,
+            Value::Map {
+                indefinite: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reports_incomplete_input() {
+        let input = b"\x9F\x19\x01\x00\x65hello\x5F\x41\x00\xFF\xFF";
+        for length in 0..input.len() {
+            let status = decode(&input[..length], &Limits::default());
+            assert!(matches!(status, Ok(Status::Incomplete)), "{length} bytes");
+        }
+        let status = decode(b"\xF6\xF5", &Limits::default()).unwrap();
+        assert_eq!(status, Status::Complete(Value::Null, 1));
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let limits = Limits {
+            max_depth: 2,
+            max_length: 3,
+        };
+        assert_eq!(
+            failure(b"\xC1\xC1\xC1\x00", &limits),
+            ("exceeded the maximum nesting depth of 2".into(), (2, 3))
+        );
+        assert_eq!(
+            failure(b"\x9F\x01\x02\x03\x04\xFF", &limits),
+            ("length 4 exceeds the limit of 3".into(), (0, 4))
+        );
+        assert_eq!(
+            failure(b"\x7F\x62ab\x62cd\xFF", &limits).0,
+            "length 4 exceeds the limit of 3"
+        );
+        let error =
+            decode(b"\x9B\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF", &Limits::default()).unwrap_err();
+        assert_eq!(error.offset(), Some((0, 9)));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let limits = Limits::default();
+        assert_eq!(
+            failure(b"\x82\x01\x1C", &limits),
+            ("reserved additional information 28".into(), (2, 3))
+        );
+        assert_eq!(
+            failure(b"\x81\xFF", &limits),
+            ("unexpected break".into(), (1, 2))
+        );
+        assert_eq!(
+            failure(b"\x1F", &limits),
+            (
+                "major type 0 cannot have an indefinite length".into(),
+                (0, 1)
+            )
+        );
+        assert_eq!(failure(b"\x5F\x61a\xFF", &limits).1, (1, 2));
+        assert_eq!(failure(b"\xF8\x10", &limits).1, (0, 2));
+        assert_eq!(
+            failure(b"\x62a\xFF", &limits),
+            ("invalid UTF-8 in text string".into(), (2, 3))
+        );
+    }
+}
@@ -0,0 +1,599 @@
+//! This module contains a parser for comma- and tab-separated values, as
+//! specified by RFC 4180.
+//!
+//! Records and fields borrow from the input. Fields are returned with their
+//! surrounding quotes removed but escapes left in place; see
+//! [`Field::unescape`]. Quoted fields may span several lines, and both `\n`
+//! and `\r\n` terminate records. Empty lines are skipped.
+//!
+//! [`Reader`] iterates over the records of a complete input, while [`Decoder`]
+//! yields records incrementally from a buffer that is filled as data arrives.
+//!
+//! ```
+//! use recoil::formats::csv::{Dialect, Reader};
+//! use recoil::prelude::Bytes;
+//!
+//! let input = Bytes::new(b"name,motto\nrecoil,\"parse, don't validate\"\n");
+//! let mut reader = Reader::new(input, Dialect::CSV);
+//! reader.read_headers().unwrap();
+//!
+//! let record = reader.next().unwrap().unwrap();
+//! let motto = record.get(reader.column(b"motto").unwrap()).unwrap();
+//! assert_eq!(motto.raw(), b"parse, don't validate");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use crate::collection::Bytes;
+use crate::error::{Error, ErrorKind, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::with_error;
+
+/// Error returned when parsing CSV fails, spanning the position of the
+/// failure.
+pub type CsvError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+/// The bytes that structure records.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Dialect {
+    /// Separates the fields of a record.
+    pub delimiter: u8,
+    /// Surrounds fields containing delimiters, quotes or line breaks.
+    pub quote: u8,
+    /// Escapes the next byte within quoted fields. When `None`, quotes are
+    /// escaped by doubling them, as specified by RFC 4180.
+    pub escape: Option<u8>,
+}
+
+impl Dialect {
+    /// Comma-separated values, as specified by RFC 4180.
+    pub const CSV: Self = Self {
+        delimiter: b',',
+        quote: b'"',
+        escape: None,
+    };
+
+    /// Tab-separated values.
+    pub const TSV: Self = Self {
+        delimiter: b'\t',
+        ..Self::CSV
+    };
+
+    /// Returns the byte that escapes quotes within quoted fields.
+    fn escape(&self) -> u8 {
+        self.escape.unwrap_or(self.quote)
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self::CSV
+    }
+}
+
+/// Returns the offset of the first byte of `haystack` that is one of
+/// `needles`, which holds one to three bytes.
+#[cfg(feature = "binary")]
+fn find(needles: &[u8], haystack: &[u8]) -> Option<usize> {
+    match *needles {
+        [a] => memchr::memchr(a, haystack),
+        [a, b] => memchr::memchr2(a, b, haystack),
+        [a, b, c] => memchr::memchr3(a, b, c, haystack),
+        _ => unreachable!("between one and three needles"),
+    }
+}
+
+#[cfg(not(feature = "binary"))]
+fn find(needles: &[u8], haystack: &[u8]) -> Option<usize> {
+    haystack.iter().position(|b| needles.contains(b))
+}
+
+/// A single field of a record.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Field<'a> {
+    raw: &'a [u8],
+    quoted: bool,
+    escape: u8,
+    quote: u8,
+}
+
+impl<'a> Field<'a> {
+    /// Returns the contents of the field, without surrounding quotes and with
+    /// escapes left in place.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn is_quoted(&self) -> bool {
+        self.quoted
+    }
+
+    /// Returns the contents of the field with escapes removed, allocating
+    /// only if the field contains any.
+    pub fn unescape(&self) -> Cow<'a, [u8]> {
+        if !self.quoted || !self.raw.contains(&self.escape) {
+            return Cow::Borrowed(self.raw);
+        }
+        let mut unescaped = Vec::with_capacity(self.raw.len());
+        let mut bytes = self.raw.iter();
+        while let Some(&byte) = bytes.next() {
+            // Both a doubled quote and an escaped byte stand for the second one.
+            let byte = if byte == self.escape {
+                bytes.next().copied().unwrap_or(byte)
+            } else {
+                byte
+            };
+            unescaped.push(byte);
+        }
+        Cow::Owned(unescaped)
+    }
+}
+
+/// Splits the first field from `rest`, returning it along with the offset of
+/// the next field, or the offset and description of the failure.
+fn split_field<'a>(
+    rest: &'a [u8],
+    dialect: &Dialect,
+) -> Result<(Field<'a>, Option<usize>), (usize, Failure)> {
+    let field = |raw, quoted| Field {
+        raw,
+        quoted,
+        escape: dialect.escape(),
+        quote: dialect.quote,
+    };
+    if rest.first() != Some(&dialect.quote) {
+        return Ok(match find(&[dialect.delimiter], rest) {
+            Some(end) => (field(&rest[..end], false), Some(end + 1)),
+            None => (field(rest, false), None),
+        });
+    }
+
+    let needles = [dialect.quote, dialect.escape()];
+    let needles = if dialect.escape.is_some() {
+        &needles[..]
+    } else {
+        &needles[..1]
+    };
+    let mut at = 1;
+    loop {
+        let Some(found) = find(needles, &rest[at..]) else {
+            return Err((0, Failure::Unterminated));
+        };
+        let index = at + found;
+        let escaped = rest[index] == dialect.escape()
+            && rest.get(index + 1).is_some()
+            && (dialect.escape.is_some() || rest[index + 1] == dialect.quote);
+        if escaped {
+            at = index + 2;
+            continue;
+        }
+        if rest[index] != dialect.quote {
+            return Err((0, Failure::Unterminated));
+        }
+        let raw = &rest[1..index];
+        return match rest.get(index + 1) {
+            None => Ok((field(raw, true), None)),
+            Some(&byte) if byte == dialect.delimiter => Ok((field(raw, true), Some(index + 2))),
+            Some(_) => Err((index + 1, Failure::AfterQuote)),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Failure {
+    Unterminated,
+    AfterQuote,
+}
+
+/// Iterator over the fields of a [`Record`].
+#[derive(Clone, Debug)]
+pub struct Fields<'a> {
+    rest: Option<&'a [u8]>,
+    dialect: Dialect,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Field<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.rest?;
+        // Records are validated when they are read.
+        let (field, next) = split_field(rest, &self.dialect).ok()?;
+        self.rest = next.map(|next| &rest[next..]);
+        Some(field)
+    }
+}
+
+/// A record, borrowing from the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Record<'a> {
+    raw: &'a [u8],
+    offset: usize,
+    dialect: Dialect,
+}
+
+impl<'a> Record<'a> {
+    /// Returns the record as it appears in the input, without its terminator.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Returns the offset of the record within the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn fields(&self) -> Fields<'a> {
+        Fields {
+            rest: Some(self.raw),
+            dialect: self.dialect,
+        }
+    }
+
+    /// Returns the field at `index`.
+    pub fn get(&self, index: usize) -> Option<Field<'a>> {
+        self.fields().nth(index)
+    }
+
+    /// Returns the number of fields.
+    pub fn len(&self) -> usize {
+        self.fields().count()
+    }
+
+    /// Returns `true` if the record has no fields, which never happens for
+    /// records read from the input.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Result of scanning for the end of a record.
+#[derive(Clone, Copy, Debug)]
+enum Scan {
+    /// The record ends with the newline at this offset.
+    End(usize),
+    /// The data ends before the record; scanning resumes at `at`.
+    Partial { at: usize, quoted: bool },
+}
+
+/// Scans `data` from `at` for the newline terminating a record.
+fn scan(data: &[u8], dialect: &Dialect, mut at: usize, mut quoted: bool) -> Scan {
+    loop {
+        if quoted {
+            let needles = [dialect.quote, dialect.escape()];
+            let needles = if dialect.escape.is_some() {
+                &needles[..]
+            } else {
+                &needles[..1]
+            };
+            let Some(found) = find(needles, &data[at..]) else {
+                return Scan::Partial {
+                    at: data.len(),
+                    quoted,
+                };
+            };
+            let index = at + found;
+            if data[index] == dialect.quote {
+                // A doubled quote leaves and re-enters the quoted field.
+                quoted = false;
+                at = index + 1;
+            } else if index + 1 < data.len() {
+                at = index + 2;
+            } else {
+                return Scan::Partial { at: index, quoted };
+            }
+        } else {
+            let Some(found) = find(&[dialect.quote, b'\n'], &data[at..]) else {
+                return Scan::Partial {
+                    at: data.len(),
+                    quoted,
+                };
+            };
+            let index = at + found;
+            if data[index] == b'\n' {
+                return Scan::End(index);
+            }
+            quoted = true;
+            at = index + 1;
+        }
+    }
+}
+
+/// Validates the record `raw`, which starts at `input`.
+fn record<'a>(
+    input: Located<&'a [u8]>,
+    raw: &'a [u8],
+    dialect: Dialect,
+) -> Result<Record<'a>, CsvError<'a>> {
+    let mut start = 0;
+    loop {
+        match split_field(&raw[start..], &dialect) {
+            Ok((_, Some(next))) => start += next,
+            Ok((_, None)) => break,
+            Err((at, failure)) => {
+                let at = input.over(start + at..input.byte_len());
+                return Err(match failure {
+                    Failure::Unterminated => Error::from_message(
+                        at,
+                        with_error!(ErrorKind::EndOfInput, "unterminated quoted field"),
+                    ),
+                    Failure::AfterQuote => {
+                        let expected = [
+                            Expected::Char(char::from(dialect.delimiter)),
+                            Expected::Rule("end of record"),
+                        ];
+                        super::expected(at, &expected)
+                    }
+                });
+            }
+        }
+    }
+    Ok(Record {
+        raw,
+        offset: input.offset(),
+        dialect,
+    })
+}
+
+/// Removes the `\r` of a `\r\n` terminator.
+fn trim_record(raw: &[u8]) -> &[u8] {
+    raw.strip_suffix(b"\r").unwrap_or(raw)
+}
+
+/// Iterator over the records of a complete input.
+///
+/// Iteration stops after the first error.
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    input: Located<&'a [u8]>,
+    dialect: Dialect,
+    headers: Option<Record<'a>>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: Bytes<'a>, dialect: Dialect) -> Self {
+        Self {
+            input: Located::new(input.inner()),
+            dialect,
+            headers: None,
+        }
+    }
+
+    /// Reads the next record as the header row, returning `None` if the input
+    /// is exhausted.
+    pub fn read_headers(&mut self) -> Result<Option<Record<'a>>, CsvError<'a>> {
+        self.headers = self.next().transpose()?;
+        Ok(self.headers)
+    }
+
+    /// Returns the header row, if it was read with [`Reader::read_headers`].
+    pub fn headers(&self) -> Option<Record<'a>> {
+        self.headers
+    }
+
+    /// Returns the index of the column whose header is `name`.
+    pub fn column(&self, name: &[u8]) -> Option<usize> {
+        self.headers?.fields().position(|field| field.raw() == name)
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Record<'a>, CsvError<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.input;
+            let data = start.fragment();
+            if data.is_empty() {
+                return None;
+            }
+            // An unterminated quoted field is reported by `record`.
+            let (end, next) = match scan(data, &self.dialect, 0, false) {
+                Scan::End(end) => (end, end + 1),
+                Scan::Partial { .. } => (data.len(), data.len()),
+            };
+            self.input = start.over(next..data.len());
+            let raw = trim_record(&data[..end]);
+            if raw.is_empty() {
+                continue;
+            }
+            let result = record(start, raw, self.dialect);
+            if result.is_err() {
+                self.input = start.over(data.len()..data.len());
+            }
+            return Some(result);
+        }
+    }
+}
+
+/// Outcome of [`Decoder::decode`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Decoded<'a> {
+    /// A complete record, along with the number of bytes it consumed from the
+    /// start of the buffer, including any preceding empty lines.
+    Record(Record<'a>, usize),
+    /// The buffer ends before the next record; more data is needed.
+    Incomplete,
+    /// The buffer holds no further records and no more data will arrive.
+    Finished,
+}
+
+/// Streaming record decoder for data that arrives in chunks.
+///
+/// The caller owns the buffer: after [`Decoded::Incomplete`], append more data
+/// and call [`Decoder::decode`] again; after [`Decoded::Record`], remove the
+/// consumed bytes from the front of the buffer. The decoder remembers how far
+/// it has scanned, so partial records are not scanned twice.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Decoder {
+    dialect: Dialect,
+    /// Start of the pending record, after any empty lines.
+    begin: usize,
+    resume: usize,
+    quoted: bool,
+}
+
+impl Decoder {
+    pub fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            ..Self::default()
+        }
+    }
+
+    /// Decodes the next record from the start of `buffer`.
+    ///
+    /// Set `eof` once no more data will be appended, so that a final record
+    /// without a terminator is returned. Offsets of records and errors are
+    /// relative to `buffer`.
+    pub fn decode<'b>(&mut self, buffer: &'b [u8], eof: bool) -> Result<Decoded<'b>, CsvError<'b>> {
+        loop {
+            let end = match scan(
+                buffer,
+                &self.dialect,
+                self.resume.max(self.begin),
+                self.quoted,
+            ) {
+                Scan::End(end) => end,
+                Scan::Partial { at, quoted } if !eof => {
+                    self.resume = at;
+                    self.quoted = quoted;
+                    return Ok(Decoded::Incomplete);
+                }
+                Scan::Partial { .. } if self.begin == buffer.len() => {
+                    *self = Self::new(self.dialect);
+                    return Ok(Decoded::Finished);
+                }
+                Scan::Partial { .. } => buffer.len(),
+            };
+            let begin = self.begin;
+            let raw = trim_record(&buffer[begin..end]);
+            let consumed = (end + 1).min(buffer.len());
+            *self = Self::new(self.dialect);
+            if raw.is_empty() {
+                self.begin = consumed;
+                continue;
+            }
+            let input = Located::new(buffer).over(begin..buffer.len());
+            return Ok(Decoded::Record(record(input, raw, self.dialect)?, consumed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(record: &Record<'_>) -> Vec<Vec<u8>> {
+        record
+            .fields()
+            .map(|field| field.unescape().into_owned())
+            .collect()
+    }
+
+    fn read_all(data: &[u8], dialect: Dialect) -> Result<Vec<Vec<Vec<u8>>>, CsvError<'_>> {
+        Reader::new(Bytes::new(data), dialect)
+            .map(|record| record.map(|record| fields(&record)))
+            .collect()
+    }
+
+    fn strings(rows: &[&[&str]]) -> Vec<Vec<Vec<u8>>> {
+        rows.iter()
+            .map(|row| row.iter().map(|field| field.as_bytes().to_vec()).collect())
+            .collect()
+    }
+
+    const SAMPLE: &[u8] = b"year,make,model,description\r\n\
+        1997,Ford,E350,\"ac, abs, moon\"\r\n\
+        \r\n\
+        1999,Chevy,\"Venture \"\"Extended Edition\"\"\",\"\"\r\n\
+        1996,Jeep,Grand Cherokee,\"MUST SELL!\nair, moon roof, loaded\"\n\
+        2000,,,";
+
+    #[test]
+    fn reads_rfc_4180_records() {
+        let rows = read_all(SAMPLE, Dialect::CSV).unwrap();
+        assert_eq!(
+            rows,
+            strings(&[
+                &["year", "make", "model", "description"],
+                &["1997", "Ford", "E350", "ac, abs, moon"],
+                &["1999", "Chevy", "Venture \"Extended Edition\"", ""],
+                &[
+                    "1996",
+                    "Jeep",
+                    "Grand Cherokee",
+                    "MUST SELL!\nair, moon roof, loaded"
+                ],
+                &["2000", "", "", ""],
+            ])
+        );
+
+        let tsv = read_all(b"a\tb,c\n\"d\te\"\tf\n", Dialect::TSV).unwrap();
+        assert_eq!(tsv, strings(&[&["a", "b,c"], &["d\te", "f"]]));
+
+        let escaped = Dialect {
+            delimiter: b';',
+            quote: b'\'',
+            escape: Some(b'\\'),
+        };
+        let rows = read_all(b"'it\\'s';'a\\\\b'\n", escaped).unwrap();
+        assert_eq!(rows, strings(&[&["it's", "a\\b"]]));
+    }
+
+    #[test]
+    fn fields_borrow_from_input() {
+        let mut reader = Reader::new(Bytes::new(SAMPLE), Dialect::CSV);
+        let headers = reader.read_headers().unwrap().unwrap();
+        assert_eq!(headers.len(), 4);
+        assert_eq!(reader.column(b"model"), Some(2));
+
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.offset(), 29);
+        let description = record.get(3).unwrap();
+        assert!(description.is_quoted());
+        assert!(matches!(
+            description.unescape(),
+            Cow::Borrowed(b"ac, abs, moon")
+        ));
+    }
+
+    #[test]
+    fn errors_span_the_failure() {
+        let error = read_all(b"a,b\n1,\"2\"x,3\n", Dialect::CSV).unwrap_err();
+        assert_eq!(error.offset(), Some((9, 9)));
+        assert_eq!(
+            error.to_string(),
+            "expected one of `,`, end of record, found `x`"
+        );
+
+        let error = read_all(b"a\n\"open,\nfield", Dialect::CSV).unwrap_err();
+        assert_eq!(error.offset(), Some((2, 2)));
+        assert_eq!(error.kind(), ErrorKind::EndOfInput);
+    }
+
+    #[test]
+    fn decodes_records_from_partial_buffers() {
+        let expected = read_all(SAMPLE, Dialect::CSV).unwrap();
+        for chunk in [1, 2, 7, SAMPLE.len()] {
+            let mut decoder = Decoder::new(Dialect::CSV);
+            let mut buffer = Vec::new();
+            let mut chunks = SAMPLE.chunks(chunk);
+            let mut rows = Vec::new();
+            loop {
+                let eof = chunks.len() == 0;
+                match decoder.decode(&buffer, eof).unwrap() {
+                    Decoded::Record(record, consumed) => {
+                        rows.push(fields(&record));
+                        buffer.drain(..consumed);
+                    }
+                    Decoded::Incomplete => buffer.extend_from_slice(chunks.next().unwrap()),
+                    Decoded::Finished => break,
+                }
+            }
+            assert_eq!(rows, expected, "chunks of {chunk} bytes");
+        }
+    }
+}
@@ -0,0 +1,1062 @@
+//! This module contains parsers for dates and times as written in ISO 8601,
+//! RFC 3339 and RFC 2822.
+//!
+//! The ISO 8601 parsers accept both the extended format, as in
+//! `2024-02-29T13:45:00+01:00`, and the basic format, as in
+//! `20240229T134500+0100`, and dates may be written as calendar dates,
+//! ordinal dates (`2024-060`) or week dates (`2024-W09-4`). [`rfc3339`] and
+//! [`rfc2822`] accept only their standard's profile, as found in logs and
+//! email headers.
+//!
+//! Each parser recognizes a value at the start of its input and returns the
+//! rest. Components outside of their range, such as month 13, fail with a
+//! `MalformedData` error. Components that are valid on their own but do not
+//! exist in the calendar, such as February 30 or week 53 of a 52-week year,
+//! fail with an `IncompatibleTypes` error. Both span the component.
+//!
+//! ```
+//! use recoil::prelude::ErrorKind;
+//! use recoil::formats::datetime::{self, Offset};
+//! use recoil::prelude::Located;
+//!
+//! let (_, stamp) = datetime::rfc3339(Located::new("2024-02-29T13:45:00Z")).unwrap();
+//! assert_eq!((stamp.date.month, stamp.date.day), (2, 29));
+//! assert_eq!(stamp.offset, Some(Offset::Utc));
+//! assert_eq!(stamp.unix_timestamp(), Some(1_709_214_300));
+//!
+//! let error = datetime::rfc3339(Located::new("2023-02-29T13:45:00Z")).unwrap_err();
+//! assert_eq!(error.kind(), ErrorKind::IncompatibleTypes);
+//! assert_eq!(error.offset(), Some((8, 10)));
+//! ```
+
+use core::fmt;
+
+use super::{days_in_month, is_leap_year, spanning};
+use crate::error::{ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::{with_error, AResult};
+
+/// Error returned when parsing a date or time fails, spanning the offending
+/// component.
+pub type DateTimeError<'a> = ErrorWithContext<Located<&'a str>>;
+
+type In<'a> = Located<&'a str>;
+type DateTimeResult<'a, O> = AResult<In<'a>, O, DateTimeError<'a>>;
+/// The offset following a component, and the component.
+type Step<'a, O> = Result<(usize, O), DateTimeError<'a>>;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+/// The obsolete North American zones of RFC 2822, with their offset in
+/// hours.
+const ZONES: [(&str, i16); 8] = [
+    ("EDT", -4),
+    ("EST", -5),
+    ("CDT", -5),
+    ("CST", -6),
+    ("MDT", -6),
+    ("MST", -7),
+    ("PDT", -7),
+    ("PST", -8),
+];
+
+/// A day of the week.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    /// Returns the ISO 8601 number of the day, from 1 for Monday to 7 for
+    /// Sunday.
+    pub fn number_from_monday(self) -> u8 {
+        self as u8 + 1
+    }
+
+    fn from_monday(number: u32) -> Option<Self> {
+        Self::ALL.get(number.checked_sub(1)? as usize).copied()
+    }
+}
+
+/// A date in the proleptic Gregorian calendar.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Creates a date, returning `None` if it does not exist in the
+    /// calendar.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        let valid = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+        valid.then_some(Self { year, month, day })
+    }
+
+    /// Returns the day of the year, starting from 1 for January 1.
+    pub fn ordinal(&self) -> u16 {
+        (self.days_since_epoch() - days_from_civil(self.year.into(), 1, 1)) as u16 + 1
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        weekday(self.days_since_epoch())
+    }
+
+    /// Returns the number of days from 1970-01-01 to the date.
+    pub fn days_since_epoch(&self) -> i64 {
+        days_from_civil(self.year.into(), self.month, self.day)
+    }
+
+    fn from_days(days: i64) -> Option<Self> {
+        let (year, month, day) = civil_from_days(days);
+        Some(Self {
+            year: u16::try_from(year).ok().filter(|&year| year <= 9999)?,
+            month,
+            day,
+        })
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// A date given as a week of an ISO 8601 week-numbering year, as in
+/// `2024-W09-4`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct WeekDate {
+    /// The week-numbering year, which differs from the calendar year for
+    /// some days around January 1.
+    pub year: u16,
+    pub week: u8,
+    pub weekday: Weekday,
+}
+
+impl WeekDate {
+    /// Returns the calendar date, or `None` if it falls outside of years
+    /// 0000 to 9999.
+    pub fn to_date(&self) -> Option<Date> {
+        let monday = first_monday(self.year);
+        let days = i64::from(self.week - 1) * 7 + i64::from(self.weekday.number_from_monday() - 1);
+        Date::from_days(monday + days)
+    }
+}
+
+/// A time of day.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    /// Seconds, which may be 60 during a leap second.
+    pub second: u8,
+    pub nanosecond: u32,
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+        if self.nanosecond == 0 {
+            return Ok(());
+        }
+        let mut nanosecond = self.nanosecond;
+        let mut width = 9;
+        while nanosecond.is_multiple_of(10) {
+            nanosecond /= 10;
+            width -= 1;
+        }
+        write!(f, ".{nanosecond:0width$}")
+    }
+}
+
+/// Offset of a time from UTC.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Offset {
+    /// UTC, written `Z`, `UT` or `GMT`.
+    Utc,
+    Fixed {
+        minutes: i16,
+    },
+}
+
+impl Offset {
+    /// Returns the offset from UTC in minutes, which is positive east of
+    /// Greenwich.
+    pub fn minutes(&self) -> i16 {
+        match *self {
+            Offset::Utc => 0,
+            Offset::Fixed { minutes } => minutes,
+        }
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Offset::Utc => f.write_str("Z"),
+            Offset::Fixed { minutes } => {
+                let sign = if minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.unsigned_abs();
+                write!(f, "{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+            }
+        }
+    }
+}
+
+/// A date and a time of day, with an optional offset from UTC.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DateTime {
+    pub date: Date,
+    pub time: Time,
+    /// The offset, which is `None` for local times.
+    pub offset: Option<Offset>,
+}
+
+impl DateTime {
+    /// Returns the number of seconds since 1970-01-01T00:00:00Z, or `None`
+    /// for local times.
+    pub fn unix_timestamp(&self) -> Option<i64> {
+        let offset = self.offset?;
+        let seconds = i64::from(self.time.hour) * 3600
+            + i64::from(self.time.minute) * 60
+            + i64::from(self.time.second);
+        Some(self.date.days_since_epoch() * 86400 + seconds - i64::from(offset.minutes()) * 60)
+    }
+}
+
+/// Renders the date-time as RFC 3339, or as its local-time variant when
+/// there is no offset.
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}T{}", self.date, self.time)?;
+        match self.offset {
+            Some(offset) => write!(f, "{offset}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An ISO 8601 duration, as in `P1Y2M10DT2H30M`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Duration {
+    pub years: u32,
+    pub months: u32,
+    pub weeks: u32,
+    pub days: u32,
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub nanoseconds: u32,
+}
+
+impl Duration {
+    /// Converts the duration to a [`core::time::Duration`], counting days as
+    /// 24 hours. Returns `None` if the duration has years or months, which
+    /// have no fixed length.
+    pub fn to_core(&self) -> Option<core::time::Duration> {
+        if self.years != 0 || self.months != 0 {
+            return None;
+        }
+        let days = u64::from(self.weeks) * 7 + u64::from(self.days);
+        let seconds = ((days * 24 + u64::from(self.hours)) * 60 + u64::from(self.minutes)) * 60
+            + u64::from(self.seconds);
+        Some(core::time::Duration::new(seconds, self.nanoseconds))
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to a date, which may be out of
+/// range for its month.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    // Days since March 1, which puts the leap day at the end of the year.
+    let day_of_year = (153 * ((i64::from(month) + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u8;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u8;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn weekday(days_since_epoch: i64) -> Weekday {
+    // 1970-01-01 was a Thursday.
+    Weekday::ALL[(days_since_epoch + 3).rem_euclid(7) as usize]
+}
+
+/// Returns the days since the epoch of the Monday starting week 1 of `year`,
+/// the week containing January 4.
+fn first_monday(year: u16) -> i64 {
+    let january_4 = days_from_civil(year.into(), 1, 4);
+    january_4 - i64::from(weekday(january_4).number_from_monday() - 1)
+}
+
+fn weeks_in_year(year: u16) -> u8 {
+    let january_1 = weekday(days_from_civil(year.into(), 1, 1));
+    match january_1 {
+        Weekday::Thursday => 53,
+        Weekday::Wednesday if is_leap_year(year) => 53,
+        _ => 52,
+    }
+}
+
+fn peek(input: In<'_>, at: usize) -> Option<u8> {
+    input.fragment().as_bytes().get(at).copied()
+}
+
+fn is_digit_at(input: In<'_>, at: usize) -> bool {
+    peek(input, at).is_some_and(|b| b.is_ascii_digit())
+}
+
+fn expected<'a>(input: In<'a>, at: usize, items: &[Expected]) -> DateTimeError<'a> {
+    super::expected(input.over(at..input.fragment().len()), items)
+}
+
+fn literal(input: In<'_>, at: usize, byte: u8) -> Result<usize, DateTimeError<'_>> {
+    match peek(input, at) {
+        Some(b) if b == byte => Ok(at + 1),
+        _ => Err(expected(input, at, &[Expected::Char(char::from(byte))])),
+    }
+}
+
+/// Parses exactly `count` digits.
+fn digits(input: In<'_>, at: usize, count: usize) -> Step<'_, u32> {
+    let mut value = 0;
+    for index in at..at + count {
+        if !is_digit_at(input, index) {
+            return Err(expected(input, index, &[Expected::Class("digit")]));
+        }
+        value = value * 10 + u32::from(peek(input, index).unwrap_or_default() - b'0');
+    }
+    Ok((at + count, value))
+}
+
+/// Fails with `message` spanning `at..end` unless `valid`.
+fn check<'a>(
+    input: In<'a>,
+    at: usize,
+    end: usize,
+    valid: bool,
+    message: ErrorMessage,
+) -> Result<(), DateTimeError<'a>> {
+    if valid {
+        Ok(())
+    } else {
+        Err(spanning(input, at, end, message))
+    }
+}
+
+fn validate_month(input: In<'_>, at: usize, month: u32) -> Result<u8, DateTimeError<'_>> {
+    let message = with_error!(ErrorKind::MalformedData, "month out of range");
+    check(input, at, at + 2, (1..=12).contains(&month), message)?;
+    Ok(month as u8)
+}
+
+fn validate_day(
+    input: In<'_>,
+    at: usize,
+    year: u16,
+    month: u8,
+    day: u32,
+) -> Result<Date, DateTimeError<'_>> {
+    let message = with_error!(ErrorKind::MalformedData, "day out of range");
+    check(input, at, at + 2, (1..=31).contains(&day), message)?;
+    let message = with_error!(
+        ErrorKind::IncompatibleTypes,
+        "day does not exist in the month"
+    );
+    check(
+        input,
+        at,
+        at + 2,
+        day as u8 <= days_in_month(year, month),
+        message,
+    )?;
+    Ok(Date {
+        year,
+        month,
+        day: day as u8,
+    })
+}
+
+fn date_at(input: In<'_>, start: usize) -> Step<'_, Date> {
+    let (mut at, year) = digits(input, start, 4)?;
+    let year = year as u16;
+    let extended = peek(input, at) == Some(b'-');
+    if extended {
+        at += 1;
+    }
+    if peek(input, at) == Some(b'W') {
+        let (end, week_date) = week_date_at(input, start)?;
+        // Only week 1 of year 0000 and week 52 or 53 of year 9999 leave the
+        // range of calendar years.
+        let Some(date) = week_date.to_date() else {
+            let message = with_error!(ErrorKind::IncompatibleTypes, "date out of range");
+            return Err(spanning(input, start, end, message));
+        };
+        return Ok((end, date));
+    }
+
+    let run = input.fragment().as_bytes()[at..]
+        .iter()
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    if run == 3 {
+        let (end, ordinal) = digits(input, at, 3)?;
+        let message = with_error!(ErrorKind::MalformedData, "day of the year out of range");
+        check(input, at, end, (1..=366).contains(&ordinal), message)?;
+        let message = with_error!(ErrorKind::IncompatibleTypes, "the year has only 365 days");
+        check(input, at, end, ordinal < 366 || is_leap_year(year), message)?;
+        let days = days_from_civil(year.into(), 1, 1) + i64::from(ordinal) - 1;
+        let (_, month, day) = civil_from_days(days);
+        return Ok((end, Date { year, month, day }));
+    }
+
+    let (end, month) = digits(input, at, 2)?;
+    let month = validate_month(input, at, month)?;
+    at = if extended {
+        literal(input, end, b'-')?
+    } else {
+        end
+    };
+    let (end, day) = digits(input, at, 2)?;
+    Ok((end, validate_day(input, at, year, month, day)?))
+}
+
+fn week_date_at(input: In<'_>, start: usize) -> Step<'_, WeekDate> {
+    let (mut at, year) = digits(input, start, 4)?;
+    let year = year as u16;
+    let extended = peek(input, at) == Some(b'-');
+    if extended {
+        at += 1;
+    }
+    at = literal(input, at, b'W')?;
+    let (end, week) = digits(input, at, 2)?;
+    let message = with_error!(ErrorKind::MalformedData, "week out of range");
+    check(input, at, end, (1..=53).contains(&week), message)?;
+    let message = with_error!(ErrorKind::IncompatibleTypes, "the year has only 52 weeks");
+    check(input, at, end, week as u8 <= weeks_in_year(year), message)?;
+    at = if extended {
+        literal(input, end, b'-')?
+    } else {
+        end
+    };
+    let (end, number) = digits(input, at, 1)?;
+    let Some(weekday) = Weekday::from_monday(number) else {
+        let message = with_error!(ErrorKind::MalformedData, "day of the week out of range");
+        return Err(spanning(input, at, end, message));
+    };
+    let week = week as u8;
+    Ok((
+        end,
+        WeekDate {
+            year,
+            week,
+            weekday,
+        },
+    ))
+}
+
+fn time_at(input: In<'_>, at: usize) -> Step<'_, Time> {
+    let (end, hour) = digits(input, at, 2)?;
+    let message = with_error!(ErrorKind::MalformedData, "hour out of range");
+    check(input, at, end, hour < 24, message)?;
+    let extended = peek(input, end) == Some(b':');
+    let at = if extended { end + 1 } else { end };
+    let (mut end, minute) = digits(input, at, 2)?;
+    let message = with_error!(ErrorKind::MalformedData, "minute out of range");
+    check(input, at, end, minute < 60, message)?;
+
+    let mut time = Time {
+        hour: hour as u8,
+        minute: minute as u8,
+        ..Time::default()
+    };
+    let at = if extended { end + 1 } else { end };
+    if (!extended || peek(input, end) == Some(b':')) && is_digit_at(input, at) {
+        let (after, second) = digits(input, at, 2)?;
+        let message = with_error!(ErrorKind::MalformedData, "second out of range");
+        check(input, at, after, second <= 60, message)?;
+        time.second = second as u8;
+        end = after;
+        if matches!(peek(input, end), Some(b'.' | b',')) && is_digit_at(input, end + 1) {
+            (end, time.nanosecond) = fraction(input, end + 1);
+        }
+    }
+    Ok((end, time))
+}
+
+/// Parses the digits of a decimal fraction as nanoseconds, ignoring digits
+/// past the ninth.
+fn fraction(input: In<'_>, mut at: usize) -> (usize, u32) {
+    let mut nanosecond = 0;
+    let mut scale = 100_000_000;
+    while let Some(digit @ b'0'..=b'9') = peek(input, at) {
+        nanosecond += u32::from(digit - b'0') * scale;
+        scale /= 10;
+        at += 1;
+    }
+    (at, nanosecond)
+}
+
+fn offset_at(input: In<'_>, at: usize) -> Step<'_, Offset> {
+    let sign = match peek(input, at) {
+        Some(b'Z' | b'z') => return Ok((at + 1, Offset::Utc)),
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => {
+            let items = [
+                Expected::Char('Z'),
+                Expected::Char('+'),
+                Expected::Char('-'),
+            ];
+            return Err(expected(input, at, &items));
+        }
+    };
+    let (mut end, hours) = digits(input, at + 1, 2)?;
+    let message = with_error!(ErrorKind::MalformedData, "offset hour out of range");
+    check(input, at + 1, end, hours < 24, message)?;
+    let mut minutes = 0;
+    let minutes_at = if peek(input, end) == Some(b':') {
+        end + 1
+    } else {
+        end
+    };
+    if is_digit_at(input, minutes_at) {
+        (end, minutes) = digits(input, minutes_at, 2)?;
+        let message = with_error!(ErrorKind::MalformedData, "offset minute out of range");
+        check(input, minutes_at, end, minutes < 60, message)?;
+    }
+    let minutes = sign * (hours * 60 + minutes) as i16;
+    Ok((end, Offset::Fixed { minutes }))
+}
+
+fn date_time_at(input: In<'_>, at: usize) -> Step<'_, DateTime> {
+    let (end, date) = date_at(input, at)?;
+    let at = match peek(input, end) {
+        Some(b'T' | b't') => end + 1,
+        Some(b' ') if is_digit_at(input, end + 1) => end + 1,
+        _ => return Err(expected(input, end, &[Expected::Char('T')])),
+    };
+    let (end, time) = time_at(input, at)?;
+    let (end, offset) = match peek(input, end) {
+        Some(b'Z' | b'z' | b'+' | b'-') => {
+            let (end, offset) = offset_at(input, end)?;
+            (end, Some(offset))
+        }
+        _ => (end, None),
+    };
+    Ok((end, DateTime { date, time, offset }))
+}
+
+fn finish<O>(input: In<'_>, (end, output): (usize, O)) -> (In<'_>, O) {
+    (input.over(end..input.fragment().len()), output)
+}
+
+/// Parses an ISO 8601 date, written as a calendar date (`2024-02-29`), an
+/// ordinal date (`2024-060`) or a week date (`2024-W09-4`), in either the
+/// extended or the basic format.
+pub fn date(input: In<'_>) -> DateTimeResult<'_, Date> {
+    date_at(input, 0).map(|step| finish(input, step))
+}
+
+/// Parses an ISO 8601 week date, as in `2024-W09-4` or `2024W094`.
+pub fn week_date(input: In<'_>) -> DateTimeResult<'_, WeekDate> {
+    week_date_at(input, 0).map(|step| finish(input, step))
+}
+
+/// Parses an ISO 8601 time of day, with optional seconds and an optional
+/// fraction of a second, as in `13:45`, `13:45:30.25` or `134530,25`.
+pub fn time(input: In<'_>) -> DateTimeResult<'_, Time> {
+    time_at(input, 0).map(|step| finish(input, step))
+}
+
+/// Parses an ISO 8601 offset from UTC: `Z`, or a sign followed by hours and
+/// optional minutes, as in `+01:00`, `+0100` or `+01`.
+pub fn offset(input: In<'_>) -> DateTimeResult<'_, Offset> {
+    offset_at(input, 0).map(|step| finish(input, step))
+}
+
+/// Parses an ISO 8601 date and time of day separated by `T`, with an
+/// optional offset from UTC.
+///
+/// A space is also accepted as the separator when followed by the time, as
+/// RFC 3339 allows.
+pub fn date_time(input: In<'_>) -> DateTimeResult<'_, DateTime> {
+    date_time_at(input, 0).map(|step| finish(input, step))
+}
+
+/// Checks that `input` matches `template` at `at`, where `9` stands for a
+/// digit and any other byte for itself.
+fn shape<'a>(input: In<'a>, at: usize, template: &str) -> Result<usize, DateTimeError<'a>> {
+    for (index, expected_byte) in template.bytes().enumerate() {
+        if expected_byte == b'9' {
+            digits(input, at + index, 1)?;
+        } else {
+            literal(input, at + index, expected_byte)?;
+        }
+    }
+    Ok(at + template.len())
+}
+
+/// Parses an RFC 3339 timestamp, as in `2024-02-29T13:45:00.5+01:00`.
+///
+/// Unlike [`date_time`], the seconds and the offset are required, and only
+/// the extended format is accepted.
+pub fn rfc3339(input: In<'_>) -> DateTimeResult<'_, DateTime> {
+    let at = shape(input, 0, "9999-99-99")?;
+    let at = match peek(input, at) {
+        Some(b'T' | b't' | b' ') => at + 1,
+        _ => return Err(expected(input, at, &[Expected::Char('T')])),
+    };
+    let mut at = shape(input, at, "99:99:99")?;
+    if peek(input, at) == Some(b'.') {
+        at = digits(input, at + 1, 1)?.0;
+        while is_digit_at(input, at) {
+            at += 1;
+        }
+    }
+    if !matches!(peek(input, at), Some(b'Z' | b'z')) {
+        offset_at(input, at)?;
+        shape(input, at + 1, "99:99")?;
+    }
+    let (end, stamp) = date_time_at(input, 0)?;
+    debug_assert!(stamp.offset.is_some());
+    Ok(finish(input, (end, stamp)))
+}
+
+fn skip_space(input: In<'_>, mut at: usize) -> usize {
+    while matches!(peek(input, at), Some(b' ' | b'\t')) {
+        at += 1;
+    }
+    at
+}
+
+/// Requires at least one space at `at`.
+fn space(input: In<'_>, at: usize) -> Result<usize, DateTimeError<'_>> {
+    match skip_space(input, at) {
+        end if end > at => Ok(end),
+        _ => Err(expected(input, at, &[Expected::Class("whitespace")])),
+    }
+}
+
+/// Returns the index in `names` of the three-letter name at `at`.
+fn name<'a>(input: In<'a>, at: usize, names: &[&str], rule: &'static str) -> Step<'a, usize> {
+    let text = input.fragment().get(at..at + 3).unwrap_or_default();
+    match names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(text))
+    {
+        Some(index) => Ok((at + 3, index)),
+        None => Err(expected(input, at, &[Expected::Rule(rule)])),
+    }
+}
+
+fn zone_at(input: In<'_>, at: usize) -> Step<'_, Offset> {
+    if matches!(peek(input, at), Some(b'+' | b'-')) {
+        shape(input, at + 1, "9999")?;
+        return offset_at(input, at);
+    }
+    let length = input.fragment().as_bytes()[at..]
+        .iter()
+        .take_while(|b| b.is_ascii_alphabetic())
+        .count();
+    let zone = &input.fragment()[at..at + length];
+    if ["UT", "GMT"]
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(zone))
+    {
+        return Ok((at + length, Offset::Utc));
+    }
+    match ZONES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(zone))
+    {
+        Some(&(_, hours)) => {
+            let minutes = hours * 60;
+            Ok((at + length, Offset::Fixed { minutes }))
+        }
+        None => Err(expected(input, at, &[Expected::Rule("time zone")])),
+    }
+}
+
+/// Parses an RFC 2822 date, as found in email headers, as in
+/// `Thu, 29 Feb 2024 13:45:00 +0100`.
+///
+/// The day of the week and the seconds are optional. Time zones may also be
+/// given by the obsolete names `UT`, `GMT` and the North American zones.
+/// A day of the week that does not match the date is an `IncompatibleTypes`
+/// error.
+pub fn rfc2822(input: In<'_>) -> DateTimeResult<'_, DateTime> {
+    let mut at = skip_space(input, 0);
+    let mut weekday = None;
+    if peek(input, at).is_some_and(|b| b.is_ascii_alphabetic()) {
+        let (end, index) = name(input, at, &WEEKDAYS, "day of the week")?;
+        weekday = Some((at, Weekday::ALL[index]));
+        at = skip_space(input, literal(input, end, b',')?);
+    }
+
+    let day_at = at;
+    let length = if is_digit_at(input, at + 1) { 2 } else { 1 };
+    let (end, day) = digits(input, at, length)?;
+    let at = space(input, end)?;
+    let (end, month) = name(input, at, &MONTHS, "month")?;
+    let at = space(input, end)?;
+    let (end, year) = digits(input, at, 4)?;
+    let month = month as u8 + 1;
+    let message = with_error!(ErrorKind::MalformedData, "day out of range");
+    check(
+        input,
+        day_at,
+        day_at + length,
+        (1..=31).contains(&day),
+        message,
+    )?;
+    let message = with_error!(
+        ErrorKind::IncompatibleTypes,
+        "day does not exist in the month"
+    );
+    let valid = day as u8 <= days_in_month(year as u16, month);
+    check(input, day_at, day_at + length, valid, message)?;
+    let date = Date {
+        year: year as u16,
+        month,
+        day: day as u8,
+    };
+    if let Some((at, weekday)) = weekday {
+        let message = with_error!(
+            ErrorKind::IncompatibleTypes,
+            "day of the week does not match the date"
+        );
+        check(input, at, at + 3, date.weekday() == weekday, message)?;
+    }
+
+    let at = space(input, end)?;
+    shape(input, at, "99:99")?;
+    let (end, time) = time_at(input, at)?;
+    let at = space(input, end)?;
+    let (end, offset) = zone_at(input, at)?;
+    let offset = Some(offset);
+    Ok(finish(input, (end, DateTime { date, time, offset })))
+}
+
+/// Parses an ISO 8601 duration, as in `P1Y2M10DT2H30M` or `PT0.5S`.
+///
+/// Components must appear in order and only the seconds may have a
+/// fraction.
+pub fn duration(input: In<'_>) -> DateTimeResult<'_, Duration> {
+    const DESIGNATORS: &[u8; 7] = b"YMWDHMS";
+
+    let mut at = literal(input, 0, b'P')?;
+    let mut values = [0; 7];
+    let mut nanoseconds = 0;
+    // Index of the next designator that may appear.
+    let mut next = 0;
+    let mut time = None;
+    loop {
+        if time.is_none() && peek(input, at) == Some(b'T') {
+            time = Some(at);
+            next = 4;
+            at += 1;
+        }
+        if !is_digit_at(input, at) {
+            if time.is_some_and(|time| time + 1 == at) {
+                return Err(expected(input, at, &[Expected::Class("digit")]));
+            }
+            break;
+        }
+        let start = at;
+        let mut value = 0u32;
+        while let Some(digit @ b'0'..=b'9') = peek(input, at) {
+            let digit = u32::from(digit - b'0');
+            let Some(next_value) = value.checked_mul(10).and_then(|v| v.checked_add(digit)) else {
+                let end = start
+                    + input.fragment().as_bytes()[start..]
+                        .iter()
+                        .take_while(|b| b.is_ascii_digit())
+                        .count();
+                let message =
+                    with_error!(ErrorKind::MalformedData, "duration component out of range");
+                return Err(spanning(input, start, end, message));
+            };
+            value = next_value;
+            at += 1;
+        }
+        let mut fraction_part = None;
+        if matches!(peek(input, at), Some(b'.' | b',')) && is_digit_at(input, at + 1) {
+            let (end, nanosecond) = fraction(input, at + 1);
+            fraction_part = Some(nanosecond);
+            at = end;
+        }
+
+        let last = if time.is_some() { 7 } else { 4 };
+        let designator = peek(input, at);
+        let Some(index) = (next..last).find(|&index| Some(DESIGNATORS[index]) == designator) else {
+            // `T` followed by the designators that may still appear.
+            let designators = &DESIGNATORS[next..last];
+            if time.is_some() && designators.is_empty() {
+                let message = with_error!(ErrorKind::MalformedData, "expected end of duration");
+                return Err(spanning(input, start, at, message));
+            }
+            let mut items = [Expected::Char('T'); 5];
+            for (item, &designator) in items[1..].iter_mut().zip(designators) {
+                *item = Expected::Char(char::from(designator));
+            }
+            let skip = usize::from(time.is_some());
+            return Err(expected(input, at, &items[skip..1 + designators.len()]));
+        };
+        if let Some(nanosecond) = fraction_part {
+            let message = with_error!(ErrorKind::MalformedData, "only seconds may have a fraction");
+            check(input, start, at, index == 6, message)?;
+            nanoseconds = nanosecond;
+        }
+        values[index] = value;
+        next = index + 1;
+        at += 1;
+    }
+    if next == 0 {
+        let message = with_error!(ErrorKind::MissingData, "duration has no components");
+        return Err(spanning(input, 0, at, message));
+    }
+
+    let [years, months, weeks, days, hours, minutes, seconds] = values;
+    let duration = Duration {
+        years,
+        months,
+        weeks,
+        days,
+        hours,
+        minutes,
+        seconds,
+        nanoseconds,
+    };
+    Ok(finish(input, (at, duration)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete<'a, O>(
+        parser: impl Fn(In<'a>) -> DateTimeResult<'a, O>,
+        text: &'a str,
+    ) -> Result<O, DateTimeError<'a>> {
+        let (rest, output) = parser(Located::new(text))?;
+        assert_eq!(rest.fragment(), "", "{text}");
+        Ok(output)
+    }
+
+    fn failure<O: fmt::Debug>(
+        parser: impl Fn(In<'_>) -> DateTimeResult<'_, O>,
+        text: &str,
+    ) -> (ErrorKind, (usize, usize)) {
+        let error = parser(Located::new(text)).unwrap_err();
+        (error.kind(), error.offset().unwrap())
+    }
+
+    fn ymd(year: u16, month: u8, day: u8) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(complete(date, "2024-02-29"), Ok(ymd(2024, 2, 29)));
+        assert_eq!(complete(date, "20240229"), Ok(ymd(2024, 2, 29)));
+        assert_eq!(complete(date, "2024-060"), Ok(ymd(2024, 2, 29)));
+        assert_eq!(complete(date, "2023365"), Ok(ymd(2023, 12, 31)));
+        assert_eq!(complete(date, "2024-W09-4"), Ok(ymd(2024, 2, 29)));
+        assert_eq!(complete(date, "2020W531"), Ok(ymd(2020, 12, 28)));
+        assert_eq!(complete(date, "2009-W01-1"), Ok(ymd(2008, 12, 29)));
+
+        use ErrorKind::*;
+        assert_eq!(failure(date, "2023-02-29"), (IncompatibleTypes, (8, 10)));
+        assert_eq!(failure(date, "1900-02-29"), (IncompatibleTypes, (8, 10)));
+        assert_eq!(failure(date, "2023-04-31"), (IncompatibleTypes, (8, 10)));
+        assert_eq!(failure(date, "2023-13-01"), (MalformedData, (5, 7)));
+        assert_eq!(failure(date, "2023-00-01"), (MalformedData, (5, 7)));
+        assert_eq!(failure(date, "2023-366"), (IncompatibleTypes, (5, 8)));
+        assert_eq!(failure(date, "2023-W53-1"), (IncompatibleTypes, (6, 8)));
+        assert_eq!(failure(date, "2023-W01-8"), (MalformedData, (9, 10)));
+
+        let error = date(Located::new("2023-1-01")).unwrap_err();
+        assert_eq!(error.to_string(), "expected digit, found `-`");
+    }
+
+    #[test]
+    fn computes_calendar_fields() {
+        let date = ymd(2024, 12, 31);
+        assert_eq!(date.ordinal(), 366);
+        assert_eq!(date.weekday(), Weekday::Tuesday);
+        assert_eq!(ymd(1970, 1, 1).days_since_epoch(), 0);
+        assert_eq!(ymd(1969, 12, 31).days_since_epoch(), -1);
+        assert_eq!(ymd(2000, 3, 1).days_since_epoch(), 11_017);
+        assert_eq!(Date::new(2100, 2, 29), None);
+
+        let week_date = complete(week_date, "2024W094").unwrap();
+        assert_eq!((week_date.week, week_date.weekday), (9, Weekday::Thursday));
+        assert_eq!(week_date.to_date(), Some(ymd(2024, 2, 29)));
+    }
+
+    #[test]
+    fn parses_times_and_offsets() {
+        let parsed = complete(time, "13:45:30.25").unwrap();
+        assert_eq!((parsed.hour, parsed.minute, parsed.second), (13, 45, 30));
+        assert_eq!(parsed.nanosecond, 250_000_000);
+        assert_eq!(parsed.to_string(), "13:45:30.25");
+        assert_eq!(complete(time, "134530,000000001").unwrap().nanosecond, 1);
+        assert_eq!(complete(time, "23:59:60").unwrap().second, 60);
+        assert_eq!(complete(time, "0730").unwrap().to_string(), "07:30:00");
+
+        let (rest, parsed) = time(Located::new("12:30: done")).unwrap();
+        assert_eq!((parsed.minute, rest.fragment()), (30, ": done"));
+
+        use ErrorKind::*;
+        assert_eq!(failure(time, "24:00"), (MalformedData, (0, 2)));
+        assert_eq!(failure(time, "12:60"), (MalformedData, (3, 5)));
+        assert_eq!(failure(time, "12:30:61"), (MalformedData, (6, 8)));
+
+        assert_eq!(complete(offset, "Z"), Ok(Offset::Utc));
+        let minutes = |text| complete(offset, text).unwrap().minutes();
+        assert_eq!(minutes("+05:30"), 330);
+        assert_eq!(minutes("-0800"), -480);
+        assert_eq!(minutes("+01"), 60);
+        assert_eq!(failure(offset, "+01:75"), (MalformedData, (4, 6)));
+        let error = offset(Located::new("01:00")).unwrap_err();
+        // Without `alloc`, errors keep a single expected item.
+        #[cfg(feature = "alloc")]
+        assert_eq!(
+            error.to_string(),
+            "expected one of `Z`, `+`, `-`, found `0`"
+        );
+        #[cfg(not(feature = "alloc"))]
+        assert_eq!(error.to_string(), "expected `Z`, found `0`");
+    }
+
+    #[test]
+    fn parses_date_times() {
+        let stamp = complete(date_time, "20240229T134500+0100").unwrap();
+        assert_eq!(stamp.to_string(), "2024-02-29T13:45:00+01:00");
+        assert_eq!(stamp.unix_timestamp(), Some(1_709_210_700));
+
+        let stamp = complete(date_time, "2024-02-29 13:45").unwrap();
+        assert_eq!((stamp.offset, stamp.unix_timestamp()), (None, None));
+
+        let (rest, stamp) = rfc3339(Located::new("1985-04-12T23:20:50.52Z level=info")).unwrap();
+        assert_eq!(stamp.to_string(), "1985-04-12T23:20:50.52Z");
+        assert_eq!(rest.fragment(), " level=info");
+        assert_eq!(
+            complete(rfc3339, "1996-12-19t16:39:57-08:00")
+                .unwrap()
+                .unix_timestamp(),
+            Some(851_042_397)
+        );
+
+        let error = rfc3339(Located::new("2024-02-29T13:45Z")).unwrap_err();
+        assert_eq!(error.to_string(), "expected `:`, found `Z`");
+        let error = rfc3339(Located::new("2024-02-29T13:45:00+0100")).unwrap_err();
+        assert_eq!(error.to_string(), "expected `:`, found `0`");
+        assert!(rfc3339(Located::new("2024-02-29T13:45:00")).is_err());
+        assert!(rfc3339(Located::new("20240229T134500Z")).is_err());
+    }
+
+    #[test]
+    fn parses_rfc2822_dates() {
+        let stamp = complete(rfc2822, "Thu, 29 Feb 2024 13:45:00 +0100").unwrap();
+        assert_eq!(stamp.to_string(), "2024-02-29T13:45:00+01:00");
+
+        let stamp = complete(rfc2822, "1 jan 2024 08:00 GMT").unwrap();
+        assert_eq!(stamp.to_string(), "2024-01-01T08:00:00Z");
+        let stamp = complete(rfc2822, "Mon,  1 Jan 2024 08:00:00 EST").unwrap();
+        assert_eq!(stamp.offset.unwrap().minutes(), -300);
+
+        use ErrorKind::*;
+        let text = "Fri, 29 Feb 2024 13:45:00 +0100";
+        assert_eq!(failure(rfc2822, text), (IncompatibleTypes, (0, 3)));
+        let text = "Thu, 30 Feb 2024 13:45:00 +0100";
+        assert_eq!(failure(rfc2822, text), (IncompatibleTypes, (5, 7)));
+        let error = rfc2822(Located::new("29 Fbr 2024 13:45 +0100")).unwrap_err();
+        assert_eq!(error.to_string(), "expected month, found `F`");
+        let error = rfc2822(Located::new("29 Feb 2024 13:45 CET")).unwrap_err();
+        assert_eq!(error.offset(), Some((18, 18)));
+    }
+
+    #[test]
+    fn parses_durations() {
+        let parsed = complete(duration, "P1Y2M10DT2H30M").unwrap();
+        let expected = Duration {
+            years: 1,
+            months: 2,
+            days: 10,
+            hours: 2,
+            minutes: 30,
+            ..Duration::default()
+        };
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.to_core(), None);
+
+        let parsed = complete(duration, "P1WT0.5S").unwrap();
+        let seconds = 7 * 86_400;
+        assert_eq!(
+            parsed.to_core(),
+            Some(core::time::Duration::new(seconds, 500_000_000))
+        );
+
+        let error = duration(Located::new("P1D2Y")).unwrap_err();
+        assert_eq!(error.to_string(), "expected `T`, found `Y`");
+        let error = duration(Located::new("P2X")).unwrap_err();
+        // Without `alloc`, errors keep a single expected item.
+        #[cfg(feature = "alloc")]
+        assert_eq!(
+            error.to_string(),
+            "expected one of `T`, `Y`, `M`, `W`, `D`, found `X`"
+        );
+        #[cfg(not(feature = "alloc"))]
+        assert_eq!(error.to_string(), "expected `T`, found `X`");
+        assert_eq!(failure(duration, "P"), (ErrorKind::MissingData, (0, 1)));
+        let error = duration(Located::new("PT")).unwrap_err();
+        assert_eq!(error.to_string(), "expected digit, found end of input");
+        assert_eq!(failure(duration, "P1.5D").1, (1, 4));
+        assert_eq!(failure(duration, "P99999999999D").1, (1, 12));
+        assert_eq!(
+            failure(duration, "PT1S2"),
+            (ErrorKind::MalformedData, (4, 5))
+        );
+        assert_eq!(failure(duration, "P1Y2M3DT4H5M6S7").1, (14, 15));
+    }
+}
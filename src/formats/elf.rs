@@ -0,0 +1,1122 @@
+//! This module contains a parser for ELF object files, executables and
+//! shared libraries.
+//!
+//! [`parse`] reads the file header of an ELF32 or ELF64 file in either byte
+//! order, and checks that its section and program header tables lie within
+//! the file. Table entries, section data, string tables and symbol tables are
+//! then read on demand from the returned [`Elf`], borrowing from the input.
+//!
+//! Every offset read from the file is checked before it is used. Errors
+//! raised for a table entry name its index and span the offending fields of
+//! the entry.
+//!
+//! ```
+//! # fn build() -> Vec<u8> {
+//! #     let mut file = vec![0; 64 + 2 * 64];
+//! #     file[..8].copy_from_slice(b"\x7FELF\x02\x01\x01\x00");
+//! #     file[16..20].copy_from_slice(&[1, 0, 0x3E, 0]);
+//! #     file[40] = 64;
+//! #     file[58..64].copy_from_slice(&[64, 0, 2, 0, 1, 0]);
+//! #     file.extend_from_slice(b"\0.shstrtab\0");
+//! #     let names = &mut file[128..192];
+//! #     names[0] = 1;
+//! #     names[4] = 3;
+//! #     names[24] = 192;
+//! #     names[32] = 11;
+//! #     file
+//! # }
+//! use recoil::formats::elf::{self, Class, Endian, SHT_STRTAB};
+//!
+//! // An empty relocatable file, with only the table of section names.
+//! let file = build();
+//! let elf = elf::parse(&file).unwrap();
+//! assert_eq!(elf.header().class, Class::Elf64);
+//! assert_eq!(elf.header().endian, Endian::Little);
+//!
+//! let names = elf.section_names().unwrap().unwrap();
+//! let sections: Vec<_> = elf
+//!     .sections()
+//!     .map(|section| names.get(section.name).unwrap())
+//!     .collect();
+//! assert_eq!(sections, [&b""[..], b".shstrtab"]);
+//!
+//! let table = elf.section_by_name(b".shstrtab").unwrap().unwrap();
+//! assert_eq!(table.kind, SHT_STRTAB);
+//! assert_eq!(elf.section_data(&table).unwrap(), b"\0.shstrtab\0");
+//! ```
+
+use core::ops::Range;
+
+use super::spanning;
+use crate::error::{ErrorKind, ErrorMessage, ErrorWithContext};
+use crate::located::Located;
+use crate::span::Span;
+use crate::with_error;
+
+/// Error returned when parsing fails, spanning the offending bytes.
+pub type ElfError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+type In<'a> = Located<&'a [u8]>;
+
+/// Section type of inactive section headers.
+pub const SHT_NULL: u32 = 0;
+/// Section type of data defined by the program.
+pub const SHT_PROGBITS: u32 = 1;
+/// Section type of the symbol table.
+pub const SHT_SYMTAB: u32 = 2;
+/// Section type of string tables.
+pub const SHT_STRTAB: u32 = 3;
+/// Section type of sections occupying no space in the file, such as `.bss`.
+pub const SHT_NOBITS: u32 = 8;
+/// Section type of the dynamic linking symbol table.
+pub const SHT_DYNSYM: u32 = 11;
+/// Segment type of loadable segments.
+pub const PT_LOAD: u32 = 1;
+/// Section index of undefined symbols, and of a missing section name table.
+pub const SHN_UNDEF: u16 = 0;
+
+/// Section index stored in the first section header when it does not fit
+/// the file header.
+const SHN_XINDEX: u16 = 0xFFFF;
+
+/// The size of addresses and offsets.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Class {
+    Elf32,
+    Elf64,
+}
+
+impl Class {
+    fn word(self) -> usize {
+        match self {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        }
+    }
+
+    fn program_header_size(self) -> usize {
+        match self {
+            Class::Elf32 => 32,
+            Class::Elf64 => 56,
+        }
+    }
+
+    fn section_header_size(self) -> usize {
+        16 + 6 * self.word()
+    }
+
+    fn symbol_size(self) -> usize {
+        match self {
+            Class::Elf32 => 16,
+            Class::Elf64 => 24,
+        }
+    }
+}
+
+/// The byte order of multi-byte values.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// The file header.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Header {
+    pub class: Class,
+    pub endian: Endian,
+    pub os_abi: u8,
+    pub abi_version: u8,
+    /// The object file type, such as 1 for relocatable files and 2 for
+    /// executables.
+    pub kind: u16,
+    pub machine: u16,
+    pub entry: u64,
+    pub flags: u32,
+    pub program_header_offset: u64,
+    pub program_header_size: u16,
+    pub program_header_count: u16,
+    pub section_header_offset: u64,
+    pub section_header_size: u16,
+    /// The number of section headers, or 0 if it is stored in the first
+    /// section header. See [`Elf::section_count`].
+    pub section_header_count: u16,
+    /// The index of the section name table, or [`SHN_UNDEF`], or `0xFFFF` if
+    /// it is stored in the first section header.
+    pub section_names_index: u16,
+}
+
+impl Header {
+    fn u16(&self, bytes: &[u8], at: usize) -> u16 {
+        let bytes = [bytes[at], bytes[at + 1]];
+        match self.endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8], at: usize) -> u32 {
+        let bytes = bytes[at..at + 4].try_into().unwrap();
+        match self.endian {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u64(&self, bytes: &[u8], at: usize) -> u64 {
+        let bytes = bytes[at..at + 8].try_into().unwrap();
+        match self.endian {
+            Endian::Little => u64::from_le_bytes(bytes),
+            Endian::Big => u64::from_be_bytes(bytes),
+        }
+    }
+
+    /// Reads an address or offset, whose size depends on the class.
+    fn word(&self, bytes: &[u8], at: usize) -> u64 {
+        match self.class {
+            Class::Elf32 => self.u32(bytes, at).into(),
+            Class::Elf64 => self.u64(bytes, at),
+        }
+    }
+
+    /// Returns the range of the `size` byte file header field at `at`, after
+    /// `words` addresses or offsets.
+    fn field(&self, at: usize, words: usize, size: usize) -> Range<usize> {
+        let start = at + words * self.class.word();
+        start..start + size
+    }
+}
+
+/// A section header.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SectionHeader {
+    index: usize,
+    /// The offset of the section name in the section name table.
+    pub name: u32,
+    /// The section type, such as [`SHT_SYMTAB`].
+    pub kind: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub alignment: u64,
+    pub entry_size: u64,
+}
+
+impl SectionHeader {
+    /// Returns the index of the section in the section header table.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// A program header, describing a segment.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ProgramHeader {
+    index: usize,
+    /// The segment type, such as [`PT_LOAD`].
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub virtual_address: u64,
+    pub physical_address: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub alignment: u64,
+}
+
+impl ProgramHeader {
+    /// Returns the index of the segment in the program header table.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// An entry of a symbol table.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Symbol {
+    index: usize,
+    /// The offset of the symbol name in the string table linked to the
+    /// symbol table.
+    pub name: u32,
+    pub value: u64,
+    pub size: u64,
+    pub info: u8,
+    pub other: u8,
+    /// The index of the section the symbol is defined in, or
+    /// [`SHN_UNDEF`].
+    pub section_index: u16,
+}
+
+impl Symbol {
+    /// Returns the index of the symbol in its symbol table.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the binding, such as 0 for local and 1 for global symbols.
+    pub fn binding(&self) -> u8 {
+        self.info >> 4
+    }
+
+    /// Returns the symbol type, such as 1 for objects and 2 for functions.
+    pub fn kind(&self) -> u8 {
+        self.info & 0xF
+    }
+}
+
+/// A parsed ELF file.
+#[derive(Clone, Copy, Debug)]
+pub struct Elf<'a> {
+    input: In<'a>,
+    header: Header,
+    section_count: usize,
+    section_names: usize,
+}
+
+/// Parses the file header of `input` and checks the bounds of its header
+/// tables.
+pub fn parse(input: &[u8]) -> Result<Elf<'_>, ElfError<'_>> {
+    let input = Located::new(input);
+    let bytes = input.fragment();
+    let error =
+        |range: Range<usize>, message| Err(spanning(input, range.start, range.end, message));
+    if !bytes.starts_with(b"\x7FELF") {
+        let message = with_error!(ErrorKind::MalformedData, "not an ELF file");
+        return error(0..bytes.len().min(4), message);
+    }
+    let truncated = || {
+        let message = with_error!(ErrorKind::EndOfInput, "truncated ELF header");
+        error(0..bytes.len(), message)
+    };
+    let Some(ident) = bytes.get(..16) else {
+        return truncated();
+    };
+    let class = match ident[4] {
+        1 => Class::Elf32,
+        2 => Class::Elf64,
+        other => {
+            let message = with_error!(ErrorKind::MalformedData, "invalid ELF class {other}");
+            return error(4..5, message);
+        }
+    };
+    let endian = match ident[5] {
+        1 => Endian::Little,
+        2 => Endian::Big,
+        other => {
+            let message = with_error!(ErrorKind::MalformedData, "invalid data encoding {other}");
+            return error(5..6, message);
+        }
+    };
+    if ident[6] != 1 {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "unsupported ELF version {}",
+            ident[6]
+        );
+        return error(6..7, message);
+    }
+    let w = class.word();
+    if bytes.len() < 40 + 3 * w {
+        return truncated();
+    }
+    let mut header = Header {
+        class,
+        endian,
+        os_abi: ident[7],
+        abi_version: ident[8],
+        kind: 0,
+        machine: 0,
+        entry: 0,
+        flags: 0,
+        program_header_offset: 0,
+        program_header_size: 0,
+        program_header_count: 0,
+        section_header_offset: 0,
+        section_header_size: 0,
+        section_header_count: 0,
+        section_names_index: 0,
+    };
+    header.kind = header.u16(bytes, 16);
+    header.machine = header.u16(bytes, 18);
+    header.entry = header.word(bytes, 24);
+    header.program_header_offset = header.word(bytes, 24 + w);
+    header.section_header_offset = header.word(bytes, 24 + 2 * w);
+    header.flags = header.u32(bytes, 24 + 3 * w);
+    header.program_header_size = header.u16(bytes, 30 + 3 * w);
+    header.program_header_count = header.u16(bytes, 32 + 3 * w);
+    header.section_header_size = header.u16(bytes, 34 + 3 * w);
+    header.section_header_count = header.u16(bytes, 36 + 3 * w);
+    header.section_names_index = header.u16(bytes, 38 + 3 * w);
+
+    if header.program_header_count > 0 {
+        let size = header.program_header_size;
+        if usize::from(size) < class.program_header_size() {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "program header size {size} is too small"
+            );
+            return error(header.field(30, 3, 2), message);
+        }
+        let count = header.program_header_count.into();
+        if within(bytes, header.program_header_offset, size.into(), count).is_none() {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "program header table is out of bounds"
+            );
+            return error(header.field(24, 1, w), message);
+        }
+    }
+
+    let mut elf = Elf {
+        input,
+        header,
+        section_count: 0,
+        section_names: 0,
+    };
+    if header.section_header_offset == 0 {
+        return Ok(elf);
+    }
+    let size = header.section_header_size;
+    if usize::from(size) < class.section_header_size() {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "section header size {size} is too small"
+        );
+        return error(header.field(34, 3, 2), message);
+    }
+    let out_of_bounds = || {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "section header table is out of bounds"
+        );
+        error(header.field(24, 2, w), message)
+    };
+    if within(bytes, header.section_header_offset, size.into(), 1).is_none() {
+        return out_of_bounds();
+    }
+    // Counts and indices too large for the file header are stored in the
+    // first section header.
+    let first = elf.read_section(0);
+    let count = match header.section_header_count {
+        0 => first.size,
+        count => count.into(),
+    };
+    let Some(count) = within(bytes, header.section_header_offset, size.into(), count) else {
+        return out_of_bounds();
+    };
+    let names = match header.section_names_index {
+        SHN_XINDEX => first.link as usize,
+        index => index.into(),
+    };
+    if names >= count && names != 0 {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "section name table index {names} is out of range"
+        );
+        return error(header.field(38, 3, 2), message);
+    }
+    elf.section_count = count;
+    elf.section_names = names;
+    Ok(elf)
+}
+
+/// Returns `count` as a `usize` if a table of `count` entries of `size`
+/// bytes at `offset` lies within `bytes`.
+fn within(bytes: &[u8], offset: u64, size: u64, count: u64) -> Option<usize> {
+    let end = size.checked_mul(count)?.checked_add(offset)?;
+    match end <= bytes.len() as u64 {
+        true => usize::try_from(count).ok(),
+        false => None,
+    }
+}
+
+/// Returns the range of `size` bytes at `offset` if it lies within `bytes`.
+fn range(bytes: &[u8], offset: u64, size: u64) -> Option<Range<usize>> {
+    let end = offset.checked_add(size)?;
+    match end <= bytes.len() as u64 {
+        true => Some(offset as usize..end as usize),
+        false => None,
+    }
+}
+
+impl<'a> Elf<'a> {
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the number of section headers, including the null section
+    /// at index 0.
+    pub fn section_count(&self) -> usize {
+        self.section_count
+    }
+
+    /// Returns the section header at `index`.
+    pub fn section(&self, index: usize) -> Option<SectionHeader> {
+        (index < self.section_count).then(|| self.read_section(index))
+    }
+
+    /// Returns an iterator over the section headers.
+    pub fn sections(&self) -> Sections<'a> {
+        Sections {
+            elf: *self,
+            next: 0,
+        }
+    }
+
+    /// Returns an iterator over the program headers.
+    pub fn program_headers(&self) -> ProgramHeaders<'a> {
+        ProgramHeaders {
+            elf: *self,
+            next: 0,
+        }
+    }
+
+    /// Returns the data of `section`, which is empty for [`SHT_NOBITS`]
+    /// sections.
+    pub fn section_data(&self, section: &SectionHeader) -> Result<&'a [u8], ElfError<'a>> {
+        if section.kind == SHT_NOBITS {
+            return Ok(&[]);
+        }
+        let bytes = self.input.fragment();
+        match range(bytes, section.offset, section.size) {
+            Some(range) => Ok(&bytes[range]),
+            None => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "section {}: data is out of bounds",
+                    section.index
+                );
+                let w = self.header.class.word();
+                Err(self.section_error(section, 8 + 2 * w..8 + 4 * w, message))
+            }
+        }
+    }
+
+    /// Returns the bytes of the segment described by `program`.
+    pub fn segment_data(&self, program: &ProgramHeader) -> Result<&'a [u8], ElfError<'a>> {
+        let w = self.header.class.word();
+        let (offset, file_size) = match self.header.class {
+            Class::Elf32 => (4..8, 16..20),
+            Class::Elf64 => (8..16, 32..40),
+        };
+        if program.file_size > program.memory_size {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "program header {}: file size exceeds memory size",
+                program.index
+            );
+            return Err(self.program_error(program, file_size.start..file_size.end + w, message));
+        }
+        let bytes = self.input.fragment();
+        match range(bytes, program.offset, program.file_size) {
+            Some(range) => Ok(&bytes[range]),
+            None => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "program header {}: segment is out of bounds",
+                    program.index
+                );
+                Err(self.program_error(program, offset, message))
+            }
+        }
+    }
+
+    /// Returns the section linked to `section`, such as the string table of
+    /// a symbol table.
+    pub fn link(&self, section: &SectionHeader) -> Result<SectionHeader, ElfError<'a>> {
+        self.section(section.link as usize).ok_or_else(|| {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "section {}: link {} is out of range",
+                section.index,
+                section.link
+            );
+            let at = 8 + 4 * self.header.class.word();
+            self.section_error(section, at..at + 4, message)
+        })
+    }
+
+    /// Returns the string table held by `section`.
+    pub fn string_table(&self, section: &SectionHeader) -> Result<StringTable<'a>, ElfError<'a>> {
+        if section.kind != SHT_STRTAB {
+            let message = with_error!(
+                ErrorKind::IncompatibleTypes,
+                "section {}: not a string table",
+                section.index
+            );
+            return Err(self.section_error(section, 4..8, message));
+        }
+        let data = self.section_data(section)?;
+        let start = data.as_ptr() as usize - self.input.fragment().as_ptr() as usize;
+        Ok(StringTable {
+            data: self.input.over(start..start + data.len()),
+            section: section.index,
+        })
+    }
+
+    /// Returns the table of section names, if the file has one.
+    pub fn section_names(&self) -> Result<Option<StringTable<'a>>, ElfError<'a>> {
+        match self.section_names {
+            0 => Ok(None),
+            index => self.string_table(&self.read_section(index)).map(Some),
+        }
+    }
+
+    /// Returns the first section named `name`.
+    pub fn section_by_name(&self, name: &[u8]) -> Result<Option<SectionHeader>, ElfError<'a>> {
+        let Some(names) = self.section_names()? else {
+            return Ok(None);
+        };
+        for section in self.sections() {
+            if names.get(section.name)? == name {
+                return Ok(Some(section));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns an iterator over the symbols of the symbol table held by
+    /// `section`.
+    pub fn symbols(&self, section: &SectionHeader) -> Result<Symbols<'a>, ElfError<'a>> {
+        let w = self.header.class.word();
+        if !matches!(section.kind, SHT_SYMTAB | SHT_DYNSYM) {
+            let message = with_error!(
+                ErrorKind::IncompatibleTypes,
+                "section {}: not a symbol table",
+                section.index
+            );
+            return Err(self.section_error(section, 4..8, message));
+        }
+        let entry_size = section.entry_size;
+        if entry_size < self.header.class.symbol_size() as u64 {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "section {}: symbol size {entry_size} is too small",
+                section.index
+            );
+            return Err(self.section_error(section, 16 + 5 * w..16 + 6 * w, message));
+        }
+        let data = self.section_data(section)?;
+        if !(data.len() as u64).is_multiple_of(entry_size) {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "section {}: size is not a multiple of the symbol size",
+                section.index
+            );
+            return Err(self.section_error(section, 8 + 3 * w..8 + 4 * w, message));
+        }
+        Ok(Symbols {
+            header: self.header,
+            data,
+            entry_size: entry_size as usize,
+            next: 0,
+        })
+    }
+
+    /// Reads the section header at `index`, which must be within the table.
+    fn read_section(&self, index: usize) -> SectionHeader {
+        let header = &self.header;
+        let w = header.class.word();
+        let start =
+            header.section_header_offset as usize + index * usize::from(header.section_header_size);
+        let bytes = &self.input.fragment()[start..];
+        SectionHeader {
+            index,
+            name: header.u32(bytes, 0),
+            kind: header.u32(bytes, 4),
+            flags: header.word(bytes, 8),
+            address: header.word(bytes, 8 + w),
+            offset: header.word(bytes, 8 + 2 * w),
+            size: header.word(bytes, 8 + 3 * w),
+            link: header.u32(bytes, 8 + 4 * w),
+            info: header.u32(bytes, 12 + 4 * w),
+            alignment: header.word(bytes, 16 + 4 * w),
+            entry_size: header.word(bytes, 16 + 5 * w),
+        }
+    }
+
+    /// Reads the program header at `index`, which must be within the table.
+    fn read_program(&self, index: usize) -> ProgramHeader {
+        let header = &self.header;
+        let start =
+            header.program_header_offset as usize + index * usize::from(header.program_header_size);
+        let bytes = &self.input.fragment()[start..];
+        match header.class {
+            Class::Elf32 => ProgramHeader {
+                index,
+                kind: header.u32(bytes, 0),
+                offset: header.word(bytes, 4),
+                virtual_address: header.word(bytes, 8),
+                physical_address: header.word(bytes, 12),
+                file_size: header.word(bytes, 16),
+                memory_size: header.word(bytes, 20),
+                flags: header.u32(bytes, 24),
+                alignment: header.word(bytes, 28),
+            },
+            Class::Elf64 => ProgramHeader {
+                index,
+                kind: header.u32(bytes, 0),
+                flags: header.u32(bytes, 4),
+                offset: header.word(bytes, 8),
+                virtual_address: header.word(bytes, 16),
+                physical_address: header.word(bytes, 24),
+                file_size: header.word(bytes, 32),
+                memory_size: header.word(bytes, 40),
+                alignment: header.word(bytes, 48),
+            },
+        }
+    }
+
+    /// Creates an error spanning the `field` bytes of a section header.
+    fn section_error(
+        &self,
+        section: &SectionHeader,
+        field: Range<usize>,
+        message: ErrorMessage,
+    ) -> ElfError<'a> {
+        let header = &self.header;
+        let start = header.section_header_offset as usize
+            + section.index * usize::from(header.section_header_size);
+        spanning(self.input, start + field.start, start + field.end, message)
+    }
+
+    /// Creates an error spanning the `field` bytes of a program header.
+    fn program_error(
+        &self,
+        program: &ProgramHeader,
+        field: Range<usize>,
+        message: ErrorMessage,
+    ) -> ElfError<'a> {
+        let header = &self.header;
+        let start = header.program_header_offset as usize
+            + program.index * usize::from(header.program_header_size);
+        spanning(self.input, start + field.start, start + field.end, message)
+    }
+}
+
+/// Iterator over the section headers of a file, created by
+/// [`Elf::sections`].
+#[derive(Clone, Debug)]
+pub struct Sections<'a> {
+    elf: Elf<'a>,
+    next: usize,
+}
+
+impl Iterator for Sections<'_> {
+    type Item = SectionHeader;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let section = self.elf.section(self.next)?;
+        self.next += 1;
+        Some(section)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.elf.section_count - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+/// Iterator over the program headers of a file, created by
+/// [`Elf::program_headers`].
+#[derive(Clone, Debug)]
+pub struct ProgramHeaders<'a> {
+    elf: Elf<'a>,
+    next: usize,
+}
+
+impl Iterator for ProgramHeaders<'_> {
+    type Item = ProgramHeader;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == usize::from(self.elf.header.program_header_count) {
+            return None;
+        }
+        self.next += 1;
+        Some(self.elf.read_program(self.next - 1))
+    }
+}
+
+/// Iterator over the entries of a symbol table, created by
+/// [`Elf::symbols`].
+#[derive(Clone, Debug)]
+pub struct Symbols<'a> {
+    header: Header,
+    data: &'a [u8],
+    entry_size: usize,
+    next: usize,
+}
+
+impl Iterator for Symbols<'_> {
+    type Item = Symbol;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = &self.header;
+        let bytes = self.data.get(self.next * self.entry_size..)?;
+        if bytes.is_empty() {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+        let symbol = match header.class {
+            Class::Elf32 => Symbol {
+                index,
+                name: header.u32(bytes, 0),
+                value: header.word(bytes, 4),
+                size: header.word(bytes, 8),
+                info: bytes[12],
+                other: bytes[13],
+                section_index: header.u16(bytes, 14),
+            },
+            Class::Elf64 => Symbol {
+                index,
+                name: header.u32(bytes, 0),
+                info: bytes[4],
+                other: bytes[5],
+                section_index: header.u16(bytes, 6),
+                value: header.word(bytes, 8),
+                size: header.word(bytes, 16),
+            },
+        };
+        Some(symbol)
+    }
+}
+
+/// A string table, holding null-terminated names.
+#[derive(Clone, Copy, Debug)]
+pub struct StringTable<'a> {
+    data: In<'a>,
+    section: usize,
+}
+
+impl<'a> StringTable<'a> {
+    /// Returns the string at `offset`, without its null terminator.
+    pub fn get(&self, offset: u32) -> Result<&'a [u8], ElfError<'a>> {
+        let bytes = self.data.fragment();
+        let offset = offset as usize;
+        let Some(tail) = bytes.get(offset..) else {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "string table in section {}: offset {offset} is out of bounds",
+                self.section
+            );
+            return Err(spanning(self.data, 0, bytes.len(), message));
+        };
+        match tail.iter().position(|&b| b == 0) {
+            Some(length) => Ok(&tail[..length]),
+            None => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "string table in section {}: unterminated string at offset {offset}",
+                    self.section
+                );
+                Err(spanning(self.data, offset, bytes.len(), message))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes values in the layout of a class and byte order.
+    struct Writer {
+        bytes: Vec<u8>,
+        class: Class,
+        endian: Endian,
+    }
+
+    impl Writer {
+        fn int(&mut self, value: u64, size: usize) {
+            let bytes = value.to_le_bytes();
+            let bytes = &bytes[..size];
+            match self.endian {
+                Endian::Little => self.bytes.extend(bytes),
+                Endian::Big => self.bytes.extend(bytes.iter().rev()),
+            }
+        }
+
+        fn word(&mut self, value: u64) {
+            self.int(value, self.class.word());
+        }
+    }
+
+    const TEXT: &[u8] = b"\x90\x90\xC3";
+    const STRINGS: &[u8] = b"\0main\0data\0";
+    const NAMES: &[u8] = b"\0.text\0.symtab\0.strtab\0.shstrtab\0.bss\0";
+
+    /// Builds an executable with a loadable segment holding `.text`, a
+    /// symbol table, its string table, the section name table and `.bss`.
+    fn build(class: Class, endian: Endian) -> Vec<u8> {
+        let w = class.word();
+        let header_size = 40 + 3 * w;
+        let program = header_size;
+        let text = program + class.program_header_size();
+        let strings = text + TEXT.len();
+        let symbols = strings + STRINGS.len();
+        let names = symbols + 2 * class.symbol_size();
+        let sections = names + NAMES.len();
+
+        let mut out = Writer {
+            bytes: Vec::new(),
+            class,
+            endian,
+        };
+        out.bytes.extend(b"\x7FELF");
+        out.bytes.push(if class == Class::Elf32 { 1 } else { 2 });
+        out.bytes.push(if endian == Endian::Little { 1 } else { 2 });
+        out.bytes.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        out.int(2, 2);
+        out.int(62, 2);
+        out.int(1, 4);
+        out.word(0x1000);
+        out.word(program as u64);
+        out.word(sections as u64);
+        out.int(0, 4);
+        for value in [header_size, class.program_header_size(), 1] {
+            out.int(value as u64, 2);
+        }
+        for value in [class.section_header_size(), 6, 4] {
+            out.int(value as u64, 2);
+        }
+
+        out.int(PT_LOAD.into(), 4);
+        if class == Class::Elf64 {
+            out.int(5, 4);
+        }
+        for value in [text as u64, 0x1000, 0x1000, 3, 3] {
+            out.word(value);
+        }
+        if class == Class::Elf32 {
+            out.int(5, 4);
+        }
+        out.word(0x1000);
+
+        out.bytes.extend(TEXT);
+        out.bytes.extend(STRINGS);
+        out.bytes.extend(vec![0; class.symbol_size()]);
+        out.int(1, 4);
+        if class == Class::Elf64 {
+            out.bytes.extend([0x12, 0]);
+            out.int(1, 2);
+        }
+        out.word(0x1000);
+        out.word(3);
+        if class == Class::Elf32 {
+            out.bytes.extend([0x12, 0]);
+            out.int(1, 2);
+        }
+        out.bytes.extend(NAMES);
+
+        let table = [
+            (0, SHT_NULL, 0, 0, 0, 0),
+            (1, SHT_PROGBITS, text, TEXT.len(), 0, 0),
+            (
+                7,
+                SHT_SYMTAB,
+                symbols,
+                2 * class.symbol_size(),
+                3,
+                class.symbol_size(),
+            ),
+            (15, SHT_STRTAB, strings, STRINGS.len(), 0, 0),
+            (23, SHT_STRTAB, names, NAMES.len(), 0, 0),
+            (33, SHT_NOBITS, 0xFFFF_FFFF, 0x100, 0, 0),
+        ];
+        for (name, kind, offset, size, link, entry_size) in table {
+            out.int(name, 4);
+            out.int(kind.into(), 4);
+            for value in [0, 0, offset as u64, size as u64] {
+                out.word(value);
+            }
+            out.int(link, 4);
+            out.int(0, 4);
+            out.word(1);
+            out.word(entry_size as u64);
+        }
+        out.bytes
+    }
+
+    fn failure(file: &[u8]) -> (String, (usize, usize)) {
+        error(parse(file))
+    }
+
+    fn error<T: core::fmt::Debug>(result: Result<T, ElfError<'_>>) -> (String, (usize, usize)) {
+        let error = result.unwrap_err();
+        (error.to_string(), error.offset().unwrap())
+    }
+
+    #[test]
+    fn parses_every_layout() {
+        for class in [Class::Elf32, Class::Elf64] {
+            for endian in [Endian::Little, Endian::Big] {
+                let file = build(class, endian);
+                let elf = parse(&file).unwrap();
+                let header = elf.header();
+                assert_eq!((header.class, header.endian), (class, endian));
+                assert_eq!((header.kind, header.machine, header.entry), (2, 62, 0x1000));
+                assert_eq!(elf.section_count(), 6);
+
+                let names = elf.section_names().unwrap().unwrap();
+                let sections: Vec<_> = elf.sections().map(|s| names.get(s.name).unwrap()).collect();
+                assert_eq!(
+                    sections[1..],
+                    [b".text", &b".symtab"[..], b".strtab", b".shstrtab", b".bss"]
+                );
+
+                let text = elf.section_by_name(b".text").unwrap().unwrap();
+                assert_eq!((text.index(), elf.section_data(&text).unwrap()), (1, TEXT));
+                let bss = elf.section(5).unwrap();
+                assert_eq!(elf.section_data(&bss), Ok(&b""[..]));
+                assert_eq!(elf.section_by_name(b".data"), Ok(None));
+
+                let symtab = elf.section(2).unwrap();
+                let strings = elf.string_table(&elf.link(&symtab).unwrap()).unwrap();
+                let symbols: Vec<_> = elf.symbols(&symtab).unwrap().collect();
+                assert_eq!(symbols.len(), 2);
+                let main = symbols[1];
+                assert_eq!(strings.get(main.name), Ok(&b"main"[..]));
+                assert_eq!((main.value, main.size, main.section_index), (0x1000, 3, 1));
+                assert_eq!((main.binding(), main.kind(), main.index()), (1, 2, 1));
+
+                let programs: Vec<_> = elf.program_headers().collect();
+                assert_eq!(programs.len(), 1);
+                assert_eq!((programs[0].kind, programs[0].flags), (PT_LOAD, 5));
+                assert_eq!(elf.segment_data(&programs[0]), Ok(TEXT));
+            }
+        }
+    }
+
+    #[test]
+    fn reads_extended_section_numbering() {
+        let mut file = build(Class::Elf64, Endian::Little);
+        let sections = u64::from_le_bytes(file[40..48].try_into().unwrap()) as usize;
+        file[60..64].copy_from_slice(&[0, 0, 0xFF, 0xFF]);
+        file[sections + 32..sections + 40].copy_from_slice(&6u64.to_le_bytes());
+        file[sections + 40..sections + 44].copy_from_slice(&4u32.to_le_bytes());
+        let elf = parse(&file).unwrap();
+        assert_eq!(elf.section_count(), 6);
+        assert!(elf.section_by_name(b".bss").unwrap().is_some());
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let file = build(Class::Elf32, Endian::Big);
+        assert_eq!(failure(b"MZ\x90\0"), ("not an ELF file".into(), (0, 4)));
+        assert_eq!(
+            failure(&file[..20]),
+            ("truncated ELF header".into(), (0, 20))
+        );
+
+        let mut corrupt = file.clone();
+        corrupt[4] = 3;
+        assert_eq!(failure(&corrupt), ("invalid ELF class 3".into(), (4, 5)));
+        let mut corrupt = file.clone();
+        corrupt[5] = 0;
+        assert_eq!(failure(&corrupt).1, (5, 6));
+        let mut corrupt = file.clone();
+        corrupt[6] = 2;
+        assert_eq!(failure(&corrupt).0, "unsupported ELF version 2");
+
+        // The program header table at 28 and the section header table at 32.
+        let mut corrupt = file.clone();
+        corrupt[28..32].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            failure(&corrupt),
+            ("program header table is out of bounds".into(), (28, 32))
+        );
+        let mut corrupt = file.clone();
+        corrupt[42..44].copy_from_slice(&20u16.to_be_bytes());
+        assert_eq!(
+            failure(&corrupt),
+            ("program header size 20 is too small".into(), (42, 44))
+        );
+        assert_eq!(
+            failure(&file[..file.len() - 1]),
+            ("section header table is out of bounds".into(), (32, 36))
+        );
+        let mut corrupt = file.clone();
+        corrupt[46..48].copy_from_slice(&0u16.to_be_bytes());
+        assert_eq!(failure(&corrupt).1, (46, 48));
+        let mut corrupt = file.clone();
+        corrupt[50..52].copy_from_slice(&6u16.to_be_bytes());
+        assert_eq!(
+            failure(&corrupt),
+            (
+                "section name table index 6 is out of range".into(),
+                (50, 52)
+            )
+        );
+    }
+
+    #[test]
+    fn identifies_malformed_entries() {
+        let mut file = build(Class::Elf64, Endian::Little);
+        let sections = u64::from_le_bytes(file[40..48].try_into().unwrap()) as usize;
+        // The offset of `.text`, and the link and symbol size of the symbol
+        // table.
+        let text = sections + 64;
+        file[text + 24..text + 32].copy_from_slice(&u64::MAX.to_le_bytes());
+        let symtab = sections + 2 * 64;
+        file[symtab + 40..symtab + 44].copy_from_slice(&9u32.to_le_bytes());
+        let elf = parse(&file).unwrap();
+
+        let section = elf.section(1).unwrap();
+        assert_eq!(
+            error(elf.section_data(&section)),
+            (
+                "section 1: data is out of bounds".into(),
+                (text + 24, text + 40)
+            )
+        );
+        assert_eq!(error(elf.symbols(&section)).1, (text + 4, text + 8));
+        assert_eq!(
+            error(elf.string_table(&section)).0,
+            "section 1: not a string table"
+        );
+
+        let symtab_header = elf.section(2).unwrap();
+        assert_eq!(
+            error(elf.link(&symtab_header)),
+            (
+                "section 2: link 9 is out of range".into(),
+                (symtab + 40, symtab + 44)
+            )
+        );
+        let mut small = symtab_header;
+        small.entry_size = 8;
+        assert_eq!(error(elf.symbols(&small)).1, (symtab + 56, symtab + 64));
+        let mut uneven = symtab_header;
+        uneven.size -= 1;
+        assert_eq!(
+            error(elf.symbols(&uneven)).0,
+            "section 2: size is not a multiple of the symbol size"
+        );
+
+        let strings = elf.string_table(&elf.section(3).unwrap()).unwrap();
+        assert_eq!(strings.get(6), Ok(&b"data"[..]));
+        assert_eq!(
+            error(strings.get(11)).0,
+            "string table in section 3: unterminated string at offset 11"
+        );
+        assert_eq!(
+            error(strings.get(12)).0,
+            "string table in section 3: offset 12 is out of bounds"
+        );
+        let names = elf.section_names().unwrap().unwrap();
+        let mut program = elf.program_headers().next().unwrap();
+        program.file_size = 4;
+        assert_eq!(
+            error(elf.segment_data(&program)).0,
+            "program header 0: file size exceeds memory size"
+        );
+        program.memory_size = 4;
+        program.offset = file.len() as u64;
+        assert_eq!(error(elf.segment_data(&program)).1, (72, 80));
+        assert_eq!(names.get(1), Ok(&b".text"[..]));
+    }
+}
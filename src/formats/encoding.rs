@@ -0,0 +1,527 @@
+//! This module contains decoders for the hexadecimal, base32 and base64
+//! encodings of RFC 4648.
+//!
+//! An [`Encoding`] selects the alphabet, whether padding is required,
+//! optional or forbidden, and whether whitespace, such as the line breaks of
+//! PEM files, is ignored. Decoding either writes into a caller buffer, which
+//! [`Encoding::decoded_len`] helps to size, or allocates.
+//!
+//! The `parse` methods decode the longest encoded prefix of their input and
+//! return the rest, for blobs embedded in other formats, while the `decode`
+//! methods require the whole input to be encoded. Errors point at the first
+//! invalid character.
+//!
+//! ```
+//! use recoil::formats::encoding::{Encoding, Padding};
+//!
+//! let mut buffer = [0; 16];
+//! let length = Encoding::BASE64.decode_into("aGVsbG8=", &mut buffer).unwrap();
+//! assert_eq!(&buffer[..length], b"hello");
+//!
+//! let unpadded = Encoding::BASE64_URL.with_padding(Padding::Forbidden);
+//! assert_eq!(unpadded.decode_into("_-8", &mut buffer), Ok(2));
+//!
+//! let error = Encoding::HEX.decode_into("c0ff ee", &mut buffer).unwrap_err();
+//! assert_eq!(error.offset(), Some((4, 5)));
+//! assert_eq!(error.to_string(), "invalid hexadecimal character");
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use super::spanning;
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::{with_error, AResult};
+
+/// Error returned when decoding fails, spanning the offending characters.
+pub type EncodingError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+type In<'a> = Located<&'a [u8]>;
+
+/// Marks bytes outside of an alphabet in the decoding tables.
+const INVALID: u8 = 0xFF;
+
+static HEX: [u8; 256] = table(b"0123456789ABCDEF", true);
+static BASE32: [u8; 256] = table(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567", true);
+static BASE64: [u8; 256] = table(
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+    false,
+);
+static BASE64_URL: [u8; 256] = table(
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+    false,
+);
+
+const fn table(alphabet: &[u8], ignore_case: bool) -> [u8; 256] {
+    let mut table = [INVALID; 256];
+    let mut index = 0;
+    while index < alphabet.len() {
+        let byte = alphabet[index];
+        table[byte as usize] = index as u8;
+        if ignore_case {
+            table[byte.to_ascii_lowercase() as usize] = index as u8;
+        }
+        index += 1;
+    }
+    table
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Alphabet {
+    Hex,
+    Base32,
+    Base64,
+    Base64Url,
+}
+
+/// Whether encoded data ends with `=` padding to a whole group of
+/// characters.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Padding {
+    Required,
+    /// Padding may be omitted, but must be complete if present.
+    Optional,
+    /// `=` is not part of the encoding.
+    Forbidden,
+}
+
+/// A binary-to-text encoding.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Encoding {
+    alphabet: Alphabet,
+    padding: Padding,
+    whitespace: bool,
+}
+
+impl Encoding {
+    /// Hexadecimal, with two case-insensitive digits per byte.
+    pub const HEX: Encoding = Encoding::new(Alphabet::Hex, Padding::Forbidden);
+    /// Base32 with the RFC 4648 alphabet, decoded case-insensitively.
+    pub const BASE32: Encoding = Encoding::new(Alphabet::Base32, Padding::Required);
+    /// Base64 with the standard alphabet, ending in `+` and `/`.
+    pub const BASE64: Encoding = Encoding::new(Alphabet::Base64, Padding::Required);
+    /// Base64 with the URL and filename safe alphabet, ending in `-` and
+    /// `_`.
+    pub const BASE64_URL: Encoding = Encoding::new(Alphabet::Base64Url, Padding::Required);
+
+    const fn new(alphabet: Alphabet, padding: Padding) -> Self {
+        Self {
+            alphabet,
+            padding,
+            whitespace: false,
+        }
+    }
+
+    /// Returns the encoding with the given padding rule. Hexadecimal has no
+    /// padding, so the rule has no effect on it.
+    pub const fn with_padding(mut self, padding: Padding) -> Self {
+        if !matches!(self.alphabet, Alphabet::Hex) {
+            self.padding = padding;
+        }
+        self
+    }
+
+    /// Returns the encoding ignoring ASCII whitespace between characters.
+    pub const fn ignoring_whitespace(mut self) -> Self {
+        self.whitespace = true;
+        self
+    }
+
+    /// Returns an upper bound of the number of bytes `length` encoded
+    /// characters decode to.
+    pub const fn decoded_len(&self, length: usize) -> usize {
+        length / 8 * self.bits() as usize + (length % 8) * self.bits() as usize / 8
+    }
+
+    const fn bits(&self) -> u32 {
+        match self.alphabet {
+            Alphabet::Hex => 4,
+            Alphabet::Base32 => 5,
+            Alphabet::Base64 | Alphabet::Base64Url => 6,
+        }
+    }
+
+    /// Returns the number of characters encoding a whole number of bytes.
+    fn group(&self) -> usize {
+        match self.alphabet {
+            Alphabet::Hex => 2,
+            Alphabet::Base32 => 8,
+            Alphabet::Base64 | Alphabet::Base64Url => 4,
+        }
+    }
+
+    fn table(&self) -> &'static [u8; 256] {
+        match self.alphabet {
+            Alphabet::Hex => &HEX,
+            Alphabet::Base32 => &BASE32,
+            Alphabet::Base64 => &BASE64,
+            Alphabet::Base64Url => &BASE64_URL,
+        }
+    }
+
+    fn character(&self) -> &'static str {
+        match self.alphabet {
+            Alphabet::Hex => "hexadecimal digit",
+            Alphabet::Base32 => "base32 character",
+            Alphabet::Base64 | Alphabet::Base64Url => "base64 character",
+        }
+    }
+
+    fn invalid(&self) -> ErrorMessage {
+        match self.alphabet {
+            Alphabet::Hex => with_error!(ErrorKind::MalformedData, "invalid hexadecimal character"),
+            Alphabet::Base32 => with_error!(ErrorKind::MalformedData, "invalid base32 character"),
+            Alphabet::Base64 | Alphabet::Base64Url => {
+                with_error!(ErrorKind::MalformedData, "invalid base64 character")
+            }
+        }
+    }
+
+    /// Decodes the longest encoded prefix of `input` into `out`, returning
+    /// the rest of the input and the number of bytes written.
+    pub fn parse_into<'a>(
+        &self,
+        input: In<'a>,
+        out: &mut [u8],
+    ) -> AResult<In<'a>, usize, EncodingError<'a>> {
+        let run = self.scan(input)?;
+        if out.len() < run.length {
+            let message = with_error!(ErrorKind::MalformedData, "output buffer too small");
+            return Err(spanning(input, 0, run.end, message));
+        }
+        self.decode_run(input, &run, &mut out[..run.length])?;
+        Ok((input.over(run.end..input.byte_len()), run.length))
+    }
+
+    /// Decodes the longest encoded prefix of `input`, returning the rest of
+    /// the input and the decoded bytes.
+    #[cfg(feature = "alloc")]
+    pub fn parse<'a>(&self, input: In<'a>) -> AResult<In<'a>, Vec<u8>, EncodingError<'a>> {
+        let run = self.scan(input)?;
+        let mut out = alloc::vec![0; run.length];
+        self.decode_run(input, &run, &mut out)?;
+        Ok((input.over(run.end..input.byte_len()), out))
+    }
+
+    /// Decodes `input` into `out`, returning the number of bytes written.
+    pub fn decode_into<'a, I>(
+        &self,
+        input: &'a I,
+        out: &mut [u8],
+    ) -> Result<usize, EncodingError<'a>>
+    where
+        I: AsRef<[u8]> + ?Sized,
+    {
+        let (rest, length) = self.parse_into(Located::new(input.as_ref()), out)?;
+        self.end(rest)?;
+        Ok(length)
+    }
+
+    /// Decodes `input`.
+    #[cfg(feature = "alloc")]
+    pub fn decode<'a, I>(&self, input: &'a I) -> Result<Vec<u8>, EncodingError<'a>>
+    where
+        I: AsRef<[u8]> + ?Sized,
+    {
+        let (rest, out) = self.parse(Located::new(input.as_ref()))?;
+        self.end(rest)?;
+        Ok(out)
+    }
+
+    /// Fails at the first character of `rest`, which the encoded prefix
+    /// stopped at.
+    fn end<'a>(&self, rest: In<'a>) -> Result<(), EncodingError<'a>> {
+        match rest.fragment().is_empty() {
+            true => Ok(()),
+            false => Err(spanning(rest, 0, 1, self.invalid())),
+        }
+    }
+
+    /// Finds the end of the encoded prefix of `input` and checks its length
+    /// and padding.
+    fn scan<'a>(&self, input: In<'a>) -> Result<Run, EncodingError<'a>> {
+        let table = self.table();
+        let bytes = input.fragment();
+        let padded = self.padding != Padding::Forbidden;
+        let mut run = Run::default();
+        // Offsets of the first and after the last `=`.
+        let mut padding = None;
+        for (at, &byte) in bytes.iter().enumerate() {
+            match byte {
+                _ if self.whitespace && byte.is_ascii_whitespace() => {}
+                b'=' if padded => {
+                    let start = padding.map_or(at, |(start, _)| start);
+                    padding = Some((start, at + 1));
+                }
+                _ if table[usize::from(byte)] == INVALID => break,
+                _ if padding.is_some() => {
+                    let message = with_error!(ErrorKind::MalformedData, "data after padding");
+                    return Err(spanning(input, at, at + 1, message));
+                }
+                _ => run.characters += 1,
+            }
+            run.end = at + 1;
+        }
+
+        let group = self.group();
+        let remainder = run.characters % group;
+        // Whether the last group may end after `remainder` characters.
+        let complete = match self.alphabet {
+            Alphabet::Hex => remainder == 0,
+            Alphabet::Base32 => matches!(remainder, 0 | 2 | 4 | 5 | 7),
+            Alphabet::Base64 | Alphabet::Base64Url => remainder != 1,
+        };
+        let end = padding.map_or(run.end, |(start, _)| start);
+        if !complete {
+            let at = input.over(end..bytes.len());
+            return Err(EncodingError::from_expected(
+                at,
+                Expected::Class(self.character()),
+            ));
+        }
+        let required = if remainder == 0 { 0 } else { group - remainder };
+        match padding {
+            None if self.padding == Padding::Required && required > 0 => {
+                let at = input.over(end..bytes.len());
+                Err(EncodingError::from_expected(at, Expected::Char('=')))
+            }
+            Some((start, finish))
+                if bytes[start..finish].iter().filter(|&&b| b == b'=').count() != required =>
+            {
+                let message = with_error!(ErrorKind::MalformedData, "invalid padding");
+                Err(spanning(input, start, finish, message))
+            }
+            _ => {
+                run.length = run.characters * self.bits() as usize / 8;
+                Ok(run)
+            }
+        }
+    }
+
+    /// Decodes the characters of `run` into `out`, which holds exactly the
+    /// decoded bytes.
+    fn decode_run<'a>(
+        &self,
+        input: In<'a>,
+        run: &Run,
+        out: &mut [u8],
+    ) -> Result<(), EncodingError<'a>> {
+        let table = self.table();
+        let bits = self.bits();
+        let mut buffer = 0u32;
+        let mut buffered = 0;
+        let mut written = 0;
+        let mut last = 0;
+        for (at, &byte) in input.fragment()[..run.end].iter().enumerate() {
+            let value = table[usize::from(byte)];
+            if value == INVALID {
+                continue;
+            }
+            buffer = buffer << bits | u32::from(value);
+            buffered += bits;
+            if buffered >= 8 {
+                buffered -= 8;
+                out[written] = (buffer >> buffered) as u8;
+                buffer &= (1 << buffered) - 1;
+                written += 1;
+            }
+            last = at;
+        }
+        if buffer != 0 {
+            // A canonical encoding leaves the unused bits of the last
+            // character zero.
+            let message = with_error!(ErrorKind::MalformedData, "non-zero trailing bits");
+            return Err(spanning(input, last, last + 1, message));
+        }
+        Ok(())
+    }
+}
+
+/// The encoded prefix of an input.
+#[derive(Default)]
+struct Run {
+    /// Offset after the prefix.
+    end: usize,
+    /// Number of characters of the alphabet in the prefix.
+    characters: usize,
+    /// Number of decoded bytes.
+    length: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The message and span of an error.
+    type Failure = (String, (usize, usize));
+
+    fn decode(encoding: Encoding, text: &str) -> Result<Vec<u8>, Failure> {
+        let mut buffer = [0; 64];
+        match encoding.decode_into(text, &mut buffer) {
+            Ok(length) => Ok(buffer[..length].to_vec()),
+            Err(error) => Err((error.to_string(), error.offset().unwrap())),
+        }
+    }
+
+    fn decoded(encoding: Encoding, text: &str) -> Vec<u8> {
+        decode(encoding, text).unwrap()
+    }
+
+    fn failure(encoding: Encoding, text: &str) -> Failure {
+        decode(encoding, text).unwrap_err()
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decoded(Encoding::HEX, "C0ffee00"), [0xC0, 0xFF, 0xEE, 0x00]);
+        assert!(decoded(Encoding::HEX, "").is_empty());
+        assert_eq!(
+            failure(Encoding::HEX, "c0f"),
+            (
+                "expected hexadecimal digit, found end of input".into(),
+                (3, 3)
+            )
+        );
+        assert_eq!(
+            failure(Encoding::HEX, "c0 ff"),
+            ("invalid hexadecimal character".into(), (2, 3))
+        );
+        assert_eq!(
+            decoded(Encoding::HEX.ignoring_whitespace(), " c0 ff\n"),
+            [0xC0, 0xFF]
+        );
+    }
+
+    #[test]
+    fn decodes_base32() {
+        let cases = [
+            ("", ""),
+            ("MY======", "f"),
+            ("MZXQ====", "fo"),
+            ("MZXW6===", "foo"),
+            ("MZXW6YQ=", "foob"),
+            ("MZXW6YTB", "fooba"),
+            ("mzxw6ytboi======", "foobar"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(
+                decoded(Encoding::BASE32, text),
+                expected.as_bytes(),
+                "{text}"
+            );
+        }
+        let unpadded = Encoding::BASE32.with_padding(Padding::Forbidden);
+        assert_eq!(decoded(unpadded, "MZXW6"), b"foo");
+        assert_eq!(
+            failure(Encoding::BASE32, "MZXW6=="),
+            ("invalid padding".into(), (5, 7))
+        );
+        assert_eq!(failure(Encoding::BASE32, "MZX=====").1, (3, 3));
+        assert_eq!(
+            failure(Encoding::BASE32, "MZXW1==="),
+            ("expected `=`, found `1`".into(), (4, 4))
+        );
+    }
+
+    #[test]
+    fn decodes_base64() {
+        let cases = [
+            ("", ""),
+            ("Zg==", "f"),
+            ("Zm8=", "fo"),
+            ("Zm9v", "foo"),
+            ("Zm9vYg==", "foob"),
+            ("Zm9vYmE=", "fooba"),
+            ("Zm9vYmFy", "foobar"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(
+                decoded(Encoding::BASE64, text),
+                expected.as_bytes(),
+                "{text}"
+            );
+        }
+
+        assert_eq!(decoded(Encoding::BASE64, "+/8="), [0xFB, 0xFF]);
+        assert_eq!(decoded(Encoding::BASE64_URL, "-_8="), [0xFB, 0xFF]);
+        assert_eq!(failure(Encoding::BASE64_URL, "+/8=").1, (0, 1));
+
+        let optional = Encoding::BASE64.with_padding(Padding::Optional);
+        assert_eq!(decoded(optional, "Zm8"), b"fo");
+        assert_eq!(decoded(optional, "Zm8="), b"fo");
+        assert_eq!(
+            failure(Encoding::BASE64, "Zm8"),
+            ("expected `=`, found end of input".into(), (3, 3))
+        );
+        let forbidden = Encoding::BASE64.with_padding(Padding::Forbidden);
+        assert_eq!(
+            failure(forbidden, "Zm8="),
+            ("invalid base64 character".into(), (3, 4))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_base64() {
+        assert_eq!(
+            failure(Encoding::BASE64, "Zm9vY"),
+            (
+                "expected base64 character, found end of input".into(),
+                (5, 5)
+            )
+        );
+        assert_eq!(
+            failure(Encoding::BASE64, "Zm9="),
+            ("non-zero trailing bits".into(), (2, 3))
+        );
+        assert_eq!(
+            failure(Encoding::BASE64, "Zg==Zg=="),
+            ("data after padding".into(), (4, 5))
+        );
+        assert_eq!(failure(Encoding::BASE64, "Zg===").1, (2, 5));
+        assert_eq!(failure(Encoding::BASE64, "Zm9v\nYmFy").1, (4, 5));
+
+        let mut small = [0; 2];
+        let error = Encoding::BASE64
+            .decode_into("Zm9v", &mut small)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "output buffer too small");
+        assert_eq!(Encoding::BASE64.decoded_len(4), 3);
+    }
+
+    #[test]
+    fn parses_embedded_blobs() {
+        let pem = "-----BEGIN DATA-----\nZm9v\nYmFy\n-----END DATA-----\n";
+        let body = Located::new(pem.as_bytes()).over(21..pem.len());
+        let encoding = Encoding::BASE64.ignoring_whitespace();
+        let mut buffer = [0; 8];
+        let (rest, length) = encoding.parse_into(body, &mut buffer).unwrap();
+        assert_eq!(&buffer[..length], b"foobar");
+        assert_eq!(rest.offset(), 31);
+        assert!(rest.fragment().starts_with(b"-----END"));
+
+        let truncated = Located::new(&b"Zm9v\nYmF"[..]);
+        let error = encoding.parse_into(truncated, &mut buffer).unwrap_err();
+        assert_eq!(error.offset(), Some((8, 8)));
+
+        let (rest, length) = Encoding::HEX
+            .parse_into(Located::new(&b"00ff\", \"next\""[..]), &mut buffer)
+            .unwrap();
+        assert_eq!(
+            (&buffer[..length], rest.fragment()),
+            (&[0, 255][..], &b"\", \"next\""[..])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn allocates_decoded_blobs() {
+        let encoding = Encoding::BASE64.ignoring_whitespace();
+        let (rest, data) = encoding.parse(Located::new(&b"Zm9v\nYmFy-"[..])).unwrap();
+        assert_eq!((&data[..], rest.fragment()), (&b"foobar"[..], &b"-"[..]));
+        let error = encoding.parse(Located::new(&b"Zm9v\nYmF"[..])).unwrap_err();
+        assert_eq!(error.offset(), Some((8, 8)));
+        assert_eq!(Encoding::BASE32.decode("MZXW6===").unwrap(), b"foo");
+    }
+}
@@ -0,0 +1,590 @@
+//! This module contains an allocation-free parser for HTTP/1.1 message heads,
+//! as specified by RFC 9112.
+//!
+//! [`parse_request`] and [`parse_response`] parse the start line and the
+//! header fields of a message into a header array provided by the caller.
+//! Both work on partial input: while the blank line ending the head has not
+//! been received, they return [`Status::Incomplete`] and should be called
+//! again once more data has arrived. The message body is left to the caller.
+//!
+//! Methods and field names are validated as tokens per RFC 9110. Lines may
+//! end with either `\r\n` or a bare `\n`, and obsolete line folding is
+//! rejected.
+//!
+//! ```
+//! use recoil::formats::http::{self, Header, Limits, Status, Version};
+//!
+//! let mut headers = [Header::EMPTY; 16];
+//! let input = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+//! let Status::Complete(request, length) =
+//!     http::parse_request(input, &mut headers, &Limits::default()).unwrap()
+//! else {
+//!     panic!("the head is complete");
+//! };
+//! assert_eq!((request.method, request.target), ("GET", "/index.html"));
+//! assert_eq!(request.version, Version::Http11);
+//! assert_eq!(request.headers[0].value, b"example.com");
+//! assert_eq!(length, input.len());
+//!
+//! let partial = &input[..20];
+//! let status = http::parse_request(partial, &mut headers, &Limits::default()).unwrap();
+//! assert!(matches!(status, Status::Incomplete));
+//! ```
+
+pub use super::Status;
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::with_error;
+
+/// Error returned when parsing an HTTP message head fails, spanning the
+/// position of the failure.
+pub type HttpError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+/// A header field, borrowing from the input.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Header<'a> {
+    pub name: &'a str,
+    /// The field value, without surrounding whitespace.
+    pub value: &'a [u8],
+}
+
+impl Header<'_> {
+    /// An empty header, for initializing header arrays.
+    pub const EMPTY: Header<'static> = Header {
+        name: "",
+        value: b"",
+    };
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+/// The head of a request.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Request<'h, 'b> {
+    pub method: &'b str,
+    pub target: &'b str,
+    pub version: Version,
+    /// The header fields, in the order they appear in the input.
+    pub headers: &'h [Header<'b>],
+}
+
+/// The head of a response.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Response<'h, 'b> {
+    pub version: Version,
+    pub code: u16,
+    /// The reason phrase, which may hold bytes outside of ASCII.
+    pub reason: &'b [u8],
+    /// The header fields, in the order they appear in the input.
+    pub headers: &'h [Header<'b>],
+}
+
+/// Size limits enforced while parsing, guarding against unbounded buffering.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Limits {
+    /// Maximum length of the head, including the blank line that ends it.
+    pub max_head_size: usize,
+    /// Maximum length of the start line and of each header line.
+    pub max_line_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_head_size: 64 * 1024,
+            max_line_size: 8 * 1024,
+        }
+    }
+}
+
+/// Parses the head of a request into `headers`.
+///
+/// Empty lines before the request line are skipped, as recommended by RFC
+/// 9112. Fails if the request has more header fields than `headers` holds.
+///
+/// The length of a complete head includes the blank line that ends it, so
+/// the body starts at that offset.
+pub fn parse_request<'h, 'b>(
+    input: &'b [u8],
+    headers: &'h mut [Header<'b>],
+    limits: &Limits,
+) -> Result<Status<Request<'h, 'b>>, HttpError<'b>> {
+    let source = Located::new(input);
+    let Some(mut lines) = head(source, limits)? else {
+        return Ok(Status::Incomplete);
+    };
+    let (at, line) = lines.next_line(limits)?;
+    let (method, rest) = token(source, at, line)?;
+    let rest = space(source, at + method.len(), rest)?;
+    let target_at = line.len() - rest.len();
+    let length = rest.iter().position(|&b| b == b' ').unwrap_or(rest.len());
+    let target = &rest[..length];
+    if target.is_empty() {
+        return Err(expected(
+            source,
+            at + target_at,
+            &[Expected::Rule("request target")],
+        ));
+    }
+    if let Some(index) = target.iter().position(|b| !b.is_ascii_graphic()) {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "invalid character in request target"
+        );
+        return Err(failure(source, at + target_at + index, message));
+    }
+    let rest = space(source, at + target_at + length, &rest[length..])?;
+    let version_at = at + line.len() - rest.len();
+    let version = version(source, version_at, rest)?;
+    end_of_line(source, version_at + 8, &rest[8..])?;
+
+    let count = fields(source, &mut lines, headers, limits)?;
+    let headers: &'h [Header<'b>] = headers;
+    let request = Request {
+        method: ascii(method),
+        target: ascii(target),
+        version,
+        headers: &headers[..count],
+    };
+    Ok(Status::Complete(request, lines.end))
+}
+
+/// Parses the head of a response into `headers`.
+///
+/// Fails if the response has more header fields than `headers` holds. As for
+/// requests, the body starts at the length of a complete head.
+pub fn parse_response<'h, 'b>(
+    input: &'b [u8],
+    headers: &'h mut [Header<'b>],
+    limits: &Limits,
+) -> Result<Status<Response<'h, 'b>>, HttpError<'b>> {
+    let source = Located::new(input);
+    let Some(mut lines) = head(source, limits)? else {
+        return Ok(Status::Incomplete);
+    };
+    let (at, line) = lines.next_line(limits)?;
+    let version = version(source, at, line)?;
+    let rest = space(source, at + 8, &line[8..])?;
+    let code_at = at + line.len() - rest.len();
+    let code = match rest.get(..3) {
+        Some(digits) if digits.iter().all(u8::is_ascii_digit) => digits
+            .iter()
+            .fold(0, |code, &digit| code * 10 + u16::from(digit - b'0')),
+        _ => {
+            let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+            return Err(expected(
+                source,
+                code_at + digits,
+                &[Expected::Class("digit")],
+            ));
+        }
+    };
+    // The space before an empty reason phrase is commonly omitted.
+    let reason = match &rest[3..] {
+        [] => &rest[3..],
+        [b' ', reason @ ..] => reason,
+        _ => return Err(expected(source, code_at + 3, &[Expected::Char(' ')])),
+    };
+    let reason_at = at + line.len() - reason.len();
+    if let Some(index) = reason.iter().position(|&b| !is_field_byte(b)) {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "invalid character in reason phrase"
+        );
+        return Err(failure(source, reason_at + index, message));
+    }
+
+    let count = fields(source, &mut lines, headers, limits)?;
+    let headers: &'h [Header<'b>] = headers;
+    let response = Response {
+        version,
+        code,
+        reason,
+        headers: &headers[..count],
+    };
+    Ok(Status::Complete(response, lines.end))
+}
+
+type In<'a> = Located<&'a [u8]>;
+
+fn failure(source: In<'_>, at: usize, message: ErrorMessage) -> HttpError<'_> {
+    Error::from_message(source.over(at..source.byte_len()), message)
+}
+
+fn expected<'a>(source: In<'a>, at: usize, items: &[Expected]) -> HttpError<'a> {
+    super::expected(source.over(at..source.byte_len()), items)
+}
+
+/// Returns `true` for the characters allowed in tokens by RFC 9110.
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Returns `true` for the bytes allowed in field values and reason phrases.
+fn is_field_byte(byte: u8) -> bool {
+    matches!(byte, b'\t' | b' '..=b'~' | 0x80..=0xFF)
+}
+
+/// Converts bytes that were validated to be ASCII to a string.
+fn ascii(bytes: &[u8]) -> &str {
+    core::str::from_utf8(bytes).expect("validated ASCII")
+}
+
+#[cfg(feature = "binary")]
+fn find_newline(haystack: &[u8]) -> Option<usize> {
+    memchr::memchr(b'\n', haystack)
+}
+
+#[cfg(not(feature = "binary"))]
+fn find_newline(haystack: &[u8]) -> Option<usize> {
+    haystack.iter().position(|&b| b == b'\n')
+}
+
+/// The lines of a complete head, as absolute offsets into the input.
+struct Lines<'a> {
+    input: &'a [u8],
+    at: usize,
+    /// Offset of the blank line ending the head.
+    blank: usize,
+    /// Offset just past the blank line.
+    end: usize,
+}
+
+impl<'a> Lines<'a> {
+    /// Returns the offset and contents of the next line, without its
+    /// terminator, or `None` once the blank line is reached.
+    fn next(&mut self, limits: &Limits) -> Result<Option<(usize, &'a [u8])>, HttpError<'a>> {
+        if self.at >= self.blank {
+            return Ok(None);
+        }
+        let at = self.at;
+        let length = find_newline(&self.input[at..]).expect("lines end before the blank line");
+        self.at += length + 1;
+        if length > limits.max_line_size {
+            let message = with_error!(ErrorKind::MalformedData, "line exceeds the size limit");
+            return Err(failure(Located::new(self.input), at, message));
+        }
+        let line = &self.input[at..at + length];
+        Ok(Some((at, line.strip_suffix(b"\r").unwrap_or(line))))
+    }
+
+    /// Returns the start line, which is never the blank line.
+    fn next_line(&mut self, limits: &Limits) -> Result<(usize, &'a [u8]), HttpError<'a>> {
+        match self.next(limits)? {
+            Some(line) => Ok(line),
+            None => Err(expected(
+                Located::new(self.input),
+                self.at,
+                &[Expected::Rule("start line")],
+            )),
+        }
+    }
+}
+
+/// Finds the blank line ending the head, returning its lines, or `None` if
+/// the input ends before it.
+fn head<'a>(source: In<'a>, limits: &Limits) -> Result<Option<Lines<'a>>, HttpError<'a>> {
+    let input = source.fragment();
+    let mut start = 0;
+    loop {
+        match &input[start..] {
+            [b'\r', b'\n', ..] => start += 2,
+            [b'\n', ..] => start += 1,
+            [b'\r'] | [] => return Ok(None),
+            _ => break,
+        }
+    }
+    let mut at = start;
+    loop {
+        let too_large = |at: usize| {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "message head exceeds the size limit"
+            );
+            Err(failure(source, at, message))
+        };
+        let Some(length) = find_newline(&input[at..]) else {
+            if input.len() - start > limits.max_head_size {
+                return too_large(start + limits.max_head_size);
+            }
+            return Ok(None);
+        };
+        at += length + 1;
+        let blank = match &input[at..] {
+            [b'\r', b'\n', ..] => Some(2),
+            [b'\n', ..] => Some(1),
+            _ => None,
+        };
+        if let Some(terminator) = blank {
+            if at + terminator - start > limits.max_head_size {
+                return too_large(start + limits.max_head_size);
+            }
+            return Ok(Some(Lines {
+                input,
+                at: start,
+                blank: at,
+                end: at + terminator,
+            }));
+        }
+    }
+}
+
+/// Splits a non-empty token from the start of `line`, which is at `at`.
+fn token<'a>(
+    source: In<'a>,
+    at: usize,
+    line: &'a [u8],
+) -> Result<(&'a [u8], &'a [u8]), HttpError<'a>> {
+    let length = line.iter().take_while(|&&b| is_token_byte(b)).count();
+    if length == 0 {
+        return Err(expected(source, at, &[Expected::Class("token character")]));
+    }
+    Ok(line.split_at(length))
+}
+
+/// Skips the single space at the start of `rest`, which is at `at`.
+fn space<'a>(source: In<'a>, at: usize, rest: &'a [u8]) -> Result<&'a [u8], HttpError<'a>> {
+    match rest {
+        [b' ', rest @ ..] => Ok(rest),
+        _ => Err(expected(source, at, &[Expected::Char(' ')])),
+    }
+}
+
+fn version<'a>(source: In<'a>, at: usize, rest: &[u8]) -> Result<Version, HttpError<'a>> {
+    match rest.get(..8) {
+        Some(b"HTTP/1.1") => Ok(Version::Http11),
+        Some(b"HTTP/1.0") => Ok(Version::Http10),
+        _ => Err(expected(
+            source,
+            at,
+            &[Expected::Tag("HTTP/1.0"), Expected::Tag("HTTP/1.1")],
+        )),
+    }
+}
+
+fn end_of_line<'a>(source: In<'a>, at: usize, rest: &[u8]) -> Result<(), HttpError<'a>> {
+    if rest.is_empty() {
+        Ok(())
+    } else {
+        Err(expected(source, at, &[Expected::Rule("end of line")]))
+    }
+}
+
+/// Parses the header fields into `headers`, returning their number.
+fn fields<'b>(
+    source: In<'b>,
+    lines: &mut Lines<'b>,
+    headers: &mut [Header<'b>],
+    limits: &Limits,
+) -> Result<usize, HttpError<'b>> {
+    let mut count = 0;
+    while let Some((at, line)) = lines.next(limits)? {
+        if matches!(line.first(), Some(b' ' | b'\t')) {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "obsolete line folding is not supported"
+            );
+            return Err(failure(source, at, message));
+        }
+        let (name, rest) = token(source, at, line)?;
+        let Some(rest) = rest.strip_prefix(b":") else {
+            return Err(expected(source, at + name.len(), &[Expected::Char(':')]));
+        };
+        let start = rest
+            .iter()
+            .take_while(|&&b| matches!(b, b' ' | b'\t'))
+            .count();
+        let end = rest
+            .iter()
+            .rposition(|&b| !matches!(b, b' ' | b'\t'))
+            .map_or(start, |index| index + 1);
+        let value = &rest[start..end];
+        if let Some(index) = value.iter().position(|&b| !is_field_byte(b)) {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "invalid character in header value"
+            );
+            return Err(failure(
+                source,
+                at + line.len() - rest.len() + start + index,
+                message,
+            ));
+        }
+        let Some(slot) = headers.get_mut(count) else {
+            let message = with_error!(ErrorKind::MalformedData, "too many headers");
+            return Err(failure(source, at, message));
+        };
+        *slot = Header {
+            name: ascii(name),
+            value,
+        };
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &[u8] = b"\r\nPOST /api/items?id=7 HTTP/1.1\r\n\
+        Host: example.com\r\n\
+        Content-Type:application/json \r\n\
+        X-Empty:\r\n\
+        \r\n\
+        {\"body\": true}";
+
+    #[test]
+    fn parses_requests() {
+        let mut headers = [Header::EMPTY; 4];
+        let status = parse_request(REQUEST, &mut headers, &Limits::default()).unwrap();
+        let Status::Complete(request, length) = status else {
+            panic!("expected a complete request");
+        };
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.target, "/api/items?id=7");
+        assert_eq!(request.version, Version::Http11);
+        assert_eq!(
+            request.headers,
+            [
+                Header {
+                    name: "Host",
+                    value: b"example.com"
+                },
+                Header {
+                    name: "Content-Type",
+                    value: b"application/json"
+                },
+                Header {
+                    name: "X-Empty",
+                    value: b""
+                },
+            ]
+        );
+        assert_eq!(&REQUEST[length..], b"{\"body\": true}");
+
+        let bare = b"GET / HTTP/1.0\nAccept: */*\n\n";
+        let status = parse_request(bare, &mut headers, &Limits::default()).unwrap();
+        assert!(matches!(
+            status,
+            Status::Complete(
+                Request {
+                    version: Version::Http10,
+                    ..
+                },
+                28
+            )
+        ));
+    }
+
+    #[test]
+    fn parses_responses() {
+        let mut headers = [Header::EMPTY; 4];
+        let input = b"HTTP/1.1 404 Not \xC3\xA9 Found\r\nContent-Length: 0\r\n\r\n";
+        let status = parse_response(input, &mut headers, &Limits::default()).unwrap();
+        let Status::Complete(response, length) = status else {
+            panic!("expected a complete response");
+        };
+        assert_eq!(
+            (response.code, response.reason),
+            (404, &b"Not \xC3\xA9 Found"[..])
+        );
+        assert_eq!(response.headers.len(), 1);
+        assert_eq!(length, input.len());
+
+        let input = b"HTTP/1.0 204\r\n\r\n";
+        let status = parse_response(input, &mut headers, &Limits::default()).unwrap();
+        assert!(matches!(
+            status,
+            Status::Complete(
+                Response {
+                    code: 204,
+                    reason: b"",
+                    ..
+                },
+                16
+            )
+        ));
+    }
+
+    #[test]
+    fn partial_input_is_incomplete() {
+        let mut headers = [Header::EMPTY; 4];
+        let end = REQUEST.len() - b"{\"body\": true}".len();
+        for length in 0..end {
+            let status = parse_request(&REQUEST[..length], &mut headers, &Limits::default());
+            assert!(matches!(status, Ok(Status::Incomplete)), "{length} bytes");
+        }
+        let status = parse_request(&REQUEST[..end], &mut headers, &Limits::default()).unwrap();
+        assert!(matches!(status, Status::Complete(_, length) if length == end));
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let limits = Limits {
+            max_head_size: 32,
+            max_line_size: 16,
+        };
+        let mut headers = [Header::EMPTY; 1];
+        let error = parse_request(
+            b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n",
+            &mut headers,
+            &limits,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "too many headers");
+        assert_eq!(error.offset(), Some((22, 22)));
+
+        let error =
+            parse_request(b"GET /a/long/path HTTP/1.1\r\n\r\n", &mut headers, &limits).unwrap_err();
+        assert_eq!(error.to_string(), "line exceeds the size limit");
+
+        // The limit applies before the end of the head is received.
+        let error = parse_request(&[b'x'; 40], &mut headers, &limits).unwrap_err();
+        assert_eq!(error.to_string(), "message head exceeds the size limit");
+        assert_eq!(error.offset(), Some((32, 32)));
+    }
+
+    #[test]
+    fn errors_span_the_failure() {
+        let mut headers = [Header::EMPTY; 4];
+        let mut parse = |input: &'static [u8]| {
+            let error = parse_request(input, &mut headers, &Limits::default()).unwrap_err();
+            (error.offset().unwrap().0, error.to_string())
+        };
+        assert_eq!(
+            parse(b"GET(x) / HTTP/1.1\r\n\r\n"),
+            (3, "expected ` `, found `(`".into())
+        );
+        assert_eq!(
+            parse(b"GET /\x7F HTTP/1.1\r\n\r\n"),
+            (5, "invalid character in request target".into())
+        );
+        // Without `alloc`, errors keep a single expected item.
+        #[cfg(feature = "alloc")]
+        assert_eq!(
+            parse(b"GET / HTTP/2.0\r\n\r\n"),
+            (
+                6,
+                "expected one of `HTTP/1.0`, `HTTP/1.1`, found `H`".into()
+            )
+        );
+        assert_eq!(
+            parse(b"GET / HTTP/1.1\r\nHost : a\r\n\r\n"),
+            (20, "expected `:`, found ` `".into())
+        );
+        assert_eq!(
+            parse(b"GET / HTTP/1.1\r\nA: b\r\n  c\r\n\r\n"),
+            (22, "obsolete line folding is not supported".into())
+        );
+        assert_eq!(
+            parse(b"GET / HTTP/1.1\r\nA: b\x00c\r\n\r\n"),
+            (20, "invalid character in header value".into())
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! This module contains a parser for INI configuration files.
+//!
+//! A file consists of `[section]` headers and `key = value` entries, which
+//! may also be written `key: value`. Entries before the first header belong
+//! to an unnamed global section. Lines starting with `;` or `#` are comments.
+//! A value continues on the following lines as long as they are indented, in
+//! which case the lines are joined with `\n`.
+//!
+//! Keys and section names are case-sensitive. Defining a key twice in a
+//! section, or a section twice in a file, is an error that points at both
+//! definitions.
+//!
+//! ```
+//! use recoil::formats::ini;
+//!
+//! let source = "[server]\nhost = example.com\nmotd = hello\n  world\n";
+//! let document = ini::parse(source).unwrap();
+//! assert_eq!(document.get("server", "host"), Some("example.com"));
+//! assert_eq!(document.get("server", "motd"), Some("hello\nworld"));
+//!
+//! let error = ini::parse("[a]\nx = 1\nx = 2\n").unwrap_err();
+//! assert_eq!(error.to_string(), "3:1: duplicate key `x` (first defined at 2:1)");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use super::SyntaxError;
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::{Span, StrSpan};
+use crate::with_error;
+
+/// An entry of a section.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IniEntry<'a> {
+    key: StrSpan<'a>,
+    value: Cow<'a, str>,
+    span: StrSpan<'a>,
+}
+
+impl<'a> IniEntry<'a> {
+    pub fn key(&self) -> &'a str {
+        self.key.as_str()
+    }
+
+    /// Returns the span of the key in the source.
+    pub fn key_span(&self) -> StrSpan<'a> {
+        self.key
+    }
+
+    /// Returns the value, with continuation lines joined by `\n`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the span of the value in the source, including any
+    /// continuation lines.
+    pub fn value_span(&self) -> StrSpan<'a> {
+        self.span
+    }
+}
+
+/// A section and its entries, in the order they appear in the input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IniSection<'a> {
+    name: Option<StrSpan<'a>>,
+    entries: Vec<IniEntry<'a>>,
+}
+
+impl<'a> IniSection<'a> {
+    /// Returns the name of the section, or `None` for the global section.
+    pub fn name(&self) -> Option<&'a str> {
+        self.name.map(|name| name.as_str())
+    }
+
+    /// Returns the span of the section name in the source.
+    pub fn name_span(&self) -> Option<StrSpan<'a>> {
+        self.name
+    }
+
+    pub fn entries(&self) -> &[IniEntry<'a>] {
+        &self.entries
+    }
+
+    /// Returns the entry for `key`.
+    pub fn entry(&self, key: &str) -> Option<&IniEntry<'a>> {
+        self.entries.iter().find(|entry| entry.key() == key)
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(IniEntry::value)
+    }
+}
+
+/// A parsed INI file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IniDocument<'a> {
+    /// The global section, followed by the named sections.
+    sections: Vec<IniSection<'a>>,
+}
+
+impl<'a> IniDocument<'a> {
+    /// Returns the unnamed section holding the entries before the first
+    /// header.
+    pub fn global(&self) -> &IniSection<'a> {
+        &self.sections[0]
+    }
+
+    /// Returns the named sections, in the order they appear in the input.
+    pub fn sections(&self) -> &[IniSection<'a>] {
+        &self.sections[1..]
+    }
+
+    /// Returns the section called `name`.
+    pub fn section(&self, name: &str) -> Option<&IniSection<'a>> {
+        self.sections()
+            .iter()
+            .find(|section| section.name() == Some(name))
+    }
+
+    /// Returns the value of `key` in the section called `section`.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section)?.get(key)
+    }
+}
+
+/// Error returned when parsing an INI file fails.
+pub type IniError<'a> = SyntaxError<'a>;
+
+type ParseError<'a> = ErrorWithContext<Located<&'a str>>;
+
+/// Parses an INI file.
+pub fn parse(input: &str) -> Result<IniDocument<'_>, IniError<'_>> {
+    let source = Located::new(input);
+    let at = |offset: usize| source.over(offset..input.len());
+    let fail = |offset, message: ErrorMessage| {
+        SyntaxError::new(ParseError::from_message(at(offset), message))
+    };
+    let expected =
+        |offset, items: &[Expected]| SyntaxError::new(super::expected(at(offset), items));
+    let span = |start: usize, end: usize| StrSpan::with_offsets(&input[start..end], start, end);
+
+    let mut sections = Vec::from([IniSection {
+        name: None,
+        entries: Vec::new(),
+    }]);
+    // Whether indented lines continue the last entry of the current section.
+    let mut continuing = false;
+    let mut begin = 0;
+    for raw in input.split_inclusive('\n') {
+        let offset = begin;
+        begin += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let indent = line.len() - line.trim_start().len();
+        let text = line.trim();
+        let start = offset + indent;
+        let end = start + text.len();
+
+        if text.is_empty() || text.starts_with([';', '#']) {
+            continuing = false;
+            continue;
+        }
+        let section = sections.last_mut().expect("the global section");
+        if indent > 0 && continuing {
+            let entry = section.entries.last_mut().expect("an entry to continue");
+            let value = entry.value.to_mut();
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(text);
+            entry.span = span(entry.span.start(), end);
+            continue;
+        }
+
+        if let Some(header) = text.strip_prefix('[') {
+            let Some(close) = header.find(']') else {
+                return Err(expected(end, &[Expected::Char(']')]));
+            };
+            let trailing = header[close + 1..].trim_start();
+            if !(trailing.is_empty() || trailing.starts_with([';', '#'])) {
+                let at = end - trailing.len();
+                return Err(expected(at, &[Expected::Rule("end of line")]));
+            }
+            let name = header[..close].trim();
+            let name_start = start + 1 + (close - header[..close].trim_start().len());
+            let name = span(name_start, name_start + name.len());
+            if name.is_empty() {
+                let message = with_error!(ErrorKind::MissingData, "empty section name");
+                return Err(fail(start, message));
+            }
+            let first = sections
+                .iter()
+                .find_map(|section| section.name.filter(|first| first.as_str() == name.as_str()));
+            if let Some(first) = first {
+                return Err(duplicate(fail, "section", name, at(first.start())));
+            }
+            sections.push(IniSection {
+                name: Some(name),
+                entries: Vec::new(),
+            });
+            continuing = false;
+            continue;
+        }
+
+        let Some(separator) = text.find(['=', ':']) else {
+            return Err(expected(end, &[Expected::Char('='), Expected::Char(':')]));
+        };
+        let key = text[..separator].trim_end();
+        if key.is_empty() {
+            let message = with_error!(ErrorKind::MissingData, "empty key");
+            return Err(fail(start, message));
+        }
+        let key = span(start, start + key.len());
+        if let Some(first) = section.entry(key.as_str()) {
+            let first = at(first.key.start());
+            return Err(duplicate(fail, "key", key, first));
+        }
+        let value = text[separator + 1..].trim_start();
+        let value_start = end - value.len();
+        section.entries.push(IniEntry {
+            key,
+            value: Cow::Borrowed(value),
+            span: span(value_start, end),
+        });
+        continuing = true;
+    }
+    Ok(IniDocument { sections })
+}
+
+fn duplicate<'a>(
+    fail: impl Fn(usize, ErrorMessage) -> SyntaxError<'a>,
+    what: &str,
+    name: StrSpan<'a>,
+    first: Located<&'a str>,
+) -> SyntaxError<'a> {
+    let message = with_error!(
+        ErrorKind::MalformedData,
+        "duplicate {what} `{}`",
+        name.as_str()
+    );
+    fail(name.start(), message).with_previous(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "; global settings\n\
+        name = demo\n\
+        \n\
+        [database]\n\
+        url: postgres://localhost/demo\n\
+        options = a=1\n    b=2\n\n\
+        # trailing comment\n\
+        [ empty ] ; no entries\n\
+        \t[indented]\n\
+        key=\n";
+
+    #[test]
+    fn parses_sections_and_entries() {
+        let document = parse(SOURCE).unwrap();
+        assert_eq!(document.global().get("name"), Some("demo"));
+        assert_eq!(
+            document.get("database", "url"),
+            Some("postgres://localhost/demo")
+        );
+        assert_eq!(document.get("database", "options"), Some("a=1\nb=2"));
+        assert_eq!(document.get("indented", "key"), Some(""));
+
+        let names: Vec<_> = document.sections().iter().map(IniSection::name).collect();
+        assert_eq!(names, [Some("database"), Some("empty"), Some("indented")]);
+        assert!(document.section("empty").unwrap().entries().is_empty());
+    }
+
+    #[test]
+    fn spans_point_into_the_source() {
+        let document = parse(SOURCE).unwrap();
+        let section = document.section("database").unwrap();
+        let name = section.name_span().unwrap();
+        assert_eq!(&SOURCE[name.start()..name.end()], "database");
+
+        let entry = section.entry("options").unwrap();
+        assert_eq!(
+            &SOURCE[entry.key_span().start()..entry.key_span().end()],
+            "options"
+        );
+        let value = entry.value_span();
+        assert_eq!(&SOURCE[value.start()..value.end()], "a=1\n    b=2");
+        assert!(matches!(
+            section.entry("url").unwrap().value,
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn duplicates_point_at_both_definitions() {
+        let error = parse("[a]\nx = 1\n[b]\nx = 2\n[a]\n").unwrap_err();
+        assert_eq!((error.line(), error.column()), (5, 2));
+        let previous = error.previous().unwrap();
+        assert_eq!((previous.line(), previous.column()), (1, 2));
+        assert_eq!(
+            error.to_string(),
+            "5:2: duplicate section `a` (first defined at 1:2)"
+        );
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let error = parse("[a\n").unwrap_err();
+        assert_eq!(error.to_string(), "1:3: expected `]`, found `\n`");
+
+        let error = parse("[a]\nflag\n").unwrap_err();
+        assert_eq!(error.offset(), 8);
+        assert_eq!(
+            error.to_string(),
+            "2:5: expected one of `=`, `:`, found `\n`"
+        );
+
+        let error = parse("[a] b\n").unwrap_err();
+        assert_eq!(error.to_string(), "1:5: expected end of line, found `b`");
+
+        let error = parse("= 1\n").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MissingData);
+    }
+}
@@ -0,0 +1,549 @@
+//! This module contains a zero-copy JSON parser, as specified by RFC 8259.
+//!
+//! Values borrow from the input wherever possible. Strings are only allocated
+//! when they contain escape sequences, and numbers keep their original text so
+//! that no precision is lost before the caller decides how to interpret them.
+//!
+//! ```
+//! use recoil::formats::json::{self, JsonValue};
+//!
+//! let value = json::parse(r#"{"name": "recoil", "tags": ["parser", "zero-copy"]}"#).unwrap();
+//! assert_eq!(value.get("name").and_then(JsonValue::as_str), Some("recoil"));
+//!
+//! let error = json::parse("[1, 2}").unwrap_err();
+//! assert_eq!(error.to_string(), "1:6: expected one of `,`, `]`, found `}`");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::SyntaxError;
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::stateful::Stateful;
+use crate::{with_error, AResult};
+
+/// Maximum nesting depth of arrays and objects used by [`parse`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// A JSON value borrowing from the input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue<'a> {
+    Null,
+    Bool(bool),
+    Number(JsonNumber<'a>),
+    String(Cow<'a, str>),
+    Array(Vec<JsonValue<'a>>),
+    /// Members of an object, in the order they appear in the input.
+    ///
+    /// Duplicate keys are preserved; [`JsonValue::get`] returns the last one.
+    Object(Vec<(Cow<'a, str>, JsonValue<'a>)>),
+}
+
+impl<'a> JsonValue<'a> {
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<JsonNumber<'a>> {
+        match self {
+            JsonValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue<'a>]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(Cow<'a, str>, JsonValue<'a>)]> {
+        match self {
+            JsonValue::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the member `key`, if this is an object.
+    pub fn get(&self, key: &str) -> Option<&JsonValue<'a>> {
+        self.as_object()?
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// A JSON number, kept as its original text.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct JsonNumber<'a>(&'a str);
+
+impl<'a> JsonNumber<'a> {
+    /// Returns the number exactly as it appears in the input.
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    /// Returns the number as an `f64`, which may lose precision.
+    pub fn as_f64(&self) -> f64 {
+        // The grammar accepted by the parser is a subset of Rust's float syntax.
+        self.0.parse().unwrap_or(f64::NAN)
+    }
+
+    /// Returns the number as an `i64`, if it is an integer within range.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.0.parse().ok()
+    }
+
+    /// Returns the number as a `u64`, if it is a non-negative integer within
+    /// range.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.0.parse().ok()
+    }
+}
+
+impl fmt::Display for JsonNumber<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Error returned when parsing JSON fails.
+pub type JsonError<'a> = SyntaxError<'a>;
+
+/// Parses a complete JSON document, allowing up to [`DEFAULT_MAX_DEPTH`]
+/// levels of nesting.
+pub fn parse(input: &str) -> Result<JsonValue<'_>, JsonError<'_>> {
+    parse_with_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Parses a complete JSON document, allowing up to `max_depth` levels of
+/// nested arrays and objects.
+pub fn parse_with_depth(input: &str, max_depth: usize) -> Result<JsonValue<'_>, JsonError<'_>> {
+    let depth = Depth {
+        current: 0,
+        max: max_depth,
+    };
+    let input = Stateful::new(Located::new(input), depth);
+    document(input).map_err(JsonError::new)
+}
+
+/// Parses a complete JSON document from bytes, which must be valid UTF-8.
+pub fn parse_bytes(input: &[u8]) -> Result<JsonValue<'_>, JsonError<'_>> {
+    match core::str::from_utf8(input) {
+        Ok(input) => parse(input),
+        Err(error) => {
+            let valid = error.valid_up_to();
+            // SAFETY: `valid_up_to` is the length of the longest valid prefix.
+            let prefix = unsafe { core::str::from_utf8_unchecked(&input[..valid]) };
+            let position = Located::new(prefix).over(valid..valid);
+            let message = with_error!(ErrorKind::MalformedData, "invalid UTF-8 sequence");
+            Err(JsonError::new(Error::from_message(position, message)))
+        }
+    }
+}
+
+/// Nesting depth of arrays and objects, carried as parser state.
+#[derive(Clone, Copy, Debug)]
+struct Depth {
+    current: usize,
+    max: usize,
+}
+
+type Input<'a> = Stateful<Located<&'a str>, Depth>;
+type ParseError<'a> = ErrorWithContext<Located<&'a str>>;
+type JsonResult<'a, O> = AResult<Input<'a>, O, ParseError<'a>>;
+
+fn text<'a>(input: &Input<'a>) -> &'a str {
+    input.input().fragment()
+}
+
+fn advance(input: Input<'_>, count: usize) -> Input<'_> {
+    input.over(count..input.byte_len())
+}
+
+fn failure(input: Input<'_>, message: ErrorMessage) -> ParseError<'_> {
+    Error::from_message(input, message)
+}
+
+fn expected<'a>(input: Input<'a>, items: &[Expected]) -> ParseError<'a> {
+    super::expected(input, items)
+}
+
+fn whitespace(input: Input<'_>) -> Input<'_> {
+    let count = text(&input)
+        .bytes()
+        .position(|b| !matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        .unwrap_or(input.byte_len());
+    advance(input, count)
+}
+
+fn document(input: Input<'_>) -> Result<JsonValue<'_>, ParseError<'_>> {
+    let (rest, value) = value(whitespace(input))?;
+    let rest = whitespace(rest);
+    if rest.byte_len() == 0 {
+        Ok(value)
+    } else {
+        Err(expected(rest, &[Expected::Rule("end of input")]))
+    }
+}
+
+fn value(input: Input<'_>) -> JsonResult<'_, JsonValue<'_>> {
+    match text(&input).as_bytes().first() {
+        Some(b'{') => object(input),
+        Some(b'[') => array(input),
+        Some(b'"') => string(input).map(|(rest, string)| (rest, JsonValue::String(string))),
+        Some(b't') => literal(input, "true", JsonValue::Bool(true)),
+        Some(b'f') => literal(input, "false", JsonValue::Bool(false)),
+        Some(b'n') => literal(input, "null", JsonValue::Null),
+        Some(b'-' | b'0'..=b'9') => {
+            number(input).map(|(rest, number)| (rest, JsonValue::Number(number)))
+        }
+        _ => Err(expected(input, &[Expected::Rule("value")])),
+    }
+}
+
+fn literal<'a>(
+    input: Input<'a>,
+    literal: &'static str,
+    value: JsonValue<'a>,
+) -> JsonResult<'a, JsonValue<'a>> {
+    if text(&input).starts_with(literal) {
+        Ok((advance(input, literal.len()), value))
+    } else {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "invalid literal, expected `{literal}`"
+        );
+        Err(failure(input, message))
+    }
+}
+
+fn digits(bytes: &[u8]) -> usize {
+    bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+}
+
+fn number(input: Input<'_>) -> JsonResult<'_, JsonNumber<'_>> {
+    let bytes = text(&input).as_bytes();
+    let expect_digit = |index| Err(expected(advance(input, index), &[Expected::Class("digit")]));
+
+    let mut index = usize::from(bytes[0] == b'-');
+    match bytes.get(index) {
+        Some(b'0') => index += 1,
+        Some(b'1'..=b'9') => index += digits(&bytes[index..]),
+        _ => return expect_digit(index),
+    }
+    if bytes.get(index) == Some(&b'.') {
+        index += 1;
+        match digits(&bytes[index..]) {
+            0 => return expect_digit(index),
+            count => index += count,
+        }
+    }
+    if matches!(bytes.get(index), Some(b'e' | b'E')) {
+        index += 1;
+        if matches!(bytes.get(index), Some(b'+' | b'-')) {
+            index += 1;
+        }
+        match digits(&bytes[index..]) {
+            0 => return expect_digit(index),
+            count => index += count,
+        }
+    }
+    let number = JsonNumber(&text(&input)[..index]);
+    Ok((advance(input, index), number))
+}
+
+fn string(input: Input<'_>) -> JsonResult<'_, Cow<'_, str>> {
+    if !text(&input).starts_with('"') {
+        return Err(expected(input, &[Expected::Rule("string")]));
+    }
+    let body = advance(input, 1);
+    let source = text(&body);
+    let bytes = source.as_bytes();
+
+    let mut owned: Option<String> = None;
+    let mut chunk = 0;
+    let mut index = 0;
+    loop {
+        match bytes.get(index) {
+            None => {
+                let message = with_error!(ErrorKind::EndOfInput, "unterminated string");
+                return Err(failure(input, message));
+            }
+            Some(b'"') => {
+                let string = match owned {
+                    None => Cow::Borrowed(&source[..index]),
+                    Some(mut owned) => {
+                        owned.push_str(&source[chunk..index]);
+                        Cow::Owned(owned)
+                    }
+                };
+                return Ok((advance(body, index + 1), string));
+            }
+            Some(b'\\') => {
+                let owned = owned.get_or_insert_with(String::new);
+                owned.push_str(&source[chunk..index]);
+                let (c, length) = escape(body, index)?;
+                owned.push(c);
+                index += length;
+                chunk = index;
+            }
+            Some(&byte) if byte < 0x20 => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "control character U+{byte:04X} must be escaped"
+                );
+                return Err(failure(advance(body, index), message));
+            }
+            Some(_) => index += 1,
+        }
+    }
+}
+
+/// Decodes the escape sequence at `index`, returning the character and the
+/// length of the sequence in bytes.
+fn escape(body: Input<'_>, index: usize) -> Result<(char, usize), ParseError<'_>> {
+    let bytes = text(&body).as_bytes();
+    let at = advance(body, index);
+    let c = match bytes.get(index + 1) {
+        Some(b'"') => '"',
+        Some(b'\\') => '\\',
+        Some(b'/') => '/',
+        Some(b'b') => '\u{8}',
+        Some(b'f') => '\u{c}',
+        Some(b'n') => '\n',
+        Some(b'r') => '\r',
+        Some(b't') => '\t',
+        Some(b'u') => return unicode_escape(at, &bytes[index..]),
+        Some(_) => {
+            let found = text(&at)[1..].chars().next().unwrap_or_default();
+            let message = with_error!(ErrorKind::MalformedData, "invalid escape `\\{found}`");
+            return Err(failure(at, message));
+        }
+        None => {
+            let message = with_error!(ErrorKind::EndOfInput, "unterminated escape sequence");
+            return Err(failure(at, message));
+        }
+    };
+    Ok((c, 2))
+}
+
+/// Decodes a `\uXXXX` escape, combining UTF-16 surrogate pairs.
+fn unicode_escape<'a>(at: Input<'a>, bytes: &[u8]) -> Result<(char, usize), ParseError<'a>> {
+    let hex = |offset: usize| {
+        let digits = bytes.get(offset..offset + 4)?;
+        digits.iter().try_fold(0u32, |value, &digit| {
+            Some(value * 16 + char::from(digit).to_digit(16)?)
+        })
+    };
+    let invalid = |message| Err(failure(at, message));
+
+    let Some(unit) = hex(2) else {
+        return invalid(with_error!(
+            ErrorKind::MalformedData,
+            "expected four hexadecimal digits after `\\u`"
+        ));
+    };
+    match unit {
+        0xD800..=0xDBFF => {
+            let low = match (bytes.get(6..8), hex(8)) {
+                (Some(b"\\u"), Some(low @ 0xDC00..=0xDFFF)) => low,
+                _ => {
+                    return invalid(with_error!(
+                        ErrorKind::MalformedData,
+                        "unpaired high surrogate `\\u{unit:04X}`"
+                    ))
+                }
+            };
+            let scalar = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+            Ok((
+                char::from_u32(scalar).unwrap_or(char::REPLACEMENT_CHARACTER),
+                12,
+            ))
+        }
+        0xDC00..=0xDFFF => invalid(with_error!(
+            ErrorKind::MalformedData,
+            "unpaired low surrogate `\\u{unit:04X}`"
+        )),
+        _ => Ok((
+            char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER),
+            6,
+        )),
+    }
+}
+
+/// Enters a nested array or object, enforcing the depth limit.
+fn enter(input: Input<'_>) -> Result<Input<'_>, ParseError<'_>> {
+    let depth = *input.state();
+    if depth.current >= depth.max {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "exceeded the maximum nesting depth of {}",
+            depth.max
+        );
+        return Err(failure(input, message));
+    }
+    Ok(input.with_state(Depth {
+        current: depth.current + 1,
+        ..depth
+    }))
+}
+
+fn array(input: Input<'_>) -> JsonResult<'_, JsonValue<'_>> {
+    let depth = *input.state();
+    let mut rest = whitespace(advance(enter(input)?, 1));
+    let mut items = Vec::new();
+    if text(&rest).starts_with(']') {
+        return Ok((advance(rest, 1).with_state(depth), JsonValue::Array(items)));
+    }
+    loop {
+        let (after, item) = value(rest)?;
+        items.push(item);
+        let after = whitespace(after);
+        match text(&after).as_bytes().first() {
+            Some(b',') => rest = whitespace(advance(after, 1)),
+            Some(b']') => {
+                return Ok((advance(after, 1).with_state(depth), JsonValue::Array(items)));
+            }
+            _ => return Err(expected(after, &[Expected::Char(','), Expected::Char(']')])),
+        }
+    }
+}
+
+fn object(input: Input<'_>) -> JsonResult<'_, JsonValue<'_>> {
+    let depth = *input.state();
+    let mut rest = whitespace(advance(enter(input)?, 1));
+    let mut members = Vec::new();
+    if text(&rest).starts_with('}') {
+        return Ok((
+            advance(rest, 1).with_state(depth),
+            JsonValue::Object(members),
+        ));
+    }
+    loop {
+        let (after, key) = string(rest)?;
+        let after = whitespace(after);
+        if !text(&after).starts_with(':') {
+            return Err(expected(after, &[Expected::Char(':')]));
+        }
+        let (after, value) = value(whitespace(advance(after, 1)))?;
+        members.push((key, value));
+        let after = whitespace(after);
+        match text(&after).as_bytes().first() {
+            Some(b',') => rest = whitespace(advance(after, 1)),
+            Some(b'}') => {
+                return Ok((
+                    advance(after, 1).with_state(depth),
+                    JsonValue::Object(members),
+                ));
+            }
+            _ => return Err(expected(after, &[Expected::Char(','), Expected::Char('}')])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the `test_parsing` cases of JSONTestSuite: `y_` files must parse,
+    /// `n_` files must be rejected and `i_` files may go either way, but must
+    /// not panic. Files that are not valid UTF-8 count as rejected.
+    #[test]
+    fn json_test_suite() {
+        let dir = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/JSONTestSuite/test_parsing"
+        );
+        let mut cases = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+            let bytes = std::fs::read(&path).unwrap();
+            let accepted = core::str::from_utf8(&bytes).is_ok_and(|text| parse(text).is_ok());
+            match name.as_bytes()[0] {
+                b'y' => assert!(accepted, "{name} should be accepted"),
+                b'n' => assert!(!accepted, "{name} should be rejected"),
+                _ => {}
+            }
+            cases += 1;
+        }
+        assert_eq!(cases, 318);
+    }
+
+    #[test]
+    fn values_borrow_from_input() {
+        let value = parse(r#"{"plain": "text", "escaped": "a\nb𝄞", "n": -12.5e1}"#).unwrap();
+        assert!(matches!(
+            value.get("plain"),
+            Some(JsonValue::String(Cow::Borrowed("text")))
+        ));
+        assert!(matches!(
+            value.get("escaped"),
+            Some(JsonValue::String(Cow::Owned(_)))
+        ));
+        assert_eq!(value.get("escaped").unwrap().as_str(), Some("a\nb𝄞"));
+
+        let number = value.get("n").and_then(JsonValue::as_number).unwrap();
+        assert_eq!(number.as_str(), "-12.5e1");
+        assert_eq!(number.as_f64(), -125.0);
+        assert_eq!(number.as_i64(), None);
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        let error = parse("{\n  \"a\": [1, 2,\n  }").unwrap_err();
+        assert_eq!((error.line(), error.column()), (3, 3));
+        assert_eq!(error.to_string(), "3:3: expected value, found `}`");
+
+        let error = parse("[\"tab\there\"]").unwrap_err();
+        assert_eq!(error.offset(), 5);
+        assert_eq!(error.kind(), ErrorKind::MalformedData);
+        assert_eq!(
+            error.to_string(),
+            "1:6: control character U+0009 must be escaped"
+        );
+
+        let error = parse_bytes(b"[\"\xff\"]").unwrap_err();
+        assert_eq!(
+            (error.offset(), error.kind()),
+            (2, ErrorKind::MalformedData)
+        );
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        let deep = "[".repeat(100_000);
+        let error = parse(&deep).unwrap_err();
+        assert_eq!(error.offset(), DEFAULT_MAX_DEPTH);
+        assert_eq!(
+            error.to_string(),
+            "1:129: exceeded the maximum nesting depth of 128"
+        );
+
+        assert!(parse_with_depth("[[1]]", 2).is_ok());
+        assert!(parse_with_depth("[[1]]", 1).is_err());
+        assert!(parse_with_depth("[{}, [], {\"a\": []}]", 3).is_ok());
+        assert!(parse_with_depth("[{}, [], {\"a\": []}]", 2).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! This module contains parsers for common data formats, built on recoil.
+
+use core::fmt;
+
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+
+#[cfg(all(feature = "alloc", feature = "binary"))]
+pub mod cbor;
+#[cfg(feature = "alloc")]
+pub mod csv;
+pub mod datetime;
+#[cfg(feature = "alloc")]
+pub mod elf;
+pub mod encoding;
+pub mod http;
+#[cfg(feature = "alloc")]
+pub mod ini;
+#[cfg(feature = "alloc")]
+pub mod json;
+#[cfg(all(feature = "alloc", feature = "binary"))]
+pub mod msgpack;
+pub mod net;
+#[cfg(feature = "alloc")]
+pub mod pdf;
+pub mod png;
+pub mod protobuf;
+#[cfg(feature = "alloc")]
+pub mod semver;
+#[cfg(feature = "alloc")]
+pub mod toml;
+#[cfg(feature = "alloc")]
+pub mod uri;
+#[cfg(feature = "alloc")]
+pub mod yaml;
+
+/// Outcome of decoding from input that may be partial.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Status<T> {
+    /// A complete item, along with its length in bytes. Any input that
+    /// follows the item starts at this offset.
+    Complete(T, usize),
+    /// The input ends before the item does; more data is needed.
+    Incomplete,
+}
+
+/// A position in the source of a text format.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn new(location: &Located<&str>) -> Self {
+        let (line, column) = location.line_column();
+        Self {
+            offset: location.offset(),
+            line,
+            column,
+        }
+    }
+
+    /// Returns the byte offset of the position.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the 1-based line of the position.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the 1-based column of the position, counted in characters.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// Error returned by the text format parsers, with the line and column at
+/// which it occurred.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyntaxError<'a> {
+    error: ErrorWithContext<Located<&'a str>>,
+    /// Offset of an earlier definition the failure conflicts with.
+    previous: Option<usize>,
+}
+
+impl<'a> SyntaxError<'a> {
+    #[cfg(feature = "alloc")]
+    pub(crate) fn new(error: ErrorWithContext<Located<&'a str>>) -> Self {
+        Self {
+            error,
+            previous: None,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    /// Records `previous` as the earlier definition the failure conflicts
+    /// with.
+    pub(crate) fn with_previous(mut self, previous: Located<&'a str>) -> Self {
+        self.previous = Some(previous.offset());
+        self
+    }
+
+    /// Returns the underlying error, spanning the position of the failure.
+    pub fn error(&self) -> &ErrorWithContext<Located<&'a str>> {
+        &self.error
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.error.kind()
+    }
+
+    /// Returns the byte offset of the failure.
+    pub fn offset(&self) -> usize {
+        self.error.input().offset()
+    }
+
+    /// Returns the 1-based line of the failure.
+    pub fn line(&self) -> usize {
+        self.error.input().line_column().0
+    }
+
+    /// Returns the 1-based column of the failure, counted in characters.
+    pub fn column(&self) -> usize {
+        self.error.input().line_column().1
+    }
+
+    /// Returns the position of an earlier definition the failure conflicts
+    /// with, such as the first definition of a duplicate key.
+    pub fn previous(&self) -> Option<Position> {
+        let source = self.error.input().original();
+        let previous = Located::new(source).over(self.previous?..source.len());
+        Some(Position::new(&previous))
+    }
+}
+
+impl fmt::Display for SyntaxError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, column) = self.error.input().line_column();
+        write!(f, "{line}:{column}: {}", self.error)?;
+        match self.previous() {
+            Some(previous) => write!(
+                f,
+                " (first defined at {}:{})",
+                previous.line, previous.column
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl core::error::Error for SyntaxError<'_> {}
+
+/// Creates an error spanning `start..end` of `input`, for parsers that point
+/// at the whole of a malformed value rather than where it starts.
+pub(crate) fn spanning<I>(
+    input: Located<I>,
+    start: usize,
+    end: usize,
+    message: ErrorMessage,
+) -> ErrorWithContext<Located<I>>
+where
+    I: Span<RefSlice = I> + Copy,
+{
+    let at = input.over(start..input.byte_len());
+    let offset = at.offset();
+    ErrorWithContext::new(at, Some((offset, offset + end - start)), 0).with_message(message)
+}
+
+/// Creates an error at `input` expecting any of `items`, or an `Unknown`
+/// error if `items` is empty.
+pub(crate) fn expected<I: Copy, E: Error<I>>(input: I, items: &[Expected]) -> E {
+    items
+        .iter()
+        .map(|item| E::from_expected(input, *item))
+        .reduce(E::or)
+        .unwrap_or_else(|| E::from_error_kind(input, ErrorKind::Unknown))
+}
+
+/// Returns whether `year` is a leap year in the proleptic Gregorian calendar.
+pub(crate) fn is_leap_year(year: u16) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+/// Returns the number of days in `month` of `year`.
+pub(crate) fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
@@ -0,0 +1,419 @@
+//! This module contains a decoder for MessagePack.
+//!
+//! [`decode`] decodes one value into a tree borrowing strings and binary
+//! data from the input. It works on partial input: while the value has not
+//! been received in full, it returns [`Status::Incomplete`] and should be
+//! called again once more data has arrived. Values sent back to back are
+//! decoded by calling it again from the length of the previous one.
+//!
+//! Declared lengths and nesting are checked against [`Limits`] before any
+//! memory is reserved, so that a few hostile bytes cannot request large
+//! allocations.
+//!
+//! ```
+//! use recoil::formats::msgpack::{self, Limits, Value};
+//! use recoil::formats::Status;
+//!
+//! // {"id": 7, "tags": ["a"]}
+//! let input = b"\x82\xa2id\x07\xa4tags\x91\xa1a";
+//! let Ok(Status::Complete(value, length)) = msgpack::decode(input, &Limits::default()) else {
+//!     panic!("the value is complete");
+//! };
+//! assert_eq!(value.get("id"), Some(&Value::Integer(7)));
+//! assert_eq!(value.get("tags").and_then(Value::as_array).map(<[_]>::len), Some(1));
+//! assert_eq!(length, input.len());
+//!
+//! let status = msgpack::decode(&input[..6], &Limits::default()).unwrap();
+//! assert!(matches!(status, Status::Incomplete));
+//! ```
+
+use alloc::vec::Vec;
+use core::str;
+
+use super::{spanning, Status};
+use crate::error::{ErrorKind, ErrorMessage, ErrorWithContext};
+use crate::located::Located;
+use crate::with_error;
+
+/// Error returned when decoding fails, spanning the offending bytes.
+pub type MsgpackError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+/// A decoded value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'a> {
+    Nil,
+    Boolean(bool),
+    /// An integer of any of the signed or unsigned formats.
+    Integer(i128),
+    /// A float, widened to `f64` if it was encoded as `f32`.
+    Float(f64),
+    String(&'a str),
+    Binary(&'a [u8]),
+    Array(Vec<Value<'a>>),
+    /// The entries of a map, in the order they were encoded.
+    Map(Vec<(Value<'a>, Value<'a>)>),
+    /// An application-defined extension type, with negative types reserved
+    /// by the specification.
+    Extension {
+        kind: i8,
+        data: &'a [u8],
+    },
+}
+
+impl<'a> Value<'a> {
+    pub fn as_integer(&self) -> Option<i128> {
+        match *self {
+            Value::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the first entry of a map whose key is the string
+    /// `key`.
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        let Value::Map(entries) = self else {
+            return None;
+        };
+        entries
+            .iter()
+            .find(|(name, _)| name.as_str() == Some(key))
+            .map(|(_, value)| value)
+    }
+}
+
+/// Limits enforced while decoding, guarding against hostile input.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Limits {
+    /// Maximum nesting of arrays and maps.
+    pub max_depth: usize,
+    /// Maximum length of a string or binary in bytes, and of an array or map
+    /// in items.
+    pub max_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_length: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Decodes the value at the start of `input`.
+pub fn decode<'a>(input: &'a [u8], limits: &Limits) -> Result<Status<Value<'a>>, MsgpackError<'a>> {
+    let mut decoder = Decoder {
+        source: input,
+        at: 0,
+        limits,
+        depth: 0,
+    };
+    match decoder.value() {
+        Ok(value) => Ok(Status::Complete(value, decoder.at)),
+        Err(Stop::Incomplete) => Ok(Status::Incomplete),
+        Err(Stop::Error(error)) => Err(error),
+    }
+}
+
+/// Reason to stop decoding.
+enum Stop<'a> {
+    Incomplete,
+    Error(MsgpackError<'a>),
+}
+
+struct Decoder<'a, 'l> {
+    source: &'a [u8],
+    at: usize,
+    limits: &'l Limits,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a, '_> {
+    fn error(&self, start: usize, end: usize, message: ErrorMessage) -> Stop<'a> {
+        Stop::Error(spanning(Located::new(self.source), start, end, message))
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Stop<'a>> {
+        let end = self.at.checked_add(length).ok_or(Stop::Incomplete)?;
+        let bytes = self.source.get(self.at..end).ok_or(Stop::Incomplete)?;
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Stop<'a>> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads a big-endian unsigned integer of `size` bytes.
+    fn uint(&mut self, size: usize) -> Result<u64, Stop<'a>> {
+        let bytes = self.take(size)?;
+        Ok(bytes.iter().fold(0, |value, &b| value << 8 | u64::from(b)))
+    }
+
+    /// Checks the length declared by the header starting at `start`.
+    fn length(&self, start: usize, length: u64) -> Result<usize, Stop<'a>> {
+        let max = self.limits.max_length;
+        match usize::try_from(length) {
+            Ok(length) if length <= max => Ok(length),
+            _ => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "length {length} exceeds the limit of {max}"
+                );
+                Err(self.error(start, self.at, message))
+            }
+        }
+    }
+
+    fn data(&mut self, start: usize, length: u64) -> Result<&'a [u8], Stop<'a>> {
+        let length = self.length(start, length)?;
+        self.take(length)
+    }
+
+    fn value(&mut self) -> Result<Value<'a>, Stop<'a>> {
+        let start = self.at;
+        let marker = self.byte()?;
+        let value = match marker {
+            0x00..=0x7F => Value::Integer(marker.into()),
+            0x80..=0x8F => return self.map(start, u64::from(marker & 0x0F)),
+            0x90..=0x9F => return self.array(start, u64::from(marker & 0x0F)),
+            0xA0..=0xBF => return self.string(start, u64::from(marker & 0x1F)),
+            0xC0 => Value::Nil,
+            0xC1 => {
+                let message = with_error!(ErrorKind::MalformedData, "reserved format byte 0xc1");
+                return Err(self.error(start, self.at, message));
+            }
+            0xC2 => Value::Boolean(false),
+            0xC3 => Value::Boolean(true),
+            0xC4..=0xC6 => {
+                let length = self.uint(1 << (marker - 0xC4))?;
+                Value::Binary(self.data(start, length)?)
+            }
+            0xC7..=0xC9 => {
+                let length = self.uint(1 << (marker - 0xC7))?;
+                let kind = self.byte()? as i8;
+                let data = self.data(start, length)?;
+                Value::Extension { kind, data }
+            }
+            0xCA => Value::Float(f32::from_bits(self.uint(4)? as u32).into()),
+            0xCB => Value::Float(f64::from_bits(self.uint(8)?)),
+            0xCC..=0xCF => Value::Integer(self.uint(1 << (marker - 0xCC))?.into()),
+            0xD0..=0xD3 => {
+                let size = 1 << (marker - 0xD0);
+                // Sign-extend from the width of the integer.
+                let shift = 64 - 8 * size;
+                let value = (self.uint(size)? << shift) as i64 >> shift;
+                Value::Integer(value.into())
+            }
+            0xD4..=0xD8 => {
+                let kind = self.byte()? as i8;
+                let data = self.take(1 << (marker - 0xD4))?;
+                Value::Extension { kind, data }
+            }
+            0xD9..=0xDB => {
+                let length = self.uint(1 << (marker - 0xD9))?;
+                return self.string(start, length);
+            }
+            0xDC | 0xDD => {
+                let length = self.uint(if marker == 0xDC { 2 } else { 4 })?;
+                return self.array(start, length);
+            }
+            0xDE | 0xDF => {
+                let length = self.uint(if marker == 0xDE { 2 } else { 4 })?;
+                return self.map(start, length);
+            }
+            0xE0..=0xFF => Value::Integer((marker as i8).into()),
+        };
+        Ok(value)
+    }
+
+    fn string(&mut self, start: usize, length: u64) -> Result<Value<'a>, Stop<'a>> {
+        let data_start = self.at;
+        let data = self.data(start, length)?;
+        match str::from_utf8(data) {
+            Ok(text) => Ok(Value::String(text)),
+            Err(error) => {
+                let at = data_start + error.valid_up_to();
+                let message = with_error!(ErrorKind::MalformedData, "invalid UTF-8 in string");
+                Err(self.error(at, at + error.error_len().unwrap_or(1), message))
+            }
+        }
+    }
+
+    /// Enters an array or map whose header starts at `start`.
+    fn enter(&mut self, start: usize) -> Result<(), Stop<'a>> {
+        if self.depth == self.limits.max_depth {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "exceeded the maximum nesting depth of {}",
+                self.limits.max_depth
+            );
+            return Err(self.error(start, self.at, message));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Returns the capacity to reserve for `length` items, each taking at
+    /// least `size` bytes of the remaining input.
+    fn capacity(&self, length: usize, size: usize) -> usize {
+        length.min((self.source.len() - self.at) / size)
+    }
+
+    fn array(&mut self, start: usize, length: u64) -> Result<Value<'a>, Stop<'a>> {
+        let length = self.length(start, length)?;
+        self.enter(start)?;
+        let mut items = Vec::with_capacity(self.capacity(length, 1));
+        for _ in 0..length {
+            items.push(self.value()?);
+        }
+        self.depth -= 1;
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, start: usize, length: u64) -> Result<Value<'a>, Stop<'a>> {
+        let length = self.length(start, length)?;
+        self.enter(start)?;
+        let mut entries = Vec::with_capacity(self.capacity(length, 2));
+        for _ in 0..length {
+            let key = self.value()?;
+            entries.push((key, self.value()?));
+        }
+        self.depth -= 1;
+        Ok(Value::Map(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(input: &[u8]) -> Value<'_> {
+        match decode(input, &Limits::default()) {
+            Ok(Status::Complete(value, length)) => {
+                assert_eq!(length, input.len());
+                value
+            }
+            status => panic!("{status:?}"),
+        }
+    }
+
+    fn failure(input: &[u8], limits: &Limits) -> (String, (usize, usize)) {
+        let error = decode(input, limits).unwrap_err();
+        (error.to_string(), error.offset().unwrap())
+    }
+
+    #[test]
+    fn decodes_scalars() {
+        let cases: [(&[u8], Value<'_>); 16] = [
+            (b"\xC0", Value::Nil),
+            (b"\xC3", Value::Boolean(true)),
+            (b"\x7F", Value::Integer(127)),
+            (b"\xE0", Value::Integer(-32)),
+            (b"\xCC\xFF", Value::Integer(255)),
+            (b"\xCD\x01\x00", Value::Integer(256)),
+            (
+                b"\xCF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                Value::Integer(u64::MAX.into()),
+            ),
+            (b"\xD0\x80", Value::Integer(-128)),
+            (b"\xD1\xFF\x7F", Value::Integer(-129)),
+            (b"\xD3\x80\0\0\0\0\0\0\0", Value::Integer(i64::MIN.into())),
+            (b"\xCA\x3F\xC0\0\0", Value::Float(1.5)),
+            (b"\xCB\x3F\xF8\0\0\0\0\0\0", Value::Float(1.5)),
+            (b"\xA3abc", Value::String("abc")),
+            (b"\xD9\x01z", Value::String("z")),
+            (b"\xC4\x02\x00\xFF", Value::Binary(&[0, 255])),
+            (
+                b"\xD6\xFF\0\0\0\x01",
+                Value::Extension {
+                    kind: -1,
+                    data: &[0, 0, 0, 1],
+                },
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(complete(input), expected, "{input:x?}");
+        }
+        let value = complete(b"\xC7\x03\x05abc");
+        assert_eq!(
+            value,
+            Value::Extension {
+                kind: 5,
+                data: b"abc"
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_containers() {
+        let value = complete(b"\x82\xA1a\x93\x01\x02\x03\xA1b\x80");
+        let items = value.get("a").and_then(Value::as_array).unwrap();
+        assert_eq!(
+            items,
+            [Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+        );
+        assert_eq!(value.get("b"), Some(&Value::Map(Vec::new())));
+        assert_eq!(
+            complete(b"\xDC\0\x01\xC0"),
+            Value::Array(Vec::from([Value::Nil]))
+        );
+    }
+
+    #[test]
+    fn reports_incomplete_input() {
+        let input = b"\x92\xCD\x01\x00\xA5hello";
+        for length in 0..input.len() {
+            let status = decode(&input[..length], &Limits::default());
+            assert!(matches!(status, Ok(Status::Incomplete)), "{length} bytes");
+        }
+        let status = decode(b"\xC0\xC3", &Limits::default()).unwrap();
+        assert_eq!(status, Status::Complete(Value::Nil, 1));
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let limits = Limits {
+            max_depth: 2,
+            max_length: 4,
+        };
+        assert_eq!(
+            failure(b"\x91\x91\x91\xC0", &limits),
+            ("exceeded the maximum nesting depth of 2".into(), (2, 3))
+        );
+        assert_eq!(
+            failure(b"\xA5hello", &limits),
+            ("length 5 exceeds the limit of 4".into(), (0, 1))
+        );
+        // The limit applies before the data has arrived.
+        let error = decode(b"\xDD\xFF\xFF\xFF\xFF", &Limits::default()).unwrap_err();
+        assert_eq!(error.offset(), Some((0, 5)));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let limits = Limits::default();
+        assert_eq!(
+            failure(b"\x91\xC1", &limits),
+            ("reserved format byte 0xc1".into(), (1, 2))
+        );
+        assert_eq!(
+            failure(b"\xA3a\xFFc", &limits),
+            ("invalid UTF-8 in string".into(), (2, 3))
+        );
+    }
+}
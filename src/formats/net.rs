@@ -0,0 +1,521 @@
+//! This module contains parsers for network addresses: IPv4 and IPv6
+//! addresses, CIDR blocks, MAC addresses and RFC 1123 hostnames.
+//!
+//! Each parser recognizes an address at the start of its input and returns
+//! the rest, so that addresses can be picked out of surrounding text or used
+//! within larger grammars. Addresses are produced as [`core::net`] types
+//! where one exists. Malformed addresses fail with a `MalformedData` error
+//! spanning the offending part, such as an out-of-range octet.
+//!
+//! ```
+//! use core::net::Ipv4Addr;
+//! use recoil::formats::net;
+//! use recoil::prelude::Located;
+//!
+//! let (rest, address) = net::ipv4(Located::new("10.0.0.1 accepted")).unwrap();
+//! assert_eq!(address, Ipv4Addr::new(10, 0, 0, 1));
+//! assert_eq!(rest.fragment(), " accepted");
+//!
+//! let error = net::ipv4(Located::new("10.0.300.1")).unwrap_err();
+//! assert_eq!(error.offset(), Some((5, 8)));
+//! assert_eq!(error.to_string(), "IPv4 octet out of range");
+//! ```
+
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::spanning;
+use crate::error::{Error, ErrorKind, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::{with_error, AResult};
+
+/// Error returned when parsing an address fails, spanning the malformed
+/// part of the address.
+pub type NetError<'a> = ErrorWithContext<Located<&'a str>>;
+
+type In<'a> = Located<&'a str>;
+type NetResult<'a, O> = AResult<In<'a>, O, NetError<'a>>;
+
+/// Maximum length of a hostname, excluding a trailing dot.
+const MAX_HOSTNAME_LENGTH: usize = 253;
+
+/// Maximum length of a hostname label.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// A block of IP addresses in CIDR notation, as in `192.0.2.0/24`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Cidr {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Creates a block, returning `None` if `prefix` is longer than the
+    /// address.
+    pub fn new(address: IpAddr, prefix: u8) -> Option<Self> {
+        (prefix <= max_prefix(&address)).then_some(Self { address, prefix })
+    }
+
+    /// Returns the address as written, which may have host bits set.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the length of the network prefix, in bits.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Returns the first address of the block, with the host bits cleared.
+    pub fn network(&self) -> IpAddr {
+        match self.address {
+            IpAddr::V4(address) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(address) & mask))
+            }
+            IpAddr::V6(address) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(address) & mask))
+            }
+        }
+    }
+
+    /// Returns `true` if `address` belongs to the block.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let Some(block) = Cidr::new(address, self.prefix) else {
+            return false;
+        };
+        match (block.network(), self.network()) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => a == b,
+            (IpAddr::V6(a), IpAddr::V6(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+fn max_prefix(address: &IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// A 48-bit MAC address.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    pub const fn new(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+
+    pub const fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+}
+
+/// Renders the address as lowercase hexadecimal octets separated by colons.
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+fn expected(input: In<'_>, at: usize, item: Expected) -> NetError<'_> {
+    NetError::from_expected(input.over(at..input.byte_len()), item)
+}
+
+fn count(bytes: &[u8], at: usize, predicate: impl Fn(u8) -> bool) -> usize {
+    bytes[at.min(bytes.len())..]
+        .iter()
+        .take_while(|&&b| predicate(b))
+        .count()
+}
+
+/// Parses an IPv4 address in dotted-decimal notation.
+///
+/// Octets with leading zeros are rejected, since some tools read them as
+/// octal.
+pub fn ipv4(input: In<'_>) -> NetResult<'_, Ipv4Addr> {
+    let bytes = input.fragment().as_bytes();
+    let mut octets = [0; 4];
+    let mut at = 0;
+    for (index, octet) in octets.iter_mut().enumerate() {
+        if index > 0 {
+            if bytes.get(at) != Some(&b'.') {
+                return Err(expected(input, at, Expected::Char('.')));
+            }
+            at += 1;
+        }
+        let digits = count(bytes, at, |b| b.is_ascii_digit());
+        if digits == 0 {
+            return Err(expected(input, at, Expected::Class("digit")));
+        }
+        let end = at + digits;
+        if digits > 1 && bytes[at] == b'0' {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "leading zeros are not allowed in IPv4 octets"
+            );
+            return Err(spanning(input, at, end, message));
+        }
+        let value = bytes[at..end].iter().try_fold(0u8, |value, &digit| {
+            value.checked_mul(10)?.checked_add(digit - b'0')
+        });
+        let Some(value) = value else {
+            let message = with_error!(ErrorKind::MalformedData, "IPv4 octet out of range");
+            return Err(spanning(input, at, end, message));
+        };
+        *octet = value;
+        at = end;
+    }
+    Ok((input.over(at..input.byte_len()), Ipv4Addr::from(octets)))
+}
+
+/// Parses an IPv6 address, in any of the text forms of RFC 4291: full,
+/// compressed with `::`, and with the last 32 bits written as an embedded
+/// IPv4 address.
+pub fn ipv6(input: In<'_>) -> NetResult<'_, Ipv6Addr> {
+    let bytes = input.fragment().as_bytes();
+    let mut groups = [0u16; 8];
+    let mut count_groups = 0;
+    // Index of the first group after `::`, if the address is compressed.
+    let mut gap = None;
+    let mut at = 0;
+    if bytes.starts_with(b"::") {
+        gap = Some(0);
+        at = 2;
+    }
+    loop {
+        // Groups may end right after `::`, but not after a single `:`.
+        let after_gap = gap == Some(count_groups);
+        let digits = count(bytes, at, |b| b.is_ascii_hexdigit());
+        if digits == 0 {
+            if after_gap && bytes.get(at) != Some(&b':') {
+                break;
+            }
+            return Err(expected(input, at, Expected::Class("hexadecimal digit")));
+        }
+        if count_groups == 8 {
+            let message = with_error!(ErrorKind::MalformedData, "too many groups in IPv6 address");
+            return Err(spanning(input, 0, at + digits, message));
+        }
+        if bytes.get(at + digits) == Some(&b'.') {
+            // An embedded IPv4 address ends the address.
+            if count_groups > 6 {
+                let message =
+                    with_error!(ErrorKind::MalformedData, "too many groups in IPv6 address");
+                return Err(spanning(input, 0, at + digits, message));
+            }
+            let (rest, ipv4) = ipv4(input.over(at..input.byte_len()))?;
+            let [a, b, c, d] = ipv4.octets();
+            groups[count_groups] = u16::from_be_bytes([a, b]);
+            groups[count_groups + 1] = u16::from_be_bytes([c, d]);
+            count_groups += 2;
+            at = rest.offset() - input.offset();
+            break;
+        }
+        if digits > 4 {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "IPv6 group has more than four digits"
+            );
+            return Err(spanning(input, at, at + digits, message));
+        }
+        let text = &input.fragment()[at..at + digits];
+        groups[count_groups] = u16::from_str_radix(text, 16).expect("validated hexadecimal digits");
+        count_groups += 1;
+        at += digits;
+
+        if bytes[at..].starts_with(b"::") {
+            if gap.is_some() {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "`::` may appear only once in an IPv6 address"
+                );
+                return Err(spanning(input, at, at + 2, message));
+            }
+            gap = Some(count_groups);
+            at += 2;
+        } else if bytes.get(at) == Some(&b':') {
+            at += 1;
+        } else {
+            break;
+        }
+    }
+
+    let address = match gap {
+        None if count_groups < 8 => {
+            let message = with_error!(ErrorKind::MalformedData, "too few groups in IPv6 address");
+            return Err(spanning(input, 0, at, message));
+        }
+        None => groups,
+        Some(_) if count_groups > 7 => {
+            let message = with_error!(ErrorKind::MalformedData, "too many groups in IPv6 address");
+            return Err(spanning(input, 0, at, message));
+        }
+        Some(gap) => {
+            // Move the groups after `::` to the end.
+            let mut address = [0; 8];
+            address[..gap].copy_from_slice(&groups[..gap]);
+            let tail = count_groups - gap;
+            address[8 - tail..].copy_from_slice(&groups[gap..count_groups]);
+            address
+        }
+    };
+    Ok((input.over(at..input.byte_len()), Ipv6Addr::from(address)))
+}
+
+/// Parses an IPv4 or an IPv6 address.
+pub fn ip(input: In<'_>) -> NetResult<'_, IpAddr> {
+    let bytes = input.fragment().as_bytes();
+    let length = count(bytes, 0, |b| {
+        b.is_ascii_hexdigit() || matches!(b, b':' | b'.')
+    });
+    if bytes[..length].contains(&b':') {
+        ipv6(input).map(|(rest, address)| (rest, IpAddr::V6(address)))
+    } else {
+        ipv4(input).map(|(rest, address)| (rest, IpAddr::V4(address)))
+    }
+}
+
+/// Parses a CIDR block, an IP address followed by `/` and a prefix length.
+pub fn cidr(input: In<'_>) -> NetResult<'_, Cidr> {
+    let (rest, address) = ip(input)?;
+    let bytes = rest.fragment().as_bytes();
+    if bytes.first() != Some(&b'/') {
+        return Err(expected(rest, 0, Expected::Char('/')));
+    }
+    let digits = count(bytes, 1, |b| b.is_ascii_digit());
+    if digits == 0 {
+        return Err(expected(rest, 1, Expected::Class("digit")));
+    }
+    let prefix = rest.fragment()[1..1 + digits].parse::<u8>().ok();
+    let Some(cidr) = prefix.and_then(|prefix| Cidr::new(address, prefix)) else {
+        let message = with_error!(ErrorKind::MalformedData, "prefix length out of range");
+        return Err(spanning(rest, 1, 1 + digits, message));
+    };
+    Ok((rest.over(1 + digits..rest.byte_len()), cidr))
+}
+
+/// Parses a MAC address written as six hexadecimal octets separated by `:`
+/// or `-`, as in `00:1a:2b:3c:4d:5e`, or as three groups of four digits
+/// separated by `.`, as in `001a.2b3c.4d5e`.
+pub fn mac(input: In<'_>) -> NetResult<'_, MacAddr> {
+    let bytes = input.fragment().as_bytes();
+    let (separator, group) = match bytes.get(2) {
+        Some(&separator @ (b':' | b'-')) => (separator, 2),
+        _ => (b'.', 4),
+    };
+    let mut octets = [0; 6];
+    let mut at = 0;
+    for index in 0..12 / group {
+        if index > 0 {
+            if bytes.get(at) != Some(&separator) {
+                return Err(expected(input, at, Expected::Char(char::from(separator))));
+            }
+            at += 1;
+        }
+        for digit in 0..group {
+            let value = bytes.get(at).and_then(|&b| char::from(b).to_digit(16));
+            let Some(value) = value else {
+                return Err(expected(input, at, Expected::Class("hexadecimal digit")));
+            };
+            let nibble = index * group + digit;
+            octets[nibble / 2] |= (value as u8) << if nibble % 2 == 0 { 4 } else { 0 };
+            at += 1;
+        }
+    }
+    Ok((input.over(at..input.byte_len()), MacAddr(octets)))
+}
+
+/// Parses a hostname as specified by RFC 1123: dot-separated labels of
+/// letters, digits and hyphens.
+///
+/// A dot that is not followed by another label is left in the input, so
+/// that a hostname at the end of a sentence is recognized without the
+/// period.
+pub fn hostname(input: In<'_>) -> NetResult<'_, &str> {
+    let text = input.fragment();
+    let bytes = text.as_bytes();
+    let is_label = |b: u8| b.is_ascii_alphanumeric() || b == b'-';
+    let mut at = 0;
+    loop {
+        let length = count(bytes, at, is_label);
+        if length == 0 {
+            return Err(expected(input, at, Expected::Rule("hostname")));
+        }
+        let end = at + length;
+        if length > MAX_LABEL_LENGTH {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "hostname label longer than 63 characters"
+            );
+            return Err(spanning(input, at, end, message));
+        }
+        if bytes[at] == b'-' || bytes[end - 1] == b'-' {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "hostname labels cannot start or end with `-`"
+            );
+            return Err(spanning(input, at, end, message));
+        }
+        at = end;
+        match bytes.get(at..at + 2) {
+            Some([b'.', next]) if is_label(*next) => at += 1,
+            _ => break,
+        }
+    }
+    if at > MAX_HOSTNAME_LENGTH {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "hostname longer than 253 characters"
+        );
+        return Err(spanning(input, 0, at, message));
+    }
+    Ok((input.over(at..input.byte_len()), &text[..at]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete<'a, O>(
+        parser: impl Fn(In<'a>) -> NetResult<'a, O>,
+        text: &'a str,
+    ) -> Result<O, NetError<'a>> {
+        let (rest, output) = parser(Located::new(text))?;
+        assert_eq!(rest.fragment(), "", "{text}");
+        Ok(output)
+    }
+
+    fn span(error: NetError<'_>) -> (usize, usize) {
+        error.offset().unwrap()
+    }
+
+    #[test]
+    fn parses_ipv4() {
+        assert_eq!(
+            complete(ipv4, "192.0.2.255"),
+            Ok(Ipv4Addr::new(192, 0, 2, 255))
+        );
+        assert_eq!(complete(ipv4, "0.0.0.0"), Ok(Ipv4Addr::UNSPECIFIED));
+
+        let (rest, _) = ipv4(Located::new("1.2.3.4.5")).unwrap();
+        assert_eq!((rest.offset(), rest.fragment()), (7, ".5"));
+
+        assert_eq!(span(ipv4(Located::new("1.2.3.256")).unwrap_err()), (6, 9));
+        assert_eq!(span(ipv4(Located::new("1.2.03.4")).unwrap_err()), (4, 6));
+        let error = ipv4(Located::new("1.2.3")).unwrap_err();
+        assert_eq!(error.to_string(), "expected `.`, found end of input");
+        let error = ipv4(Located::new("1..3.4")).unwrap_err();
+        assert_eq!(error.to_string(), "expected digit, found `.`");
+    }
+
+    #[test]
+    fn parses_ipv6() {
+        let valid = [
+            "2001:db8:85a3:8d3:1319:8a2e:370:7348",
+            "2001:DB8::1",
+            "::",
+            "::1",
+            "1::",
+            "fe80::1:2",
+            "1:2:3:4:5:6:7::",
+            "::2:3:4:5:6:7:8",
+            "::ffff:192.0.2.1",
+            "64:ff9b::192.0.2.33",
+            "1:2:3:4:5:6:1.2.3.4",
+        ];
+        for text in valid {
+            let expected = text.parse::<Ipv6Addr>().unwrap();
+            assert_eq!(complete(ipv6, text), Ok(expected), "{text}");
+        }
+
+        let (rest, address) = ipv6(Located::new("::1]:80")).unwrap();
+        assert_eq!((address, rest.fragment()), (Ipv6Addr::LOCALHOST, "]:80"));
+
+        let span = |text| span(ipv6(Located::new(text)).unwrap_err());
+        assert_eq!(span("1::2::3"), (4, 6));
+        assert_eq!(span("12345::"), (0, 5));
+        assert_eq!(span("1:2:3:4:5:6:7"), (0, 13));
+        assert_eq!(span("1:2:3:4:5:6:7:8:9"), (0, 17));
+        assert_eq!(span("1:2:3:4::5:6:7:8"), (0, 16));
+        assert_eq!(span("::ffff:1.2.3.999"), (13, 16));
+        assert_eq!(span(":::"), (2, 2));
+        assert_eq!(span("1:"), (2, 2));
+    }
+
+    #[test]
+    fn parses_cidr_blocks() {
+        let block = complete(cidr, "192.0.2.77/24").unwrap();
+        assert_eq!(block.prefix(), 24);
+        assert_eq!(block.network(), IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)));
+        assert!(block.contains(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+        assert!(!block.contains(IpAddr::V4(Ipv4Addr::new(192, 0, 3, 1))));
+        assert_eq!(block.to_string(), "192.0.2.77/24");
+
+        let block = complete(cidr, "2001:db8::/32").unwrap();
+        assert!(block.contains("2001:db8:ffff::1".parse().unwrap()));
+        assert!(!block.contains(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert_eq!(
+            complete(cidr, "0.0.0.0/0").unwrap().network(),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        );
+
+        let error = cidr(Located::new("10.0.0.0/33")).unwrap_err();
+        assert_eq!(error.offset(), Some((9, 11)));
+        assert_eq!(error.to_string(), "prefix length out of range");
+        assert!(cidr(Located::new("::/129")).is_err());
+        assert!(cidr(Located::new("10.0.0.0")).is_err());
+    }
+
+    #[test]
+    fn parses_mac_addresses() {
+        let expected = MacAddr::new([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        for text in ["00:1a:2b:3c:4d:5e", "00-1A-2B-3C-4D-5E", "001a.2b3c.4d5e"] {
+            assert_eq!(complete(mac, text), Ok(expected), "{text}");
+        }
+        assert_eq!(expected.to_string(), "00:1a:2b:3c:4d:5e");
+
+        let error = mac(Located::new("00:1a-2b:3c:4d:5e")).unwrap_err();
+        assert_eq!(error.to_string(), "expected `:`, found `-`");
+        let error = mac(Located::new("00:1a:2b:3c:4d:5")).unwrap_err();
+        assert_eq!(error.offset(), Some((16, 16)));
+    }
+
+    #[test]
+    fn parses_hostnames() {
+        assert_eq!(complete(hostname, "example.com"), Ok("example.com"));
+        assert_eq!(complete(hostname, "3com.net"), Ok("3com.net"));
+        assert_eq!(complete(hostname, "localhost"), Ok("localhost"));
+
+        let (rest, host) = hostname(Located::new("www.example.org.")).unwrap();
+        assert_eq!((host, rest.fragment()), ("www.example.org", "."));
+
+        let error = hostname(Located::new("a.-b.c")).unwrap_err();
+        assert_eq!(error.offset(), Some((2, 4)));
+        assert_eq!(
+            error.to_string(),
+            "hostname labels cannot start or end with `-`"
+        );
+
+        let long = "a".repeat(64);
+        assert_eq!(span(hostname(Located::new(&long)).unwrap_err()), (0, 64));
+        let long = [(); 4].map(|_| "a".repeat(63)).join(".");
+        assert_eq!(span(hostname(Located::new(&long)).unwrap_err()), (0, 255));
+        assert!(hostname(Located::new("_srv")).is_err());
+    }
+}
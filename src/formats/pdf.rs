@@ -0,0 +1,877 @@
+//! This module contains a lexer for the object syntax of PDF files, as
+//! specified by ISO 32000.
+//!
+//! It parses objects (numbers, names, strings, arrays, dictionaries and
+//! indirect references), `obj` / `endobj` blocks with their stream data, and
+//! the cross-reference table and trailer. Stream data is returned as it
+//! appears in the file; filters such as `FlateDecode` are not applied.
+//!
+//! ```
+//! use recoil::formats::pdf::{self, PdfObject};
+//!
+//! let data = b"4 0 obj\n<< /Type /Page /Contents 5 0 R >>\nendobj\n";
+//! let object = pdf::object_at(data, 0).unwrap();
+//! let dictionary = object.object.as_dictionary().unwrap();
+//! assert_eq!(dictionary.get(b"Type").and_then(PdfObject::as_name), Some(&b"Page"[..]));
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::{with_error, AResult};
+
+/// Maximum nesting depth of arrays and dictionaries.
+const MAX_DEPTH: usize = 128;
+
+/// Error returned when lexing PDF data fails, spanning the position of the
+/// failure.
+pub type PdfError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+type In<'a> = Located<&'a [u8]>;
+type PdfResult<'a, O> = AResult<In<'a>, O, PdfError<'a>>;
+
+/// Identifies an indirect object.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ObjectId {
+    pub number: u32,
+    pub generation: u16,
+}
+
+/// A direct PDF object.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PdfObject<'a> {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    /// A name, with `#xx` escapes decoded and without the leading `/`.
+    Name(Cow<'a, [u8]>),
+    /// A literal or hexadecimal string, with escapes decoded.
+    String(Cow<'a, [u8]>),
+    Array(Vec<PdfObject<'a>>),
+    Dictionary(Dictionary<'a>),
+    Reference(ObjectId),
+}
+
+impl<'a> PdfObject<'a> {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            PdfObject::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            PdfObject::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            PdfObject::String(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[PdfObject<'a>]> {
+        match self {
+            PdfObject::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&Dictionary<'a>> {
+        match self {
+            PdfObject::Dictionary(dictionary) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    pub fn as_reference(&self) -> Option<ObjectId> {
+        match self {
+            PdfObject::Reference(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// A PDF dictionary, keeping its entries in the order they appear.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dictionary<'a> {
+    entries: Vec<(Cow<'a, [u8]>, PdfObject<'a>)>,
+}
+
+impl<'a> Dictionary<'a> {
+    /// Returns the value of the entry named `key`, given without the leading
+    /// `/`.
+    pub fn get(&self, key: &[u8]) -> Option<&PdfObject<'a>> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name.as_ref() == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn entries(&self) -> &[(Cow<'a, [u8]>, PdfObject<'a>)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// The data of a stream object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Stream<'a> {
+    /// The encoded data, between the `stream` and `endstream` keywords.
+    pub data: &'a [u8],
+    /// Absolute offset of the data within the file.
+    pub offset: usize,
+}
+
+/// An object defined by an `obj` / `endobj` block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndirectObject<'a> {
+    pub id: ObjectId,
+    /// The object, which is the stream dictionary for stream objects.
+    pub object: PdfObject<'a>,
+    pub stream: Option<Stream<'a>>,
+}
+
+/// An entry of the cross-reference table.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct XrefEntry {
+    pub id: ObjectId,
+    /// Byte offset of the object, or the number of the next free object for
+    /// free entries.
+    pub offset: u64,
+    pub in_use: bool,
+}
+
+/// A cross-reference section and its trailer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct XrefSection<'a> {
+    /// Entries of the table, which is empty for cross-reference streams since
+    /// their entries are compressed.
+    pub entries: Vec<XrefEntry>,
+    /// The trailer dictionary, or the dictionary of a cross-reference stream.
+    pub trailer: Dictionary<'a>,
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+#[cfg(feature = "binary")]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    memchr::memmem::find(haystack, needle)
+}
+
+#[cfg(not(feature = "binary"))]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(feature = "binary")]
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    memchr::memmem::rfind(haystack, needle)
+}
+
+#[cfg(not(feature = "binary"))]
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+fn advance(input: In<'_>, count: usize) -> In<'_> {
+    input.over(count..input.byte_len())
+}
+
+fn failure(input: In<'_>, message: ErrorMessage) -> PdfError<'_> {
+    Error::from_message(input, message)
+}
+
+/// Returns the length of the token of regular characters at the start of
+/// `input`.
+fn token_length(input: &In<'_>) -> usize {
+    input
+        .fragment()
+        .iter()
+        .take_while(|&&b| is_regular(b))
+        .count()
+}
+
+/// Skips whitespace and comments.
+fn skip(mut input: In<'_>) -> In<'_> {
+    loop {
+        let bytes = input.fragment();
+        let count = bytes.iter().take_while(|&&b| is_whitespace(b)).count();
+        if bytes.get(count) != Some(&b'%') {
+            return advance(input, count);
+        }
+        let comment = bytes[count..]
+            .iter()
+            .take_while(|&&b| !matches!(b, b'\r' | b'\n'))
+            .count();
+        input = advance(input, count + comment);
+    }
+}
+
+/// Recognizes `keyword`, which must not be followed by a regular character.
+fn keyword<'a>(input: In<'a>, keyword: &'static str) -> PdfResult<'a, ()> {
+    let bytes = input.fragment();
+    let delimited = bytes.get(keyword.len()).is_none_or(|&b| !is_regular(b));
+    if bytes.starts_with(keyword.as_bytes()) && delimited {
+        Ok((advance(input, keyword.len()), ()))
+    } else {
+        Err(Error::from_expected(input, Expected::Tag(keyword)))
+    }
+}
+
+fn unsigned(input: In<'_>) -> PdfResult<'_, u64> {
+    let length = token_length(&input);
+    let token = &input.fragment()[..length];
+    if token.is_empty() || !token.iter().all(u8::is_ascii_digit) {
+        return Err(Error::from_expected(input, Expected::Class("digit")));
+    }
+    let value = token
+        .iter()
+        .try_fold(0u64, |value, &digit| {
+            value.checked_mul(10)?.checked_add(u64::from(digit - b'0'))
+        })
+        .ok_or_else(|| {
+            failure(
+                input,
+                with_error!(ErrorKind::MalformedData, "number is too large"),
+            )
+        })?;
+    Ok((advance(input, length), value))
+}
+
+/// Parses a direct object.
+pub fn object(input: In<'_>) -> PdfResult<'_, PdfObject<'_>> {
+    value(input, 0)
+}
+
+fn value(input: In<'_>, depth: usize) -> PdfResult<'_, PdfObject<'_>> {
+    let input = skip(input);
+    let bytes = input.fragment();
+    match bytes.first() {
+        Some(b'/') => name(input).map(|(rest, name)| (rest, PdfObject::Name(name))),
+        Some(b'(') => literal_string(input).map(|(rest, s)| (rest, PdfObject::String(s))),
+        Some(b'<') if bytes.get(1) == Some(&b'<') => {
+            let (rest, dictionary) = dictionary(input, depth)?;
+            Ok((rest, PdfObject::Dictionary(dictionary)))
+        }
+        Some(b'<') => hex_string(input).map(|(rest, s)| (rest, PdfObject::String(s))),
+        Some(b'[') => array(input, depth),
+        Some(b'+' | b'-' | b'.' | b'0'..=b'9') => number_or_reference(input),
+        _ => {
+            let token = &bytes[..token_length(&input)];
+            let object = match token {
+                b"true" => PdfObject::Bool(true),
+                b"false" => PdfObject::Bool(false),
+                b"null" => PdfObject::Null,
+                _ => return Err(Error::from_expected(input, Expected::Rule("object"))),
+            };
+            Ok((advance(input, token.len()), object))
+        }
+    }
+}
+
+fn enter(input: In<'_>, depth: usize) -> Result<(), PdfError<'_>> {
+    if depth >= MAX_DEPTH {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "exceeded the maximum nesting depth of {}",
+            MAX_DEPTH
+        );
+        return Err(failure(input, message));
+    }
+    Ok(())
+}
+
+fn number_or_reference(input: In<'_>) -> PdfResult<'_, PdfObject<'_>> {
+    let length = token_length(&input);
+    let token = &input.fragment()[..length];
+    let digits = token
+        .strip_prefix(b"+")
+        .or_else(|| token.strip_prefix(b"-"));
+    let digits = digits.unwrap_or(token);
+    let points = digits.iter().filter(|&&b| b == b'.').count();
+    let valid = points <= 1
+        && digits.iter().any(u8::is_ascii_digit)
+        && digits.iter().all(|&b| b.is_ascii_digit() || b == b'.');
+    // The token is ASCII once validated.
+    let text = core::str::from_utf8(token).unwrap_or_default();
+    let number = match (valid, points) {
+        (true, 0) => text.parse().ok().map(PdfObject::Integer),
+        (true, _) => text.parse().ok().map(PdfObject::Real),
+        _ => None,
+    };
+    let Some(number) = number else {
+        return Err(failure(
+            input,
+            with_error!(ErrorKind::MalformedData, "malformed number"),
+        ));
+    };
+    let rest = advance(input, length);
+
+    // `number generation R` is a reference rather than two integers.
+    if let PdfObject::Integer(number) = number {
+        if let Some((rest, id)) = reference_tail(rest, number) {
+            return Ok((rest, PdfObject::Reference(id)));
+        }
+    }
+    Ok((rest, number))
+}
+
+fn reference_tail(input: In<'_>, number: i64) -> Option<(In<'_>, ObjectId)> {
+    let number = u32::try_from(number).ok()?;
+    let (rest, generation) = unsigned(skip(input)).ok()?;
+    let generation = u16::try_from(generation).ok()?;
+    let (rest, ()) = keyword(skip(rest), "R").ok()?;
+    Some((rest, ObjectId { number, generation }))
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    char::from(byte).to_digit(16).map(|digit| digit as u8)
+}
+
+fn name(input: In<'_>) -> PdfResult<'_, Cow<'_, [u8]>> {
+    let body = advance(input, 1);
+    let length = token_length(&body);
+    let raw = &body.fragment()[..length];
+    if !raw.contains(&b'#') {
+        return Ok((advance(body, length), Cow::Borrowed(raw)));
+    }
+    let mut decoded = Vec::with_capacity(raw.len());
+    let mut index = 0;
+    while index < raw.len() {
+        if raw[index] == b'#' {
+            let high = raw.get(index + 1).copied().and_then(hex_digit);
+            let low = raw.get(index + 2).copied().and_then(hex_digit);
+            let (Some(high), Some(low)) = (high, low) else {
+                let message = with_error!(ErrorKind::MalformedData, "invalid `#` escape in name");
+                return Err(failure(advance(body, index), message));
+            };
+            decoded.push(high << 4 | low);
+            index += 3;
+        } else {
+            decoded.push(raw[index]);
+            index += 1;
+        }
+    }
+    Ok((advance(body, length), Cow::Owned(decoded)))
+}
+
+fn literal_string(input: In<'_>) -> PdfResult<'_, Cow<'_, [u8]>> {
+    let bytes = input.fragment();
+    let unterminated = || {
+        let message = with_error!(ErrorKind::EndOfInput, "unterminated string");
+        failure(input, message)
+    };
+
+    let mut owned: Option<Vec<u8>> = None;
+    let mut chunk = 1;
+    let mut index = 1;
+    let mut depth = 1;
+    loop {
+        match *bytes.get(index).ok_or_else(unterminated)? {
+            b'(' => {
+                depth += 1;
+                index += 1;
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                index += 1;
+            }
+            b'\\' => {
+                let owned = owned.get_or_insert_with(Vec::new);
+                owned.extend_from_slice(&bytes[chunk..index]);
+                let escaped = *bytes.get(index + 1).ok_or_else(unterminated)?;
+                index += 2;
+                match escaped {
+                    b'n' => owned.push(b'\n'),
+                    b'r' => owned.push(b'\r'),
+                    b't' => owned.push(b'\t'),
+                    b'b' => owned.push(b'\x08'),
+                    b'f' => owned.push(b'\x0C'),
+                    b'0'..=b'7' => {
+                        let mut value = u32::from(escaped - b'0');
+                        for _ in 0..2 {
+                            match bytes.get(index) {
+                                Some(&digit @ b'0'..=b'7') => {
+                                    value = value * 8 + u32::from(digit - b'0');
+                                    index += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        // High-order overflow is ignored.
+                        owned.push(value as u8);
+                    }
+                    // A backslash at the end of a line continues the string.
+                    b'\r' if bytes.get(index) == Some(&b'\n') => index += 1,
+                    b'\r' | b'\n' => {}
+                    // `\(`, `\)` and `\\` stand for the character itself, and
+                    // the backslash is ignored before any other character.
+                    other => owned.push(other),
+                }
+                chunk = index;
+            }
+            b'\r' => {
+                // End-of-line markers are read as a single line feed.
+                let owned = owned.get_or_insert_with(Vec::new);
+                owned.extend_from_slice(&bytes[chunk..index]);
+                owned.push(b'\n');
+                index += if bytes.get(index + 1) == Some(&b'\n') {
+                    2
+                } else {
+                    1
+                };
+                chunk = index;
+            }
+            _ => index += 1,
+        }
+    }
+    let string = match owned {
+        None => Cow::Borrowed(&bytes[1..index]),
+        Some(mut owned) => {
+            owned.extend_from_slice(&bytes[chunk..index]);
+            Cow::Owned(owned)
+        }
+    };
+    Ok((advance(input, index + 1), string))
+}
+
+fn hex_string(input: In<'_>) -> PdfResult<'_, Cow<'_, [u8]>> {
+    let mut decoded = Vec::new();
+    let mut pending = None;
+    for (index, &byte) in input.fragment().iter().enumerate().skip(1) {
+        if byte == b'>' {
+            // An odd number of digits is completed with a zero.
+            decoded.extend(pending.map(|high: u8| high << 4));
+            return Ok((advance(input, index + 1), Cow::Owned(decoded)));
+        }
+        if is_whitespace(byte) {
+            continue;
+        }
+        let Some(digit) = hex_digit(byte) else {
+            return Err(Error::from_expected(
+                advance(input, index),
+                Expected::Class("hexadecimal digit"),
+            ));
+        };
+        match pending.take() {
+            Some(high) => decoded.push(high << 4 | digit),
+            None => pending = Some(digit),
+        }
+    }
+    let message = with_error!(ErrorKind::EndOfInput, "unterminated hexadecimal string");
+    Err(failure(input, message))
+}
+
+fn array(input: In<'_>, depth: usize) -> PdfResult<'_, PdfObject<'_>> {
+    enter(input, depth)?;
+    let mut items = Vec::new();
+    let mut rest = skip(advance(input, 1));
+    loop {
+        match rest.fragment().first() {
+            Some(b']') => return Ok((advance(rest, 1), PdfObject::Array(items))),
+            None => {
+                let message = with_error!(ErrorKind::EndOfInput, "unterminated array");
+                return Err(failure(input, message));
+            }
+            _ => {}
+        }
+        let (after, item) = value(rest, depth + 1)?;
+        items.push(item);
+        rest = skip(after);
+    }
+}
+
+fn dictionary(input: In<'_>, depth: usize) -> PdfResult<'_, Dictionary<'_>> {
+    enter(input, depth)?;
+    let mut entries = Vec::new();
+    let mut rest = skip(advance(input, 2));
+    loop {
+        let bytes = rest.fragment();
+        if bytes.starts_with(b">>") {
+            return Ok((advance(rest, 2), Dictionary { entries }));
+        }
+        if bytes.first() != Some(&b'/') {
+            if bytes.is_empty() {
+                let message = with_error!(ErrorKind::EndOfInput, "unterminated dictionary");
+                return Err(failure(input, message));
+            }
+            let expected = [Expected::Rule("name"), Expected::Tag(">>")];
+            return Err(super::expected(rest, &expected));
+        }
+        let (after, key) = name(rest)?;
+        let (after, value) = value(after, depth + 1)?;
+        entries.push((key, value));
+        rest = skip(after);
+    }
+}
+
+/// Parses an `obj` / `endobj` block, including the data of stream objects.
+pub fn indirect_object(input: In<'_>) -> PdfResult<'_, IndirectObject<'_>> {
+    let start = skip(input);
+    let (rest, number) = unsigned(start)?;
+    let (rest, generation) = unsigned(skip(rest))?;
+    let (Ok(number), Ok(generation)) = (u32::try_from(number), u16::try_from(generation)) else {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "object identifier is out of range"
+        );
+        return Err(failure(start, message));
+    };
+    let (rest, ()) = keyword(skip(rest), "obj")?;
+    let (rest, object) = value(rest, 0)?;
+
+    let mut rest = skip(rest);
+    let mut stream = None;
+    if let (Some(dictionary), Ok((after, ()))) = (object.as_dictionary(), keyword(rest, "stream")) {
+        let (after, data) = stream_data(after, dictionary)?;
+        stream = Some(data);
+        rest = skip(after);
+    }
+    let (rest, ()) = keyword(rest, "endobj")?;
+    let id = ObjectId { number, generation };
+    Ok((rest, IndirectObject { id, object, stream }))
+}
+
+/// Reads the data following the `stream` keyword, using the `/Length` entry
+/// of `dictionary` when it is a direct integer.
+fn stream_data<'a>(input: In<'a>, dictionary: &Dictionary<'_>) -> PdfResult<'a, Stream<'a>> {
+    let bytes = input.fragment();
+    let eol = match bytes {
+        [b'\r', b'\n', ..] => 2,
+        [b'\n' | b'\r', ..] => 1,
+        _ => return Err(Error::from_expected(input, Expected::Rule("end of line"))),
+    };
+    let data = &bytes[eol..];
+    let offset = input.offset() + eol;
+
+    let declared = dictionary
+        .get(b"Length")
+        .and_then(PdfObject::as_i64)
+        .and_then(|length| usize::try_from(length).ok())
+        .filter(|&length| {
+            let after = data.get(length..).unwrap_or_default();
+            let after = skip(Located::new(after)).fragment();
+            length <= data.len() && after.starts_with(b"endstream")
+        });
+    let length = match declared {
+        Some(length) => length,
+        None => {
+            let Some(end) = find(data, b"endstream") else {
+                let message = with_error!(ErrorKind::EndOfInput, "missing `endstream`");
+                return Err(failure(input, message));
+            };
+            // The end-of-line marker before `endstream` is not part of the data.
+            let data = &data[..end];
+            let data = data.strip_suffix(b"\n").unwrap_or(data);
+            data.strip_suffix(b"\r").unwrap_or(data).len()
+        }
+    };
+    let stream = Stream {
+        data: &data[..length],
+        offset,
+    };
+    let rest = skip(advance(input, eol + length));
+    let (rest, ()) = keyword(rest, "endstream")?;
+    Ok((rest, stream))
+}
+
+/// Parses a cross-reference section and its trailer.
+///
+/// Cross-reference streams are recognized as well, in which case the section
+/// has no entries and the stream dictionary is returned as the trailer.
+pub fn xref_section(input: In<'_>) -> PdfResult<'_, XrefSection<'_>> {
+    let input = skip(input);
+    let Ok((mut rest, ())) = keyword(input, "xref") else {
+        let (rest, object) = indirect_object(input)?;
+        return match object.object {
+            PdfObject::Dictionary(trailer)
+                if trailer.get(b"Type").and_then(PdfObject::as_name) == Some(b"XRef") =>
+            {
+                Ok((
+                    rest,
+                    XrefSection {
+                        entries: Vec::new(),
+                        trailer,
+                    },
+                ))
+            }
+            _ => Err(Error::from_expected(input, Expected::Tag("xref"))),
+        };
+    };
+
+    let mut entries = Vec::new();
+    loop {
+        rest = skip(rest);
+        if let Ok((after, ())) = keyword(rest, "trailer") {
+            let after = skip(after);
+            if !after.fragment().starts_with(b"<<") {
+                return Err(Error::from_expected(after, Expected::Tag("<<")));
+            }
+            let (after, trailer) = dictionary(after, 0)?;
+            return Ok((after, XrefSection { entries, trailer }));
+        }
+        let (after, first) = unsigned(rest)?;
+        let (mut after, count) = unsigned(skip(after))?;
+        for number in first..first.saturating_add(count) {
+            let (next, offset) = unsigned(skip(after))?;
+            let (next, generation) = unsigned(skip(next))?;
+            let next = skip(next);
+            let in_use = match next.fragment().first() {
+                Some(b'n') => true,
+                Some(b'f') => false,
+                _ => {
+                    let expected = [Expected::Char('n'), Expected::Char('f')];
+                    return Err(super::expected(next, &expected));
+                }
+            };
+            let (Ok(number), Ok(generation)) = (u32::try_from(number), u16::try_from(generation))
+            else {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "object identifier is out of range"
+                );
+                return Err(failure(rest, message));
+            };
+            entries.push(XrefEntry {
+                id: ObjectId { number, generation },
+                offset,
+                in_use,
+            });
+            after = advance(next, 1);
+        }
+        rest = after;
+    }
+}
+
+/// Returns the PDF version declared by the `%PDF-` header of `data`.
+pub fn version(data: &[u8]) -> Result<(u8, u8), PdfError<'_>> {
+    let input = Located::new(data);
+    let invalid = || Error::from_expected(input, Expected::Tag("%PDF-"));
+    let header = data.strip_prefix(b"%PDF-").ok_or_else(invalid)?;
+    match header {
+        [major @ b'0'..=b'9', b'.', minor @ b'0'..=b'9', ..] => Ok((major - b'0', minor - b'0')),
+        _ => Err(invalid()),
+    }
+}
+
+/// Returns the offset of the last cross-reference section, as given after the
+/// `startxref` keyword near the end of `data`.
+pub fn startxref(data: &[u8]) -> Result<usize, PdfError<'_>> {
+    let input = Located::new(data);
+    let Some(index) = rfind(data, b"startxref") else {
+        let end = input.over(data.len()..data.len());
+        return Err(Error::from_expected(end, Expected::Tag("startxref")));
+    };
+    let (_, offset) = unsigned(skip(advance(input, index + b"startxref".len())))?;
+    usize::try_from(offset)
+        .ok()
+        .filter(|&offset| offset <= data.len())
+        .ok_or_else(|| {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "`startxref` offset is out of bounds"
+            );
+            failure(advance(input, index), message)
+        })
+}
+
+/// Parses the last cross-reference section of `data` and its trailer.
+pub fn read_xref(data: &[u8]) -> Result<XrefSection<'_>, PdfError<'_>> {
+    let offset = startxref(data)?;
+    xref_section(advance(Located::new(data), offset)).map(|(_, section)| section)
+}
+
+/// Parses the indirect object starting at `offset` in `data`.
+pub fn object_at(data: &[u8], offset: usize) -> Result<IndirectObject<'_>, PdfError<'_>> {
+    let input = Located::new(data);
+    if offset > data.len() {
+        let message = with_error!(ErrorKind::MalformedData, "object offset is out of bounds");
+        return Err(failure(input.over(data.len()..data.len()), message));
+    }
+    indirect_object(advance(input, offset)).map(|(_, object)| object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &[u8]) -> PdfObject<'_> {
+        let (rest, object) = object(Located::new(data)).unwrap();
+        assert!(
+            rest.fragment().is_empty(),
+            "unparsed: {:?}",
+            rest.fragment()
+        );
+        object
+    }
+
+    fn name(bytes: &[u8]) -> PdfObject<'_> {
+        PdfObject::Name(Cow::Borrowed(bytes))
+    }
+
+    fn string(bytes: &[u8]) -> PdfObject<'_> {
+        PdfObject::String(Cow::Borrowed(bytes))
+    }
+
+    #[test]
+    fn lexes_objects() {
+        assert_eq!(parse(b"-42"), PdfObject::Integer(-42));
+        assert_eq!(parse(b"+.5"), PdfObject::Real(0.5));
+        assert_eq!(parse(b"34."), PdfObject::Real(34.0));
+        assert_eq!(parse(b"/A#20B"), name(b"A B"));
+        assert_eq!(parse(b"/"), name(b""));
+        assert_eq!(parse(b"(a (nested) string)"), string(b"a (nested) string"));
+        assert_eq!(parse(b"(\\(\\)\\\\\\101\\0053\\q)"), string(b"()\\A\x053q"));
+        assert_eq!(
+            parse(b"(split \\\r\nline\r\nend)"),
+            string(b"split line\nend")
+        );
+        assert_eq!(parse(b"<48 65 6c6C 6>"), string(b"Hell\x60"));
+        assert_eq!(
+            parse(b"[1 2 0 R 3 % comment\n true null]"),
+            PdfObject::Array(vec![
+                PdfObject::Integer(1),
+                PdfObject::Reference(ObjectId {
+                    number: 2,
+                    generation: 0
+                }),
+                PdfObject::Integer(3),
+                PdfObject::Bool(true),
+                PdfObject::Null,
+            ])
+        );
+
+        let object = parse(b"<</Type/Page/Kids[3 0 R]/Rotate 90>>");
+        let dictionary = object.as_dictionary().unwrap();
+        assert_eq!(dictionary.len(), 3);
+        assert_eq!(dictionary.get(b"Type"), Some(&name(b"Page")));
+        assert_eq!(
+            dictionary.get(b"Rotate").and_then(PdfObject::as_i64),
+            Some(90)
+        );
+    }
+
+    #[test]
+    fn reads_indirect_objects_and_streams() {
+        let data = b"7 0 obj\n<< /Length 5 >>\nstream\r\nhello\nendstream\nendobj\n\
+                     8 1 obj\n<< /Length 9 0 R >>\nstream\nbytes\r\nendstream endobj";
+        let (rest, object) = indirect_object(Located::new(&data[..])).unwrap();
+        assert_eq!(
+            object.id,
+            ObjectId {
+                number: 7,
+                generation: 0
+            }
+        );
+        let stream = object.stream.unwrap();
+        assert_eq!((stream.data, stream.offset), (&b"hello"[..], 32));
+
+        let (_, object) = indirect_object(rest).unwrap();
+        assert_eq!(
+            object.id,
+            ObjectId {
+                number: 8,
+                generation: 1
+            }
+        );
+        assert_eq!(object.stream.unwrap().data, b"bytes");
+    }
+
+    #[test]
+    fn reads_xref_table_and_trailer() {
+        let data = b"xref\n0 2\n0000000000 65535 f\r\n0000000017 00000 n\r\n\
+                     5 1\n0000000081 00002 n \ntrailer\n<< /Size 6 /Root 1 0 R >>\n\
+                     startxref\n0\n%%EOF\n";
+        let section = read_xref(data).unwrap();
+        assert_eq!(section.entries.len(), 3);
+        assert!(!section.entries[0].in_use);
+        assert_eq!(section.entries[1].offset, 17);
+        assert_eq!(
+            section.entries[2].id,
+            ObjectId {
+                number: 5,
+                generation: 2
+            }
+        );
+        assert_eq!(
+            section
+                .trailer
+                .get(b"Root")
+                .and_then(PdfObject::as_reference),
+            Some(ObjectId {
+                number: 1,
+                generation: 0
+            })
+        );
+    }
+
+    #[test]
+    fn errors_span_the_failure() {
+        let error = object(Located::new(&b"[1 (open"[..])).unwrap_err();
+        assert_eq!(error.offset(), Some((3, 3)));
+        assert_eq!(error.kind(), ErrorKind::EndOfInput);
+
+        let error = object_at(b"1 0 obj 42 end", 0).unwrap_err();
+        assert_eq!(error.offset(), Some((11, 11)));
+        assert_eq!(error.to_string(), "expected `endobj`, found `e`");
+
+        let error = object(Located::new(&b"<< /A 1 2 >>"[..])).unwrap_err();
+        assert_eq!(error.to_string(), "expected one of name, `>>`, found `2`");
+
+        let deep = [b'['; 200];
+        let error = object(Located::new(&deep[..])).unwrap_err();
+        assert_eq!(error.offset(), Some((MAX_DEPTH, MAX_DEPTH)));
+    }
+
+    #[test]
+    fn reads_resume_fixture() {
+        let data = include_bytes!("../../mock/resume.pdf");
+        assert!(version(data).is_ok());
+        let section = read_xref(data).unwrap();
+        assert!(section
+            .trailer
+            .get(b"Root")
+            .and_then(PdfObject::as_reference)
+            .is_some());
+        for entry in section.entries.iter().filter(|entry| entry.in_use) {
+            let object = object_at(data, entry.offset as usize).unwrap();
+            assert_eq!(object.id, entry.id);
+        }
+    }
+}
@@ -0,0 +1,757 @@
+//! This module contains a parser for the chunks and metadata of PNG images.
+//!
+//! [`chunks`] checks the signature, then iterates over the chunks of an
+//! image, verifying their CRC and the ordering rules of the specification.
+//! The data of the chunks holding metadata is decoded by [`header`],
+//! [`palette`], [`text`] and [`international_text`]. Image data and
+//! compressed text are returned as they are, since inflating them is beyond
+//! the scope of the parser.
+//!
+//! Chunk data is located within the whole file, so that errors raised while
+//! decoding it point to the offending bytes of the file.
+//!
+//! ```
+//! use recoil::formats::png::{self, ColorType};
+//!
+//! let image = b"\x89PNG\r\n\x1A\n\
+//!     \0\0\0\x0DIHDR\0\0\0\x01\0\0\0\x01\x08\0\0\0\0\x3A\x7E\x9B\x55\
+//!     \0\0\0\x09tEXtTitle\0Dot\xF4\x61\x06\x6C\
+//!     \0\0\0\x0AIDAT\x78\x9C\x63\x60\0\0\0\x02\0\x01\x48\xAF\xA4\x71\
+//!     \0\0\0\0IEND\xAE\x42\x60\x82";
+//!
+//! let mut chunks = png::chunks(image);
+//! let header = png::header(chunks.next().unwrap().unwrap().data).unwrap();
+//! assert_eq!((header.width, header.height), (1, 1));
+//! assert_eq!(header.color_type, ColorType::Grayscale);
+//!
+//! let text = png::text(chunks.next().unwrap().unwrap().data).unwrap();
+//! assert_eq!((text.keyword, text.text), (&b"Title"[..], &b"Dot"[..]));
+//!
+//! let names: Vec<_> = chunks.map(|chunk| chunk.unwrap().kind).collect();
+//! assert_eq!(names, [*b"IDAT", *b"IEND"]);
+//! ```
+
+use core::str;
+
+use super::spanning;
+use crate::checksum::{checksummed, Checksum};
+use crate::combinator::be_u32;
+use crate::error::{ErrorKind, ErrorMessage, ErrorWithContext};
+use crate::located::Located;
+use crate::span::Span;
+use crate::{with_error, AResult};
+
+/// Error returned when parsing fails, spanning the offending bytes.
+pub type PngError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+type In<'a> = Located<&'a [u8]>;
+
+/// The eight bytes every PNG image starts with.
+pub const SIGNATURE: [u8; 8] = *b"\x89PNG\r\n\x1A\n";
+
+/// Largest length of chunk data, and of the dimensions of an image.
+const MAX_LENGTH: u32 = (1 << 31) - 1;
+
+/// Ancillary chunks that must precede the image data.
+const BEFORE_DATA: [[u8; 4]; 10] = [
+    *b"bKGD", *b"cHRM", *b"gAMA", *b"hIST", *b"iCCP", *b"pHYs", *b"sBIT", *b"sPLT", *b"sRGB",
+    *b"tRNS",
+];
+
+/// A chunk, whose CRC has been verified.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Chunk<'a> {
+    /// The chunk type, made of four ASCII letters.
+    pub kind: [u8; 4],
+    pub data: Located<&'a [u8]>,
+}
+
+impl Chunk<'_> {
+    /// Returns the chunk type as a string, or `None` if `kind` is not made of
+    /// ASCII letters, which never happens for chunks returned by [`chunk`].
+    pub fn name(&self) -> Option<&str> {
+        match self.kind.iter().all(u8::is_ascii_alphabetic) {
+            true => str::from_utf8(&self.kind).ok(),
+            false => None,
+        }
+    }
+
+    /// Returns `true` if the chunk is necessary to display the image.
+    pub fn is_critical(&self) -> bool {
+        self.kind[0].is_ascii_uppercase()
+    }
+
+    /// Returns `true` if editors unaware of the chunk type may copy it to a
+    /// modified image.
+    pub fn is_safe_to_copy(&self) -> bool {
+        self.kind[3].is_ascii_lowercase()
+    }
+}
+
+/// Parses the signature at the start of `input`.
+pub fn signature(input: In<'_>) -> AResult<In<'_>, (), PngError<'_>> {
+    let length = input.byte_len().min(SIGNATURE.len());
+    if input.fragment()[..length] != SIGNATURE[..length] {
+        let message = with_error!(ErrorKind::MalformedData, "invalid PNG signature");
+        return Err(spanning(input, 0, length, message));
+    }
+    if length < SIGNATURE.len() {
+        let message = with_error!(ErrorKind::EndOfInput, "truncated PNG signature");
+        return Err(spanning(input, 0, length, message));
+    }
+    Ok((input.over(SIGNATURE.len()..input.byte_len()), ()))
+}
+
+/// Parses the chunk at the start of `input` and verifies its CRC, without
+/// checking where it may appear.
+pub fn chunk<'a>(input: In<'a>) -> AResult<In<'a>, Chunk<'a>, PngError<'a>> {
+    let truncated = || {
+        let message = with_error!(ErrorKind::EndOfInput, "truncated chunk");
+        spanning(input, 0, input.byte_len(), message)
+    };
+    let (rest, length) = be_u32(input).map_err(|_: PngError<'a>| truncated())?;
+    if length > MAX_LENGTH {
+        let message = with_error!(ErrorKind::MalformedData, "chunk length exceeds 2^31 - 1");
+        return Err(spanning(input, 0, 4, message));
+    }
+    let length = length as usize;
+    let body = |rest: In<'a>| {
+        if rest.byte_len() < 4 + length {
+            return Err(truncated());
+        }
+        let kind = rest.over(0..4);
+        let data = rest.over(4..4 + length);
+        Ok((rest.over(4 + length..rest.byte_len()), (kind, data)))
+    };
+    let (rest, (kind, data)) =
+        checksummed(body, Checksum::Crc32, be_u32)(rest).map_err(|error| match error.kind() {
+            ErrorKind::EndOfInput => truncated(),
+            _ => error,
+        })?;
+    // `body` took exactly four bytes for the chunk type.
+    let kind: [u8; 4] = kind.fragment().try_into().unwrap();
+    if !kind.iter().all(u8::is_ascii_alphabetic) {
+        let message = with_error!(ErrorKind::MalformedData, "invalid chunk type");
+        return Err(spanning(input, 4, 8, message));
+    }
+    Ok((rest, Chunk { kind, data }))
+}
+
+/// Returns an iterator over the chunks of the PNG image `input`.
+pub fn chunks(input: &[u8]) -> Chunks<'_> {
+    Chunks {
+        input: Located::new(input),
+        order: Order::Signature,
+        palette: false,
+    }
+}
+
+/// Iterator over the chunks of an image, created by [`chunks`].
+///
+/// It returns an error for an invalid signature, a chunk that cannot be
+/// parsed or appears out of order, a missing `IEND` chunk or data after it,
+/// then stops.
+#[derive(Clone, Debug)]
+pub struct Chunks<'a> {
+    input: In<'a>,
+    order: Order,
+    /// Whether a `PLTE` chunk was seen.
+    palette: bool,
+}
+
+/// Position in the sequence of chunks, relative to the image data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Order {
+    Signature,
+    Header,
+    BeforeData,
+    Data,
+    AfterData,
+    End,
+}
+
+impl<'a> Chunks<'a> {
+    fn next_chunk(&mut self) -> Result<Option<Chunk<'a>>, PngError<'a>> {
+        match self.order {
+            Order::Signature => {
+                self.input = signature(self.input)?.0;
+                self.order = Order::Header;
+            }
+            Order::End if self.input.fragment().is_empty() => return Ok(None),
+            Order::End => {
+                let message = with_error!(ErrorKind::MalformedData, "data after IEND chunk");
+                return Err(spanning(self.input, 0, self.input.byte_len(), message));
+            }
+            _ => {}
+        }
+        if self.input.fragment().is_empty() {
+            let message = with_error!(ErrorKind::EndOfInput, "missing IEND chunk");
+            return Err(spanning(self.input, 0, 0, message));
+        }
+        let (rest, chunk) = chunk(self.input)?;
+        if let Err(message) = self.check_order(&chunk) {
+            return Err(spanning(self.input, 4, 8, message));
+        }
+        self.input = rest;
+        Ok(Some(chunk))
+    }
+
+    /// Checks that `chunk` may appear next and advances the order.
+    fn check_order(&mut self, chunk: &Chunk<'_>) -> Result<(), ErrorMessage> {
+        let message = match (&chunk.kind, self.order) {
+            (b"IHDR", Order::Header) => {
+                self.order = Order::BeforeData;
+                return Ok(());
+            }
+            (_, Order::Header) => "the first chunk must be IHDR",
+            (b"IHDR", _) => "IHDR chunk must appear once",
+            (b"PLTE", Order::BeforeData) if !self.palette => {
+                self.palette = true;
+                return Ok(());
+            }
+            (b"PLTE", Order::BeforeData) => "PLTE chunk must appear at most once",
+            (b"PLTE", _) => "PLTE chunk must precede the IDAT chunks",
+            (b"IDAT", Order::BeforeData | Order::Data) => {
+                self.order = Order::Data;
+                return Ok(());
+            }
+            (b"IDAT", _) => "IDAT chunks must be consecutive",
+            (b"IEND", Order::BeforeData) => "missing IDAT chunk",
+            (b"IEND", _) => {
+                self.order = Order::End;
+                return Ok(());
+            }
+            (b"cHRM" | b"gAMA" | b"iCCP" | b"sBIT" | b"sRGB", _) if self.palette => {
+                "color space chunks must precede the PLTE chunk"
+            }
+            (b"hIST", _) if !self.palette => "hIST chunk must follow the PLTE chunk",
+            (kind, Order::Data | Order::AfterData) if BEFORE_DATA.contains(kind) => {
+                "chunk must precede the IDAT chunks"
+            }
+            (_, Order::Data) => {
+                self.order = Order::AfterData;
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        Err(ErrorMessage::new(ErrorKind::MalformedData, message))
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, PngError<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.input.fragment().is_empty() && self.order == Order::End {
+            return None;
+        }
+        let result = self.next_chunk().transpose();
+        if let Some(Err(_)) = result {
+            self.order = Order::End;
+            self.input = self
+                .input
+                .over(self.input.byte_len()..self.input.byte_len());
+        }
+        result
+    }
+}
+
+/// How the samples of a pixel are interpreted.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    /// Each pixel is an index into the palette.
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    /// Returns the number of samples in a pixel.
+    pub fn channels(self) -> u8 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+/// The image header, held by the `IHDR` chunk.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Header {
+    pub width: u32,
+    pub height: u32,
+    /// The number of bits per sample, or per palette index.
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    /// Whether the image uses Adam7 interlacing.
+    pub interlaced: bool,
+}
+
+/// Decodes the data of an `IHDR` chunk.
+pub fn header(data: In<'_>) -> Result<Header, PngError<'_>> {
+    let bytes = data.fragment();
+    if bytes.len() != 13 {
+        let message = with_error!(ErrorKind::MalformedData, "IHDR chunk must be 13 bytes long");
+        return Err(spanning(data, 0, bytes.len(), message));
+    }
+    let invalid = |at: usize, message| {
+        let message = ErrorMessage::new(ErrorKind::MalformedData, message);
+        Err(spanning(data, at, at + 1, message))
+    };
+    let dimension = |at: usize| {
+        // The header is 13 bytes long, and dimensions start at 0 and 4.
+        let value = u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+        if value == 0 || value > MAX_LENGTH {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "image dimensions must be between 1 and 2^31 - 1"
+            );
+            return Err(spanning(data, at, at + 4, message));
+        }
+        Ok(value)
+    };
+    let (width, height) = (dimension(0)?, dimension(4)?);
+    let bit_depth = bytes[8];
+    let (color_type, depths): (_, &[u8]) = match bytes[9] {
+        0 => (ColorType::Grayscale, &[1, 2, 4, 8, 16]),
+        2 => (ColorType::Rgb, &[8, 16]),
+        3 => (ColorType::Indexed, &[1, 2, 4, 8]),
+        4 => (ColorType::GrayscaleAlpha, &[8, 16]),
+        6 => (ColorType::Rgba, &[8, 16]),
+        _ => return invalid(9, "invalid color type"),
+    };
+    if !depths.contains(&bit_depth) {
+        return invalid(8, "invalid bit depth for the color type");
+    }
+    if bytes[10] != 0 {
+        return invalid(10, "unknown compression method");
+    }
+    if bytes[11] != 0 {
+        return invalid(11, "unknown filter method");
+    }
+    let interlaced = match bytes[12] {
+        0 => false,
+        1 => true,
+        _ => return invalid(12, "unknown interlace method"),
+    };
+    Ok(Header {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        interlaced,
+    })
+}
+
+/// The palette, held by the `PLTE` chunk.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Palette<'a> {
+    entries: &'a [u8],
+}
+
+impl<'a> Palette<'a> {
+    /// Returns the number of entries, between 1 and 256.
+    pub fn len(&self) -> usize {
+        self.entries.len() / 3
+    }
+
+    /// Always returns `false`, as a palette has at least one entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the red, green and blue samples of the entry at `index`.
+    pub fn get(&self, index: usize) -> Option<[u8; 3]> {
+        let entry = self.entries.get(index * 3..index * 3 + 3)?;
+        entry.try_into().ok()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = [u8; 3]> + 'a {
+        self.entries
+            .chunks_exact(3)
+            .map(|entry| [entry[0], entry[1], entry[2]])
+    }
+}
+
+/// Decodes the data of a `PLTE` chunk.
+pub fn palette(data: In<'_>) -> Result<Palette<'_>, PngError<'_>> {
+    let entries = data.fragment();
+    if entries.is_empty() || entries.len() > 256 * 3 || !entries.len().is_multiple_of(3) {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "PLTE chunk must hold between 1 and 256 entries of 3 bytes"
+        );
+        return Err(spanning(data, 0, entries.len(), message));
+    }
+    Ok(Palette { entries })
+}
+
+/// Text held by a `tEXt` chunk, encoded in Latin-1.
+///
+/// Each byte of Latin-1 text is the code point of a character, which
+/// `text.iter().map(|&byte| char::from(byte))` decodes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Text<'a> {
+    pub keyword: &'a [u8],
+    pub text: &'a [u8],
+}
+
+/// Decodes the data of a `tEXt` chunk.
+pub fn text(data: In<'_>) -> Result<Text<'_>, PngError<'_>> {
+    let (keyword, rest) = keyword(data)?;
+    Ok(Text {
+        keyword,
+        text: rest.fragment(),
+    })
+}
+
+/// International text held by an `iTXt` chunk.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct InternationalText<'a> {
+    /// The keyword, encoded in Latin-1.
+    pub keyword: &'a [u8],
+    /// Whether the text is compressed with zlib.
+    pub compressed: bool,
+    /// An RFC 5646 language tag, which may be empty.
+    pub language: &'a str,
+    pub translated_keyword: &'a str,
+    /// The text, which is valid UTF-8 unless it is compressed.
+    pub text: &'a [u8],
+}
+
+impl<'a> InternationalText<'a> {
+    /// Returns the text if it is not compressed.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.compressed {
+            true => None,
+            false => str::from_utf8(self.text).ok(),
+        }
+    }
+}
+
+/// Decodes the data of an `iTXt` chunk.
+pub fn international_text(data: In<'_>) -> Result<InternationalText<'_>, PngError<'_>> {
+    let (keyword, rest) = keyword(data)?;
+    let (compressed, rest) = match rest.fragment() {
+        [0, 0, ..] => (false, rest.over(2..rest.byte_len())),
+        [1, 0, ..] => (true, rest.over(2..rest.byte_len())),
+        [0 | 1, ..] => {
+            let message = with_error!(ErrorKind::MalformedData, "unknown compression method");
+            return Err(spanning(rest, 1, rest.byte_len().min(2), message));
+        }
+        _ => {
+            let message = with_error!(ErrorKind::MalformedData, "invalid compression flag");
+            return Err(spanning(rest, 0, rest.byte_len().min(1), message));
+        }
+    };
+    let (language, after) = utf8_field(rest)?;
+    if !language
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    {
+        let message = with_error!(ErrorKind::MalformedData, "invalid language tag");
+        return Err(spanning(rest, 0, language.len(), message));
+    }
+    let (translated_keyword, rest) = utf8_field(after)?;
+    let text = rest.fragment();
+    if let (false, Err(error)) = (compressed, str::from_utf8(text)) {
+        let at = error.valid_up_to();
+        let message = with_error!(ErrorKind::MalformedData, "invalid UTF-8 in text");
+        return Err(spanning(
+            rest,
+            at,
+            at + error.error_len().unwrap_or(1),
+            message,
+        ));
+    }
+    Ok(InternationalText {
+        keyword,
+        compressed,
+        language,
+        translated_keyword,
+        text,
+    })
+}
+
+/// Splits a null-terminated keyword from the start of `data`, checking that
+/// it is 1 to 79 printable Latin-1 characters without extra spaces.
+fn keyword(data: In<'_>) -> Result<(&[u8], In<'_>), PngError<'_>> {
+    let bytes = data.fragment();
+    let Some(length) = bytes.iter().position(|&b| b == 0) else {
+        let message = with_error!(ErrorKind::EndOfInput, "unterminated keyword");
+        return Err(spanning(data, 0, bytes.len(), message));
+    };
+    let keyword = &bytes[..length];
+    let printable = |&b: &u8| matches!(b, 32..=126 | 161..=255);
+    let valid = (1..80).contains(&length)
+        && keyword.iter().all(printable)
+        && !keyword.starts_with(b" ")
+        && !keyword.ends_with(b" ")
+        && !keyword.windows(2).any(|pair| pair == b"  ");
+    if !valid {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "keywords must be 1 to 79 printable Latin-1 characters without extra spaces"
+        );
+        return Err(spanning(data, 0, length, message));
+    }
+    Ok((keyword, data.over(length + 1..bytes.len())))
+}
+
+/// Splits a null-terminated UTF-8 field from the start of `data`.
+fn utf8_field(data: In<'_>) -> Result<(&str, In<'_>), PngError<'_>> {
+    let bytes = data.fragment();
+    let Some(length) = bytes.iter().position(|&b| b == 0) else {
+        let message = with_error!(ErrorKind::EndOfInput, "unterminated field");
+        return Err(spanning(data, 0, bytes.len(), message));
+    };
+    match str::from_utf8(&bytes[..length]) {
+        Ok(field) => Ok((field, data.over(length + 1..bytes.len()))),
+        Err(error) => {
+            let at = error.valid_up_to();
+            let message = with_error!(ErrorKind::MalformedData, "invalid UTF-8 in text");
+            Err(spanning(
+                data,
+                at,
+                at + error.error_len().unwrap_or(1),
+                message,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_bytes(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(data);
+        let crc = Checksum::Crc32.compute(&bytes[4..]);
+        bytes.extend_from_slice(&crc.to_be_bytes());
+        bytes
+    }
+
+    type ChunkList<'a> = [(&'a [u8; 4], &'a [u8])];
+
+    fn encode(chunks: &ChunkList<'_>) -> Vec<u8> {
+        let mut image = SIGNATURE.to_vec();
+        for (kind, data) in chunks {
+            image.extend(chunk_bytes(kind, data));
+        }
+        image
+    }
+
+    const IHDR: &[u8] = b"\0\0\0\x20\0\0\0\x10\x04\x03\0\0\x01";
+
+    fn failure(image: &[u8]) -> (String, (usize, usize)) {
+        let error = chunks(image).find_map(Result::err).unwrap();
+        (error.to_string(), error.offset().unwrap())
+    }
+
+    #[test]
+    fn iterates_over_chunks() {
+        let image = encode(&[
+            (b"IHDR", IHDR),
+            (b"gAMA", b"\0\0\xB1\x8F"),
+            (b"PLTE", b"\xFF\0\0\0\xFF\0"),
+            (b"tRNS", b"\x80"),
+            (b"IDAT", b"\x01"),
+            (b"IDAT", b"\x02"),
+            (b"tEXt", b"Comment\0hi"),
+            (b"IEND", b""),
+        ]);
+        let chunks: Vec<_> = chunks(&image).collect::<Result<_, _>>().unwrap();
+        let names: Vec<_> = chunks.iter().map(|chunk| chunk.name().unwrap()).collect();
+        assert_eq!(
+            names,
+            ["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]
+        );
+        let mut invalid = chunks[0];
+        invalid.kind = *b"I\xC3\xA9R";
+        assert_eq!(invalid.name(), None);
+        assert!(chunks[2].is_critical() && !chunks[2].is_safe_to_copy());
+        assert!(!chunks[6].is_critical() && chunks[6].is_safe_to_copy());
+        assert_eq!(chunks[0].data.offset(), 16);
+
+        let header = header(chunks[0].data).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                width: 32,
+                height: 16,
+                bit_depth: 4,
+                color_type: ColorType::Indexed,
+                interlaced: true,
+            }
+        );
+        assert_eq!(header.color_type.channels(), 1);
+
+        let palette = palette(chunks[2].data).unwrap();
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.get(1), Some([0, 0xFF, 0]));
+        assert_eq!(palette.get(2), None);
+        assert_eq!(palette.iter().next(), Some([0xFF, 0, 0]));
+    }
+
+    #[test]
+    fn rejects_corrupt_chunks() {
+        let mut image = encode(&[(b"IHDR", IHDR), (b"IDAT", b""), (b"IEND", b"")]);
+        assert_eq!(
+            failure(&image[..5]),
+            ("truncated PNG signature".into(), (0, 5))
+        );
+        assert_eq!(failure(b"GIF89a"), ("invalid PNG signature".into(), (0, 6)));
+        assert_eq!(failure(&image[..40]), ("truncated chunk".into(), (33, 40)));
+        assert_eq!(failure(&image[..45]).0, "missing IEND chunk");
+
+        let mut trailing = image.clone();
+        trailing.push(0);
+        assert_eq!(
+            failure(&trailing),
+            ("data after IEND chunk".into(), (57, 58))
+        );
+
+        image[20] ^= 1;
+        assert_eq!(failure(&image), ("checksum mismatch".into(), (29, 29)));
+
+        let mut iter = chunks(&image);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        let mut image = encode(&[(b"IH\0R", IHDR)]);
+        assert_eq!(failure(&image), ("invalid chunk type".into(), (12, 16)));
+        image.truncate(29);
+        image.extend_from_slice(&[0; 4]);
+        assert_eq!(failure(&image).1, (29, 29));
+    }
+
+    #[test]
+    fn rejects_misordered_chunks() {
+        let cases: [(&ChunkList<'_>, &str); 8] = [
+            (&[(b"IDAT", b"")], "the first chunk must be IHDR"),
+            (
+                &[(b"IHDR", IHDR), (b"IHDR", IHDR)],
+                "IHDR chunk must appear once",
+            ),
+            (
+                &[(b"IHDR", IHDR), (b"IDAT", b""), (b"PLTE", b"\0\0\0")],
+                "PLTE chunk must precede the IDAT chunks",
+            ),
+            (
+                &[
+                    (b"IHDR", IHDR),
+                    (b"IDAT", b""),
+                    (b"tEXt", b"a\0"),
+                    (b"IDAT", b""),
+                ],
+                "IDAT chunks must be consecutive",
+            ),
+            (&[(b"IHDR", IHDR), (b"IEND", b"")], "missing IDAT chunk"),
+            (
+                &[
+                    (b"IHDR", IHDR),
+                    (b"PLTE", b"\0\0\0"),
+                    (b"gAMA", b"\0\0\0\0"),
+                ],
+                "color space chunks must precede the PLTE chunk",
+            ),
+            (
+                &[(b"IHDR", IHDR), (b"hIST", b"\0\0")],
+                "hIST chunk must follow the PLTE chunk",
+            ),
+            (
+                &[(b"IHDR", IHDR), (b"IDAT", b""), (b"pHYs", b"")],
+                "chunk must precede the IDAT chunks",
+            ),
+        ];
+        for (chunks, message) in cases {
+            let image = encode(chunks);
+            let (error, (start, end)) = failure(&image);
+            assert_eq!(error, message);
+            assert_eq!(&image[start..end], chunks.last().unwrap().0);
+        }
+    }
+
+    #[test]
+    fn decodes_headers() {
+        let data = |bytes: &'static [u8]| Located::new(bytes);
+        let error = |bytes: &'static [u8]| {
+            let error = header(data(bytes)).unwrap_err();
+            (error.to_string(), error.offset().unwrap())
+        };
+        let header = header(data(b"\0\0\x01\0\0\0\0\x01\x10\x06\0\0\0")).unwrap();
+        assert_eq!((header.width, header.bit_depth), (256, 16));
+        assert_eq!(
+            (header.color_type, header.interlaced),
+            (ColorType::Rgba, false)
+        );
+
+        assert_eq!(
+            error(b"\0\0\0\x01"),
+            ("IHDR chunk must be 13 bytes long".into(), (0, 4))
+        );
+        assert_eq!(error(b"\0\0\0\0\0\0\0\x01\x08\x00\0\0\0").1, (0, 4));
+        assert_eq!(error(b"\x80\0\0\0\0\0\0\x01\x08\x00\0\0\0").1, (0, 4));
+        assert_eq!(
+            error(b"\0\0\0\x01\0\0\0\x01\x10\x03\0\0\0"),
+            ("invalid bit depth for the color type".into(), (8, 9))
+        );
+        assert_eq!(
+            error(b"\0\0\0\x01\0\0\0\x01\x08\x05\0\0\0").0,
+            "invalid color type"
+        );
+        assert_eq!(error(b"\0\0\0\x01\0\0\0\x01\x08\x00\x01\0\0").1, (10, 11));
+        assert_eq!(error(b"\0\0\0\x01\0\0\0\x01\x08\x00\0\0\x02").1, (12, 13));
+
+        assert!(palette(data(b"\0\0\0\0")).is_err());
+        assert!(palette(data(b"")).is_err());
+        assert!(palette(data(&[0; 257 * 3])).is_err());
+    }
+
+    #[test]
+    fn decodes_text() {
+        let data = Located::new(&b"Title\0Caf\xE9"[..]);
+        let text = text(data).unwrap();
+        assert_eq!(text.keyword, b"Title");
+        let decoded: String = text.text.iter().map(|&byte| char::from(byte)).collect();
+        assert_eq!(decoded, "Café");
+
+        let data = Located::new(&b"Title\0\0\0fr\0Titre\0Caf\xC3\xA9"[..]);
+        let international = international_text(data).unwrap();
+        assert_eq!(international.language, "fr");
+        assert_eq!(international.translated_keyword, "Titre");
+        assert_eq!(international.as_str(), Some("Café"));
+
+        let data = Located::new(&b"Title\0\x01\0\0\0\x78\x9C"[..]);
+        let compressed = international_text(data).unwrap();
+        assert!(compressed.compressed);
+        assert_eq!(
+            (compressed.as_str(), compressed.text),
+            (None, &b"\x78\x9C"[..])
+        );
+
+        let error = |bytes: &'static [u8]| {
+            let error = international_text(Located::new(bytes)).unwrap_err();
+            (error.to_string(), error.offset().unwrap())
+        };
+        assert_eq!(error(b"Title\0\0\0\0\0\xFF").1, (10, 11));
+        assert_eq!(error(b"Title\0\x02\0\0\0").0, "invalid compression flag");
+        assert_eq!(error(b"Title\0\x01\x01\0\0").1, (7, 8));
+        assert_eq!(
+            error(b"Title\0\0\0f_r\0\0"),
+            ("invalid language tag".into(), (8, 11))
+        );
+        assert_eq!(error(b"Title\0\0\0en").0, "unterminated field");
+        assert_eq!(error(b" Title\0\0\0\0\0").1, (0, 6));
+        assert_eq!(error(b"A  B\0\0\0\0\0").1, (0, 4));
+        assert_eq!(error(b"\0\0\0\0\0").1, (0, 0));
+        assert_eq!(error(b"Title").0, "unterminated keyword");
+    }
+}
@@ -0,0 +1,497 @@
+//! This module contains a reader for the Protocol Buffers wire format.
+//!
+//! Without the schema of a message, its encoding can only be split into
+//! fields, each made of a number, a wire type and a value whose meaning
+//! depends on the schema. [`field`] reads one field and [`fields`] iterates
+//! over those of a message, borrowing length-delimited values and groups
+//! from the input.
+//!
+//! A length-delimited value is a string, bytes, a packed repeated field or a
+//! nested message. With the `alloc` feature, [`guess`] also decodes the
+//! values that parse as messages, recursively, as debugging dumps do.
+//!
+//! ```
+//! use recoil::formats::protobuf::{self, Value, WireType};
+//!
+//! // A message with field 1 set to 150 and field 2 set to "testing".
+//! let input = b"\x08\x96\x01\x12\x07testing";
+//! let fields: Vec<_> = protobuf::fields(input).collect::<Result<_, _>>().unwrap();
+//! assert_eq!(fields[0].number, 1);
+//! assert_eq!(fields[0].value, Value::Varint(150));
+//! assert_eq!(fields[1].wire_type(), WireType::LengthDelimited);
+//! assert_eq!(fields[1].value.as_bytes(), Some(&b"testing"[..]));
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::str;
+
+use super::spanning;
+use crate::error::{ErrorKind, ErrorMessage, ErrorWithContext};
+use crate::framing::{varint, VarintError};
+use crate::located::Located;
+use crate::span::Span;
+use crate::{with_error, AResult};
+
+/// Error returned when reading fails, spanning the offending bytes.
+pub type ProtobufError<'a> = ErrorWithContext<Located<&'a [u8]>>;
+
+/// Maximum nesting of groups, and of messages guessed by [`guess`].
+pub const MAX_DEPTH: usize = 100;
+
+/// Largest valid field number.
+const MAX_NUMBER: u64 = (1 << 29) - 1;
+
+/// The encoding of a field, given by the low three bits of its tag.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WireType {
+    Varint,
+    Fixed64,
+    LengthDelimited,
+    StartGroup,
+    EndGroup,
+    Fixed32,
+}
+
+impl WireType {
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        let wire_type = match bits {
+            0 => WireType::Varint,
+            1 => WireType::Fixed64,
+            2 => WireType::LengthDelimited,
+            3 => WireType::StartGroup,
+            4 => WireType::EndGroup,
+            5 => WireType::Fixed32,
+            _ => return None,
+        };
+        Some(wire_type)
+    }
+}
+
+/// The value of a field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Value<'a> {
+    /// An `int32`, `int64`, `uint32`, `uint64`, `sint32`, `sint64`, `bool`
+    /// or `enum`.
+    Varint(u64),
+    /// A `fixed64`, `sfixed64` or `double`.
+    Fixed64(u64),
+    /// A string, bytes, an embedded message or a packed repeated field.
+    LengthDelimited(&'a [u8]),
+    /// The encoded fields of a group, without its end tag.
+    Group(&'a [u8]),
+    /// A `fixed32`, `sfixed32` or `float`.
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    /// Returns a varint decoded as a `sint32` or `sint64`, which use zigzag
+    /// encoding.
+    pub fn as_sint(&self) -> Option<i64> {
+        match *self {
+            Value::Varint(value) => Some((value >> 1) as i64 ^ -((value & 1) as i64)),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            Value::LengthDelimited(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// A field of a message.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Field<'a> {
+    pub number: u32,
+    pub value: Value<'a>,
+}
+
+impl Field<'_> {
+    /// Returns the wire type the field was encoded with, which is
+    /// [`WireType::StartGroup`] for groups.
+    pub fn wire_type(&self) -> WireType {
+        match self.value {
+            Value::Varint(_) => WireType::Varint,
+            Value::Fixed64(_) => WireType::Fixed64,
+            Value::LengthDelimited(_) => WireType::LengthDelimited,
+            Value::Group(_) => WireType::StartGroup,
+            Value::Fixed32(_) => WireType::Fixed32,
+        }
+    }
+}
+
+/// Reads the field at the start of `input`, including the whole of a group.
+pub fn field(input: Located<&[u8]>) -> AResult<Located<&[u8]>, Field<'_>, ProtobufError<'_>> {
+    let mut reader = Reader {
+        input,
+        bytes: input.fragment(),
+        at: 0,
+    };
+    match reader.item(0)? {
+        Item::Field(field) => Ok((input.over(reader.at..input.byte_len()), field)),
+        Item::End(_) => {
+            let message = with_error!(ErrorKind::MalformedData, "unexpected end of group");
+            Err(reader.error(0, reader.at, message))
+        }
+    }
+}
+
+/// Returns an iterator over the fields of the encoded message `input`.
+pub fn fields(input: &[u8]) -> Fields<'_> {
+    Fields {
+        input: Located::new(input),
+        failed: false,
+    }
+}
+
+/// Iterator over the fields of a message, created by [`fields`].
+///
+/// It returns an error for a field that cannot be read, then stops.
+#[derive(Clone, Debug)]
+pub struct Fields<'a> {
+    input: Located<&'a [u8]>,
+    failed: bool,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<Field<'a>, ProtobufError<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.input.fragment().is_empty() {
+            return None;
+        }
+        match field(self.input) {
+            Ok((rest, field)) => {
+                self.input = rest;
+                Some(Ok(field))
+            }
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// A field read from a message, or the tag ending a group.
+enum Item<'a> {
+    Field(Field<'a>),
+    End(u32),
+}
+
+struct Reader<'a> {
+    input: Located<&'a [u8]>,
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, start: usize, end: usize, message: ErrorMessage) -> ProtobufError<'a> {
+        spanning(self.input, start, end, message)
+    }
+
+    fn truncated(&self, start: usize) -> ProtobufError<'a> {
+        let message = with_error!(ErrorKind::EndOfInput, "truncated field");
+        self.error(start, self.bytes.len(), message)
+    }
+
+    fn varint(&mut self, start: usize) -> Result<u64, ProtobufError<'a>> {
+        let message = match varint(&self.bytes[self.at..]) {
+            Ok((value, size)) => {
+                self.at += size;
+                return Ok(value);
+            }
+            Err(VarintError::Truncated) => return Err(self.truncated(start)),
+            Err(VarintError::Overflow) => {
+                with_error!(ErrorKind::MalformedData, "varint overflows 64 bits")
+            }
+            Err(VarintError::TooLong) => {
+                with_error!(ErrorKind::MalformedData, "varint is longer than 10 bytes")
+            }
+        };
+        // Both failures are raised at the tenth byte.
+        let last = self.at + 9;
+        Err(self.error(last, last + 1, message))
+    }
+
+    fn take(&mut self, start: usize, length: u64) -> Result<&'a [u8], ProtobufError<'a>> {
+        let available = self.bytes.len() - self.at;
+        match usize::try_from(length) {
+            Ok(length) if length <= available => {
+                self.at += length;
+                Ok(&self.bytes[self.at - length..self.at])
+            }
+            _ => Err(self.truncated(start)),
+        }
+    }
+
+    fn item(&mut self, depth: usize) -> Result<Item<'a>, ProtobufError<'a>> {
+        let start = self.at;
+        let tag = self.varint(start)?;
+        let number = match tag >> 3 {
+            0 => {
+                let message = with_error!(ErrorKind::MalformedData, "field number 0 is reserved");
+                return Err(self.error(start, self.at, message));
+            }
+            number @ 1..=MAX_NUMBER => number as u32,
+            _ => {
+                let message = with_error!(ErrorKind::MalformedData, "field number is too large");
+                return Err(self.error(start, self.at, message));
+            }
+        };
+        let Some(wire_type) = WireType::from_bits(tag as u8 & 7) else {
+            let message = with_error!(ErrorKind::MalformedData, "invalid wire type");
+            return Err(self.error(start, self.at, message));
+        };
+        let value = match wire_type {
+            WireType::Varint => Value::Varint(self.varint(start)?),
+            WireType::Fixed64 => {
+                let bytes = self.take(start, 8)?;
+                Value::Fixed64(u64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            WireType::LengthDelimited => {
+                let length = self.varint(start)?;
+                Value::LengthDelimited(self.take(start, length)?)
+            }
+            WireType::StartGroup => Value::Group(self.group(start, number, depth)?),
+            WireType::EndGroup => return Ok(Item::End(number)),
+            WireType::Fixed32 => {
+                let bytes = self.take(start, 4)?;
+                Value::Fixed32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        };
+        Ok(Item::Field(Field { number, value }))
+    }
+
+    /// Reads the fields of a group up to the end tag matching `number`.
+    fn group(
+        &mut self,
+        start: usize,
+        number: u32,
+        depth: usize,
+    ) -> Result<&'a [u8], ProtobufError<'a>> {
+        if depth == MAX_DEPTH {
+            let message = with_error!(ErrorKind::MalformedData, "groups are nested too deeply");
+            return Err(self.error(start, self.at, message));
+        }
+        let contents = self.at;
+        loop {
+            if self.at == self.bytes.len() {
+                let message = with_error!(ErrorKind::EndOfInput, "unterminated group");
+                return Err(self.error(start, contents, message));
+            }
+            let end = self.at;
+            match self.item(depth + 1)? {
+                Item::Field(_) => {}
+                Item::End(found) if found == number => return Ok(&self.bytes[contents..end]),
+                Item::End(_) => {
+                    let message = with_error!(
+                        ErrorKind::MalformedData,
+                        "end of group does not match its start"
+                    );
+                    return Err(self.error(end, self.at, message));
+                }
+            }
+        }
+    }
+}
+
+/// A field of a message decoded by [`guess`].
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Guess<'a> {
+    pub number: u32,
+    pub value: Value<'a>,
+    /// The fields of a group, or of a length-delimited value that parses as
+    /// a message.
+    pub message: Option<Vec<Guess<'a>>>,
+}
+
+/// Decodes the fields of the encoded message `input`, guessing which
+/// length-delimited values are nested messages, or returns `None` if it is
+/// not a valid message.
+///
+/// A length-delimited value is taken to be a message if it is not empty,
+/// parses as one and is not printable UTF-8 text, since short strings often
+/// happen to parse as messages too. Messages nested deeper than
+/// [`MAX_DEPTH`] are left undecoded.
+///
+/// ```
+/// use recoil::formats::protobuf;
+///
+/// // Field 3 holds a message with field 1 set to 150, and field 2 a string.
+/// let input = b"\x1A\x03\x08\x96\x01\x12\x02hi";
+/// let fields = protobuf::guess(input).unwrap();
+/// let nested = fields[0].message.as_deref().unwrap();
+/// assert_eq!((nested[0].number, nested[0].value.as_sint()), (1, Some(75)));
+/// assert_eq!(fields[1].message, None);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn guess(input: &[u8]) -> Option<Vec<Guess<'_>>> {
+    guess_nested(input, 0)
+}
+
+#[cfg(feature = "alloc")]
+fn guess_nested(input: &[u8], depth: usize) -> Option<Vec<Guess<'_>>> {
+    fields(input)
+        .map(|field| {
+            let Field { number, value } = field.ok()?;
+            let message = match value {
+                _ if depth == MAX_DEPTH => None,
+                Value::Group(contents) => guess_nested(contents, depth + 1),
+                Value::LengthDelimited(bytes) if !bytes.is_empty() && !is_text(bytes) => {
+                    guess_nested(bytes, depth + 1)
+                }
+                _ => None,
+            };
+            Some(Guess {
+                number,
+                value,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Returns `true` if `bytes` is UTF-8 text without control characters other
+/// than whitespace.
+#[cfg(feature = "alloc")]
+fn is_text(bytes: &[u8]) -> bool {
+    str::from_utf8(bytes).is_ok_and(|text| {
+        text.chars()
+            .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(input: &[u8]) -> (String, ErrorKind, (usize, usize)) {
+        let error = field(Located::new(input)).unwrap_err();
+        (error.to_string(), error.kind(), error.offset().unwrap())
+    }
+
+    #[test]
+    fn reads_fields() {
+        let input = b"\x08\x96\x01\x11\x01\0\0\0\0\0\0\x80\x1A\x03abc\x25\x00\x00\x80\x3F\x28\x03";
+        let fields: Vec<_> = fields(input).collect::<Result<_, _>>().unwrap();
+        let values: Vec<_> = fields
+            .iter()
+            .map(|field| (field.number, field.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (1, Value::Varint(150)),
+                (2, Value::Fixed64(0x8000_0000_0000_0001)),
+                (3, Value::LengthDelimited(b"abc")),
+                (4, Value::Fixed32(1.0f32.to_bits())),
+                (5, Value::Varint(3)),
+            ]
+        );
+        assert_eq!(fields[4].value.as_sint(), Some(-2));
+        assert_eq!(Value::Varint(u64::MAX).as_sint(), Some(i64::MIN));
+
+        let (rest, field) = field(Located::new(&b"\x80\x80\x80\x80\x0F\x01\xFF"[..])).unwrap();
+        assert_eq!(field.number, 0x1E00_0000);
+        assert_eq!(field.wire_type(), WireType::Varint);
+        assert_eq!(rest.offset(), 6);
+        assert_eq!(rest.fragment(), b"\xFF");
+    }
+
+    #[test]
+    fn reads_groups() {
+        // Group 1 holds field 2 and group 3, which holds field 4.
+        let input = b"\x0B\x10\x01\x1B\x20\x02\x1C\x0C\x28\x03";
+        let (rest, outer) = field(Located::new(&input[..])).unwrap();
+        assert_eq!(outer.value, Value::Group(b"\x10\x01\x1B\x20\x02\x1C"));
+        assert_eq!(outer.wire_type(), WireType::StartGroup);
+        assert_eq!(rest.offset(), 8);
+
+        let Value::Group(contents) = outer.value else {
+            unreachable!()
+        };
+        let inner: Vec<_> = fields(contents).map(|field| field.unwrap().value).collect();
+        assert_eq!(inner, [Value::Varint(1), Value::Group(b"\x20\x02")]);
+
+        let mut deep = Vec::new();
+        deep.resize(MAX_DEPTH + 1, 0x0B);
+        assert_eq!(
+            failure(&deep),
+            (
+                "groups are nested too deeply".into(),
+                ErrorKind::MalformedData,
+                (MAX_DEPTH, MAX_DEPTH + 1)
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        assert_eq!(
+            failure(b"\x12\x05abc"),
+            ("truncated field".into(), ErrorKind::EndOfInput, (0, 5))
+        );
+        assert_eq!(failure(b"\x08\x80").2, (0, 2));
+        assert_eq!(
+            failure(b"\x08\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x02").0,
+            "varint overflows 64 bits"
+        );
+        assert_eq!(
+            failure(b"\x08\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x00").2,
+            (10, 11)
+        );
+        assert_eq!(failure(b"\x02\x00").0, "field number 0 is reserved");
+        assert_eq!(
+            failure(b"\x80\x80\x80\x80\x10").0,
+            "field number is too large"
+        );
+        assert_eq!(failure(b"\x0E").2, (0, 1));
+        assert_eq!(failure(b"\x0C").0, "unexpected end of group");
+        assert_eq!(
+            failure(b"\x0B\x08\x01"),
+            ("unterminated group".into(), ErrorKind::EndOfInput, (0, 1))
+        );
+        assert_eq!(
+            failure(b"\x0B\x08\x01\x14"),
+            (
+                "end of group does not match its start".into(),
+                ErrorKind::MalformedData,
+                (3, 4)
+            )
+        );
+
+        let mut iter = fields(b"\x08\x01\x0E\x08\x01");
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next().unwrap().unwrap_err().offset(), Some((2, 3)));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn guesses_nested_messages() {
+        // Field 1 holds a message whose field 2 holds a message, field 2
+        // holds text and field 3 holds bytes that are not a message.
+        let input =
+            b"\x0A\x06\x12\x04\x08\x01\x10\x02\x12\x05hello\x1A\x02\xFF\xFF\x23\x08\x07\x24";
+        let fields = guess(input).unwrap();
+        assert_eq!(fields.len(), 4);
+
+        let nested = fields[0].message.as_deref().unwrap();
+        let inner = nested[0].message.as_deref().unwrap();
+        assert_eq!(inner[1].value, Value::Varint(2));
+        assert_eq!(fields[1].value.as_bytes(), Some(&b"hello"[..]));
+        assert_eq!(fields[1].message, None);
+        assert_eq!(fields[2].message, None);
+        let group = fields[3].message.as_deref().unwrap();
+        assert_eq!((group[0].number, group[0].value), (1, Value::Varint(7)));
+
+        assert_eq!(guess(b"\x12\x05ab"), None);
+        assert_eq!(guess(b""), Some(Vec::new()));
+    }
+}
@@ -0,0 +1,774 @@
+//! This module contains parsers for semantic versions and version
+//! requirements.
+//!
+//! Versions follow [SemVer 2.0](https://semver.org), and are ordered by the
+//! precedence rules of the specification. Requirements can be written in the
+//! syntax of Cargo, where `1.2` means `^1.2` and comparators are separated by
+//! commas, or of npm, where `1.2` means `1.2.x`, comparators are separated by
+//! spaces, alternatives by `||` and `1.2.3 - 2.3` is a hyphen range.
+//!
+//! In both syntaxes a pre-release version only satisfies a requirement if
+//! one of its comparators names a pre-release of the same major, minor and
+//! patch version, so that `^1.2.3` does not select `1.3.0-beta`.
+//!
+//! ```
+//! use recoil::formats::semver;
+//!
+//! let version = semver::parse_version("1.4.0-rc.1+build.5").unwrap();
+//! assert_eq!((version.major, version.minor, version.pre), (1, 4, "rc.1"));
+//! assert!(version < semver::parse_version("1.4.0").unwrap());
+//!
+//! let requirement = semver::parse_cargo(">=1.2, <1.5").unwrap();
+//! assert!(!requirement.matches(&version));
+//! let requirement = semver::parse_npm("1.2.x || >=1.4.0-rc.0 <2").unwrap();
+//! assert!(requirement.matches(&version));
+//!
+//! let error = semver::parse_version("1.02.0").unwrap_err();
+//! assert_eq!(error.to_string(), "1:3: leading zeros are not allowed");
+//! ```
+
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+use super::{spanning, SyntaxError};
+use crate::error::{ErrorKind, ErrorMessage, Expected};
+use crate::located::Located;
+use crate::span::Span;
+use crate::with_error;
+
+/// A semantic version.
+///
+/// Versions are ordered by precedence, with build metadata, which has no
+/// precedence, compared last so that the ordering agrees with equality.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Version<'a> {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// Dot-separated pre-release identifiers, or an empty string.
+    pub pre: &'a str,
+    /// Dot-separated build metadata, or an empty string.
+    pub build: &'a str,
+}
+
+impl<'a> Version<'a> {
+    /// Creates a release version without build metadata.
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: "",
+            build: "",
+        }
+    }
+
+    pub fn is_pre_release(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    /// Returns the pre-release identifiers.
+    pub fn pre_release(&self) -> impl Iterator<Item = Identifier<'a>> {
+        self.pre
+            .split('.')
+            .filter(|identifier| !identifier.is_empty())
+            .map(Identifier::new)
+    }
+
+    /// Compares the precedence of two versions, which ignores build
+    /// metadata.
+    pub fn cmp_precedence(&self, other: &Version<'_>) -> Ordering {
+        let release = (self.major, self.minor, self.patch);
+        release
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                // A pre-release precedes the release.
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre_release().cmp(other.pre_release()),
+            })
+    }
+
+    fn same_release(&self, other: &Version<'_>) -> bool {
+        (self.major, self.minor, self.patch) == (other.major, other.minor, other.patch)
+    }
+}
+
+impl Ord for Version<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_precedence(other)
+            .then_with(|| self.build.cmp(other.build))
+    }
+}
+
+impl PartialOrd for Version<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre)?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+/// A pre-release identifier.
+///
+/// Numeric identifiers are compared numerically and precede alphanumeric
+/// identifiers, which are compared in ASCII order.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Identifier<'a> {
+    Numeric(u64),
+    Alphanumeric(&'a str),
+}
+
+impl<'a> Identifier<'a> {
+    fn new(text: &'a str) -> Self {
+        match text.parse() {
+            Ok(number) if text.bytes().all(|b| b.is_ascii_digit()) => Identifier::Numeric(number),
+            _ => Identifier::Alphanumeric(text),
+        }
+    }
+}
+
+/// The operator of a [`Comparator`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+/// A comparison against a version, which requirements are reduced to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Comparator<'a> {
+    pub op: Op,
+    pub version: Version<'a>,
+}
+
+impl Comparator<'_> {
+    pub fn matches(&self, version: &Version<'_>) -> bool {
+        let ordering = version.cmp_precedence(&self.version);
+        match self.op {
+            Op::Exact => ordering.is_eq(),
+            Op::Greater => ordering.is_gt(),
+            Op::GreaterEq => ordering.is_ge(),
+            Op::Less => ordering.is_lt(),
+            Op::LessEq => ordering.is_le(),
+        }
+    }
+}
+
+impl fmt::Display for Comparator<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            Op::Exact => "=",
+            Op::Greater => ">",
+            Op::GreaterEq => ">=",
+            Op::Less => "<",
+            Op::LessEq => "<=",
+        };
+        write!(f, "{op}{}", self.version)
+    }
+}
+
+/// A version requirement, reduced to alternatives of comparators that must
+/// all match.
+///
+/// Caret, tilde, wildcard and hyphen ranges are expanded into their bounds,
+/// so that `^1.2` becomes `>=1.2.0, <2.0.0`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionReq<'a> {
+    alternatives: Vec<Vec<Comparator<'a>>>,
+}
+
+impl<'a> VersionReq<'a> {
+    /// Returns the alternatives, each a set of comparators that must all
+    /// match. An empty set matches any release.
+    pub fn alternatives(&self) -> &[Vec<Comparator<'a>>] {
+        &self.alternatives
+    }
+
+    /// Returns `true` if `version` satisfies the requirement.
+    pub fn matches(&self, version: &Version<'_>) -> bool {
+        self.alternatives.iter().any(|comparators| {
+            comparators
+                .iter()
+                .all(|comparator| comparator.matches(version))
+                && (!version.is_pre_release()
+                    || comparators.iter().any(|comparator| {
+                        comparator.version.is_pre_release()
+                            && comparator.version.same_release(version)
+                    }))
+        })
+    }
+}
+
+/// Renders the expanded comparators, with alternatives separated by `||`.
+impl fmt::Display for VersionReq<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, comparators) in self.alternatives.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" || ")?;
+            }
+            if comparators.is_empty() {
+                f.write_str("*")?;
+            }
+            for (index, comparator) in comparators.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{comparator}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when parsing a version or a requirement fails.
+pub type SemverError<'a> = SyntaxError<'a>;
+
+/// Parses a version, such as `1.0.0-alpha.1+001`.
+pub fn parse_version(input: &str) -> Result<Version<'_>, SemverError<'_>> {
+    let mut reader = Reader::new(input);
+    let version = reader.version()?;
+    reader.end()?;
+    Ok(version)
+}
+
+/// Parses a requirement in Cargo syntax, such as `>=1.2.3, <1.8`.
+///
+/// A version without an operator is a caret requirement.
+pub fn parse_cargo(input: &str) -> Result<VersionReq<'_>, SemverError<'_>> {
+    let mut reader = Reader::new(input);
+    let mut comparators = Vec::new();
+    loop {
+        reader.skip_space();
+        let op = reader.op();
+        reader.skip_space();
+        let partial = reader.partial(false)?;
+        expand(op.unwrap_or(Syntax::Caret), partial, &mut comparators);
+        reader.skip_space();
+        if reader.peek().is_none() {
+            break;
+        }
+        if !reader.eat(b',') {
+            return Err(reader.expected(&[Expected::Char(','), Expected::Rule("end of input")]));
+        }
+    }
+    Ok(VersionReq {
+        alternatives: Vec::from([comparators]),
+    })
+}
+
+/// Parses a range in npm syntax, such as `^1.2.3 || 2.x` or
+/// `1.2.3 - 2.3`.
+///
+/// A version without an operator is an exact requirement, or a wildcard
+/// requirement if it is partial. An empty range matches any release.
+pub fn parse_npm(input: &str) -> Result<VersionReq<'_>, SemverError<'_>> {
+    let mut reader = Reader::new(input);
+    let mut alternatives = Vec::new();
+    loop {
+        let mut comparators = Vec::new();
+        reader.skip_space();
+        while reader.peek().is_some() && !reader.starts_with("||") {
+            let op = reader.op();
+            reader.skip_space();
+            let partial = reader.partial(true)?;
+            let after = reader.at;
+            reader.skip_space();
+            if op.is_none() && reader.starts_with("- ") {
+                reader.at += 1;
+                reader.skip_space();
+                let upper = reader.partial(true)?;
+                expand(Syntax::GreaterEq, partial, &mut comparators);
+                expand(Syntax::LessEq, upper, &mut comparators);
+            } else {
+                expand(op.unwrap_or(Syntax::Exact), partial, &mut comparators);
+                if reader.at == after && reader.peek().is_some() && !reader.starts_with("||") {
+                    return Err(
+                        reader.expected(&[Expected::Class("whitespace"), Expected::Tag("||")])
+                    );
+                }
+            }
+            reader.skip_space();
+        }
+        alternatives.push(comparators);
+        if !reader.starts_with("||") {
+            break;
+        }
+        reader.at += 2;
+    }
+    Ok(VersionReq { alternatives })
+}
+
+/// The operators of the requirement syntaxes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Syntax {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+}
+
+/// A version whose missing or wildcard components are `None`.
+#[derive(Clone, Copy, Debug)]
+struct Partial<'a> {
+    major: Option<u64>,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    pre: &'a str,
+}
+
+impl<'a> Partial<'a> {
+    fn is_full(&self) -> bool {
+        self.patch.is_some()
+    }
+
+    /// Returns the version with missing components as zero.
+    fn lower(&self) -> Version<'a> {
+        Version {
+            pre: self.pre,
+            ..Version::new(
+                self.major.unwrap_or(0),
+                self.minor.unwrap_or(0),
+                self.patch.unwrap_or(0),
+            )
+        }
+    }
+
+    /// Returns the first version after those matching the given components,
+    /// or `None` if there is no such version.
+    fn upper(&self) -> Option<Version<'static>> {
+        match (self.major, self.minor, self.patch) {
+            (Some(major), None, _) => Some(Version::new(major.checked_add(1)?, 0, 0)),
+            (Some(major), Some(minor), None) => Some(Version::new(major, minor.checked_add(1)?, 0)),
+            (Some(major), Some(minor), Some(patch)) => {
+                Some(Version::new(major, minor, patch.checked_add(1)?))
+            }
+            (None, ..) => None,
+        }
+    }
+
+    /// Returns the partial version truncated to its first `count` components.
+    fn truncate(mut self, count: usize) -> Self {
+        if count < 3 {
+            self.patch = None;
+            self.pre = "";
+        }
+        if count < 2 {
+            self.minor = None;
+        }
+        self
+    }
+}
+
+/// Appends the comparators that `op` applied to `partial` stands for.
+fn expand<'a>(op: Syntax, partial: Partial<'a>, comparators: &mut Vec<Comparator<'a>>) {
+    let bound = |op, version| Some(Comparator { op, version });
+    let below = |partial: Partial<'a>| partial.upper().and_then(|version| bound(Op::Less, version));
+    let lower = bound(Op::GreaterEq, partial.lower());
+    // No version is less than 0.0.0.
+    let nothing = bound(Op::Less, Version::new(0, 0, 0));
+    let expanded = match op {
+        Syntax::Greater | Syntax::Less if partial.major.is_none() => [nothing, None],
+        _ if partial.major.is_none() => [None, None],
+        Syntax::Exact if partial.is_full() => [bound(Op::Exact, partial.lower()), None],
+        Syntax::Greater if partial.is_full() => [bound(Op::Greater, partial.lower()), None],
+        Syntax::LessEq if partial.is_full() => [bound(Op::LessEq, partial.lower()), None],
+        Syntax::Exact => [lower, below(partial)],
+        Syntax::Greater => match partial.upper() {
+            Some(upper) => [bound(Op::GreaterEq, upper), None],
+            None => [nothing, None],
+        },
+        Syntax::GreaterEq => [lower, None],
+        Syntax::Less => [bound(Op::Less, partial.lower()), None],
+        Syntax::LessEq => [below(partial), None],
+        Syntax::Tilde => {
+            let count = if partial.minor.is_some() { 2 } else { 1 };
+            [lower, below(partial.truncate(count))]
+        }
+        Syntax::Caret => {
+            // The first non-zero component, or the last given one, may not
+            // change.
+            let count = match (partial.major, partial.minor, partial.patch) {
+                (Some(0), Some(0), Some(_)) => 3,
+                (Some(0), Some(_), _) => 2,
+                _ => 1,
+            };
+            [lower, below(partial.truncate(count))]
+        }
+    };
+    comparators.extend(expanded.into_iter().flatten());
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(source: &'a str) -> Self {
+        Self { source, at: 0 }
+    }
+
+    fn bytes(&self) -> &'a [u8] {
+        &self.source.as_bytes()[self.at..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes().first().copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.bytes().starts_with(prefix.as_bytes())
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        self.at += usize::from(found);
+        found
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.at += 1;
+        }
+    }
+
+    fn located(&self, at: usize) -> Located<&'a str> {
+        Located::new(self.source).over(at..self.source.len())
+    }
+
+    fn error(&self, start: usize, end: usize, message: ErrorMessage) -> SemverError<'a> {
+        SyntaxError::new(spanning(Located::new(self.source), start, end, message))
+    }
+
+    fn expected(&self, items: &[Expected]) -> SemverError<'a> {
+        SyntaxError::new(super::expected(self.located(self.at), items))
+    }
+
+    fn end(&self) -> Result<(), SemverError<'a>> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.expected(&[Expected::Rule("end of input")])),
+        }
+    }
+
+    fn op(&mut self) -> Option<Syntax> {
+        let (op, length) = match self.bytes() {
+            [b'>', b'=', ..] => (Syntax::GreaterEq, 2),
+            [b'<', b'=', ..] => (Syntax::LessEq, 2),
+            [b'>', ..] => (Syntax::Greater, 1),
+            [b'<', ..] => (Syntax::Less, 1),
+            [b'=', ..] => (Syntax::Exact, 1),
+            [b'~', ..] => (Syntax::Tilde, 1),
+            [b'^', ..] => (Syntax::Caret, 1),
+            _ => return None,
+        };
+        self.at += length;
+        Some(op)
+    }
+
+    /// Parses a version number, which has no leading zeros.
+    fn number(&mut self) -> Result<u64, SemverError<'a>> {
+        let start = self.at;
+        let length = self
+            .bytes()
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if length == 0 {
+            return Err(self.expected(&[Expected::Class("digit")]));
+        }
+        self.at += length;
+        let text = &self.source[start..self.at];
+        if length > 1 && text.starts_with('0') {
+            let message = with_error!(ErrorKind::MalformedData, "leading zeros are not allowed");
+            return Err(self.error(start, self.at, message));
+        }
+        text.parse().map_err(|_| {
+            let message = with_error!(ErrorKind::MalformedData, "version number too large");
+            self.error(start, self.at, message)
+        })
+    }
+
+    /// Parses dot-separated identifiers, checking numeric identifiers for
+    /// leading zeros if `numeric` is set.
+    fn identifiers(&mut self, numeric: bool) -> Result<&'a str, SemverError<'a>> {
+        let start = self.at;
+        loop {
+            let begin = self.at;
+            let length = self
+                .bytes()
+                .iter()
+                .take_while(|&&b| b.is_ascii_alphanumeric() || b == b'-')
+                .count();
+            if length == 0 {
+                return Err(self.expected(&[Expected::Rule("identifier")]));
+            }
+            self.at += length;
+            let identifier = &self.source[begin..self.at];
+            let is_number = identifier.bytes().all(|b| b.is_ascii_digit());
+            if numeric && is_number && length > 1 && identifier.starts_with('0') {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "leading zeros are not allowed in numeric identifiers"
+                );
+                return Err(self.error(begin, self.at, message));
+            }
+            if !self.eat(b'.') {
+                return Ok(&self.source[start..self.at]);
+            }
+        }
+    }
+
+    fn pre_and_build(&mut self) -> Result<(&'a str, &'a str), SemverError<'a>> {
+        let pre = if self.eat(b'-') {
+            self.identifiers(true)?
+        } else {
+            ""
+        };
+        let build = if self.eat(b'+') {
+            self.identifiers(false)?
+        } else {
+            ""
+        };
+        Ok((pre, build))
+    }
+
+    fn version(&mut self) -> Result<Version<'a>, SemverError<'a>> {
+        let major = self.number()?;
+        self.expect(b'.')?;
+        let minor = self.number()?;
+        self.expect(b'.')?;
+        let patch = self.number()?;
+        let (pre, build) = self.pre_and_build()?;
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            pre,
+            build,
+        })
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SemverError<'a>> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.expected(&[Expected::Char(char::from(byte))]))
+        }
+    }
+
+    /// Parses a version whose minor and patch components may be missing or
+    /// wildcards, allowing a `v` prefix in the npm syntax.
+    fn partial(&mut self, npm: bool) -> Result<Partial<'a>, SemverError<'a>> {
+        if npm && matches!(self.peek(), Some(b'v' | b'V')) {
+            self.at += 1;
+        }
+        let start = self.at;
+        let mut components = [None; 3];
+        let mut count = 0;
+        while count < 3 && (count == 0 || self.eat(b'.')) {
+            let begin = self.at;
+            components[count] = match self.peek() {
+                Some(b'*' | b'x' | b'X') => {
+                    self.at += 1;
+                    None
+                }
+                _ => Some(self.number()?),
+            };
+            if count > 0 && components[count - 1].is_none() && components[count].is_some() {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "a version number cannot follow a wildcard"
+                );
+                return Err(self.error(begin, self.at, message));
+            }
+            count += 1;
+        }
+        let [major, minor, patch] = components;
+        let (pre, _) = if count == 3 && patch.is_some() {
+            self.pre_and_build()?
+        } else if matches!(self.peek(), Some(b'-' | b'+')) && !self.starts_with("- ") {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "pre-release and build metadata require a full version"
+            );
+            return Err(self.error(start, self.at, message));
+        } else {
+            ("", "")
+        };
+        Ok(Partial {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version<'_> {
+        parse_version(text).unwrap()
+    }
+
+    fn matches(requirement: &VersionReq<'_>, versions: &[&str]) -> Vec<bool> {
+        versions
+            .iter()
+            .map(|text| requirement.matches(&version(text)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_versions() {
+        let parsed = version("1.0.0-alpha.1+001.sha-5114f85");
+        assert_eq!((parsed.major, parsed.minor, parsed.patch), (1, 0, 0));
+        assert_eq!(parsed.pre, "alpha.1");
+        assert_eq!(parsed.build, "001.sha-5114f85");
+        let identifiers: Vec<_> = parsed.pre_release().collect();
+        assert_eq!(
+            identifiers,
+            [Identifier::Alphanumeric("alpha"), Identifier::Numeric(1)]
+        );
+        assert_eq!(parsed.to_string(), "1.0.0-alpha.1+001.sha-5114f85");
+        assert_eq!(version("0.0.0").pre_release().count(), 0);
+
+        let error = parse_version("1.2").unwrap_err();
+        assert_eq!(error.to_string(), "1:4: expected `.`, found end of input");
+        let error = parse_version("1.2.3-beta.01").unwrap_err();
+        assert_eq!(error.error().offset(), Some((11, 13)));
+        let error = parse_version("1.2.3-").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:7: expected identifier, found end of input"
+        );
+        let error = parse_version("1.2.3 ").unwrap_err();
+        assert_eq!(error.to_string(), "1:6: expected end of input, found ` `");
+        let error = parse_version("1.2.99999999999999999999").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MalformedData);
+    }
+
+    #[test]
+    fn orders_by_precedence() {
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.1.0",
+            "2.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{pair:?}");
+        }
+        let (a, b) = (version("1.0.0+a"), version("1.0.0+b"));
+        assert_eq!(a.cmp_precedence(&b), Ordering::Equal);
+        assert!(a < b);
+    }
+
+    #[test]
+    fn expands_cargo_requirements() {
+        let cases = [
+            ("1.2.3", ">=1.2.3, <2.0.0"),
+            ("^0.2.3", ">=0.2.3, <0.3.0"),
+            ("^0.0.3", ">=0.0.3, <0.0.4"),
+            ("^0.0", ">=0.0.0, <0.1.0"),
+            ("~1.2.3", ">=1.2.3, <1.3.0"),
+            ("~1", ">=1.0.0, <2.0.0"),
+            ("1.*", ">=1.0.0, <2.0.0"),
+            ("*", "*"),
+            ("=1.2", ">=1.2.0, <1.3.0"),
+            (">1.2", ">=1.3.0"),
+            ("<=1.2", "<1.3.0"),
+            (">= 1.2.0, < 1.5.0-0", ">=1.2.0, <1.5.0-0"),
+        ];
+        for (text, expanded) in cases {
+            assert_eq!(parse_cargo(text).unwrap().to_string(), expanded, "{text}");
+        }
+
+        let requirement = parse_cargo("^1.2.3").unwrap();
+        let versions = ["1.2.3", "1.9.0", "2.0.0", "1.2.2", "1.3.0-beta"];
+        assert_eq!(
+            matches(&requirement, &versions),
+            [true, true, false, false, false]
+        );
+        let requirement = parse_cargo(">=1.3.0-alpha.2").unwrap();
+        let versions = ["1.3.0-alpha.3", "1.3.0-alpha.1", "1.4.0-alpha.3", "1.4.0"];
+        assert_eq!(matches(&requirement, &versions), [true, false, false, true]);
+
+        let error = parse_cargo("1.2 1.3").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:5: expected one of `,`, end of input, found `1`"
+        );
+        let error = parse_cargo("1.*.3").unwrap_err();
+        assert_eq!(error.error().offset(), Some((4, 5)));
+        assert!(parse_cargo("").is_err());
+        assert!(parse_cargo("v1.2.3").is_err());
+    }
+
+    #[test]
+    fn expands_npm_ranges() {
+        let cases = [
+            ("1.2.3", "=1.2.3"),
+            ("v1.2.x", ">=1.2.0, <1.3.0"),
+            ("", "*"),
+            ("1.2.3 - 2.3.4", ">=1.2.3, <=2.3.4"),
+            ("1.2 - 2.3", ">=1.2.0, <2.4.0"),
+            (">=1.0.0 <1.4 || ^2.1", ">=1.0.0, <1.4.0 || >=2.1.0, <3.0.0"),
+            ("~0.2 || *", ">=0.2.0, <0.3.0 || *"),
+            (">*", "<0.0.0"),
+        ];
+        for (text, expanded) in cases {
+            assert_eq!(parse_npm(text).unwrap().to_string(), expanded, "{text}");
+        }
+
+        let requirement = parse_npm("1.x || >=2.5.0-rc.1 <3").unwrap();
+        let versions = [
+            "1.9.9",
+            "2.0.0",
+            "2.5.0-rc.2",
+            "2.6.0",
+            "2.6.0-rc.1",
+            "3.0.0",
+        ];
+        assert_eq!(
+            matches(&requirement, &versions),
+            [true, false, true, true, false, false]
+        );
+
+        let error = parse_npm("1.2-beta").unwrap_err();
+        assert_eq!(error.error().offset(), Some((0, 3)));
+        let error = parse_npm(">=1.2.3<2").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:8: expected one of whitespace, `||`, found `<`"
+        );
+    }
+}
@@ -0,0 +1,1378 @@
+//! This module contains a parser for TOML 1.0 documents.
+//!
+//! The whole of TOML 1.0 is supported: bare, quoted and dotted keys, all four
+//! kinds of strings, integers in every radix, floats, datetimes, arrays,
+//! inline tables, table headers and arrays of tables.
+//!
+//! Every key records the span of the source it was parsed from, so that
+//! tools can point back at a setting. A table keeps the span of the key that
+//! defined it, and redefinitions are errors that point at both definitions.
+//!
+//! ```
+//! use recoil::formats::toml;
+//!
+//! let source = "[package]\nname = \"recoil\"\nauthors = [\"a\", \"b\"]\n";
+//! let document = toml::parse(source).unwrap();
+//! let package = document.get("package").unwrap();
+//! assert_eq!(package.get("name").and_then(|name| name.as_str()), Some("recoil"));
+//!
+//! let error = toml::parse("a = 1\nb = 2\na = 3\n").unwrap_err();
+//! assert_eq!(error.to_string(), "3:1: duplicate key `a` (first defined at 1:1)");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{days_in_month, SyntaxError};
+use crate::error::{Error, ErrorKind, ErrorMessage, Expected};
+use crate::located::Located;
+use crate::span::{Span, StrSpan};
+use crate::with_error;
+
+/// Maximum nesting depth of arrays and inline tables.
+const MAX_DEPTH: usize = 128;
+
+/// A key, along with the span of the source it was parsed from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TomlKey<'a> {
+    name: Cow<'a, str>,
+    span: StrSpan<'a>,
+}
+
+impl<'a> TomlKey<'a> {
+    /// Returns the name of the key, with quotes and escapes removed.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the span of the key in the source, including any quotes.
+    pub fn span(&self) -> StrSpan<'a> {
+        self.span
+    }
+}
+
+/// A TOML value borrowing from the input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TomlValue<'a> {
+    String(Cow<'a, str>),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Datetime(TomlDatetime),
+    Array(Vec<TomlValue<'a>>),
+    /// An array of tables, defined by `[[name]]` headers.
+    ArrayOfTables(Vec<TomlTable<'a>>),
+    Table(TomlTable<'a>),
+}
+
+impl<'a> TomlValue<'a> {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            TomlValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TomlValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TomlValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_datetime(&self) -> Option<&TomlDatetime> {
+        match self {
+            TomlValue::Datetime(datetime) => Some(datetime),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[TomlValue<'a>]> {
+        match self {
+            TomlValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_tables(&self) -> Option<&[TomlTable<'a>]> {
+        match self {
+            TomlValue::ArrayOfTables(tables) => Some(tables),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&TomlTable<'a>> {
+        match self {
+            TomlValue::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of `key`, if this is a table.
+    pub fn get(&self, key: &str) -> Option<&TomlValue<'a>> {
+        self.as_table()?.get(key)
+    }
+}
+
+/// How a table came into existence, which decides how it may be extended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TableKind {
+    /// Created as a parent of a table header, and not yet defined itself.
+    Implicit,
+    /// Defined by a table header.
+    Header,
+    /// Created by a dotted key.
+    Dotted,
+    /// Defined by an inline table, which cannot be extended.
+    Inline,
+}
+
+/// A table, with its entries in the order they were defined.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TomlTable<'a> {
+    entries: Vec<(TomlKey<'a>, TomlValue<'a>)>,
+    kind: TableKind,
+}
+
+impl<'a> TomlTable<'a> {
+    fn new(kind: TableKind) -> Self {
+        Self {
+            entries: Vec::new(),
+            kind,
+        }
+    }
+
+    pub fn entries(&self) -> &[(TomlKey<'a>, TomlValue<'a>)] {
+        &self.entries
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &str) -> Option<&TomlValue<'a>> {
+        self.position(key).map(|index| &self.entries[index].1)
+    }
+
+    /// Returns the key that defined `key`, which spans its first definition.
+    pub fn key(&self, key: &str) -> Option<&TomlKey<'a>> {
+        self.position(key).map(|index| &self.entries[index].0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|(name, _)| name.name() == key)
+    }
+
+    fn value_mut(&mut self, key: &str) -> Option<&mut TomlValue<'a>> {
+        let index = self.position(key)?;
+        Some(&mut self.entries[index].1)
+    }
+
+    /// Returns the index of the entry for `key`, creating an empty table of
+    /// `kind` if there is none.
+    fn entry_or_table(&mut self, key: &TomlKey<'a>, kind: TableKind) -> usize {
+        self.position(key.name()).unwrap_or_else(|| {
+            self.entries
+                .push((key.clone(), TomlValue::Table(TomlTable::new(kind))));
+            self.entries.len() - 1
+        })
+    }
+}
+
+/// A calendar date.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TomlDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// A time of day.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TomlTime {
+    pub hour: u8,
+    pub minute: u8,
+    /// Seconds, which may be 60 during a leap second.
+    pub second: u8,
+    pub nanosecond: u32,
+}
+
+/// Offset of a datetime from UTC.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TomlOffset {
+    /// UTC, written `Z`.
+    Z,
+    Custom {
+        minutes: i16,
+    },
+}
+
+/// A datetime, which is one of an offset datetime, a local datetime, a local
+/// date or a local time.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TomlDatetime {
+    pub date: Option<TomlDate>,
+    pub time: Option<TomlTime>,
+    /// Offset of an offset datetime, which has both a date and a time.
+    pub offset: Option<TomlOffset>,
+}
+
+impl fmt::Display for TomlDatetime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(date) = self.date {
+            write!(f, "{:04}-{:02}-{:02}", date.year, date.month, date.day)?;
+            if self.time.is_some() {
+                f.write_str("T")?;
+            }
+        }
+        if let Some(time) = self.time {
+            write!(f, "{:02}:{:02}:{:02}", time.hour, time.minute, time.second)?;
+            if time.nanosecond > 0 {
+                let mut fraction = time.nanosecond;
+                let mut width = 9;
+                while fraction % 10 == 0 {
+                    fraction /= 10;
+                    width -= 1;
+                }
+                write!(f, ".{fraction:0width$}")?;
+            }
+        }
+        match self.offset {
+            Some(TomlOffset::Z) => f.write_str("Z"),
+            Some(TomlOffset::Custom { minutes }) => {
+                let sign = if minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.unsigned_abs();
+                write!(f, "{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error returned when parsing TOML fails.
+pub type TomlError<'a> = SyntaxError<'a>;
+
+/// Parses a TOML document into its root table.
+pub fn parse(input: &str) -> Result<TomlTable<'_>, TomlError<'_>> {
+    Reader {
+        source: input,
+        at: 0,
+        depth: 0,
+    }
+    .document()
+}
+
+type TomlResult<'a, T> = Result<T, TomlError<'a>>;
+
+/// Returns `true` for the control characters that TOML does not allow in
+/// comments and strings.
+fn is_control(byte: u8) -> bool {
+    (byte < 0x20 && byte != b'\t') || byte == 0x7F
+}
+
+fn is_bare_key(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-')
+}
+
+/// Returns `true` if `text` is a non-empty run of digits in `radix`, with
+/// single underscores allowed between digits.
+fn is_digits(text: &str, radix: u32) -> bool {
+    let bytes = text.as_bytes();
+    let digit = |byte: Option<&u8>| byte.is_some_and(|&b| char::from(b).is_digit(radix));
+    digit(bytes.first())
+        && digit(bytes.last())
+        && !text.contains("__")
+        && bytes
+            .iter()
+            .all(|&b| b == b'_' || char::from(b).is_digit(radix))
+}
+
+fn duplicate_key(key: &TomlKey<'_>) -> ErrorMessage {
+    with_error!(ErrorKind::MalformedData, "duplicate key `{}`", key.name())
+}
+
+fn duplicate_table(key: &TomlKey<'_>) -> ErrorMessage {
+    with_error!(ErrorKind::MalformedData, "duplicate table `{}`", key.name())
+}
+
+fn closed_table(key: &TomlKey<'_>) -> ErrorMessage {
+    with_error!(
+        ErrorKind::MalformedData,
+        "cannot extend table `{}` outside its definition",
+        key.name()
+    )
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    at: usize,
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self) -> &'a [u8] {
+        &self.source.as_bytes()[self.at..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes().first().copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.bytes().starts_with(prefix.as_bytes())
+    }
+
+    fn span(&self, start: usize, end: usize) -> StrSpan<'a> {
+        StrSpan::with_offsets(&self.source[start..end], start, end)
+    }
+
+    fn located(&self, at: usize) -> Located<&'a str> {
+        Located::new(self.source).over(at..self.source.len())
+    }
+
+    fn error(&self, at: usize, message: ErrorMessage) -> TomlError<'a> {
+        SyntaxError::new(Error::from_message(self.located(at), message))
+    }
+
+    fn expected(&self, items: &[Expected]) -> TomlError<'a> {
+        SyntaxError::new(super::expected(self.located(self.at), items))
+    }
+
+    /// Reports `key` as conflicting with the earlier definition `first`.
+    fn conflict(
+        &self,
+        message: ErrorMessage,
+        key: &TomlKey<'a>,
+        first: &TomlKey<'a>,
+    ) -> TomlError<'a> {
+        self.error(key.span.start(), message)
+            .with_previous(self.located(first.span.start()))
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.starts_with(prefix);
+        if found {
+            self.at += prefix.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> TomlResult<'a, ()> {
+        let mut buffer = [0; 4];
+        if self.eat(c.encode_utf8(&mut buffer)) {
+            Ok(())
+        } else {
+            Err(self.expected(&[Expected::Char(c)]))
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.at += 1;
+        }
+    }
+
+    /// Skips a comment, if there is one.
+    fn comment(&mut self) -> TomlResult<'a, ()> {
+        if self.peek() != Some(b'#') {
+            return Ok(());
+        }
+        let length = self
+            .bytes()
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(self.bytes().len());
+        let comment = &self.bytes()[..length];
+        let comment = comment.strip_suffix(b"\r").unwrap_or(comment);
+        if let Some(index) = comment.iter().position(|&b| is_control(b)) {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "control character U+{:04X} is not allowed in comments",
+                comment[index]
+            );
+            return Err(self.error(self.at + index, message));
+        }
+        self.at += comment.len();
+        Ok(())
+    }
+
+    fn newline(&mut self) -> bool {
+        self.eat("\n") || self.eat("\r\n")
+    }
+
+    /// Skips whitespace and an optional comment, then the end of the line.
+    fn line_end(&mut self) -> TomlResult<'a, ()> {
+        self.whitespace();
+        self.comment()?;
+        if self.newline() || self.peek().is_none() {
+            Ok(())
+        } else {
+            Err(self.expected(&[Expected::Rule("end of line")]))
+        }
+    }
+
+    /// Skips whitespace, comments and newlines, as allowed within arrays.
+    fn blank(&mut self) -> TomlResult<'a, ()> {
+        loop {
+            self.whitespace();
+            self.comment()?;
+            if !self.newline() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs `parse` one nesting level deeper, enforcing the depth limit.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> TomlResult<'a, T>,
+    ) -> TomlResult<'a, T> {
+        if self.depth >= MAX_DEPTH {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "exceeded the maximum nesting depth of {}",
+                MAX_DEPTH
+            );
+            return Err(self.error(self.at, message));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn document(mut self) -> TomlResult<'a, TomlTable<'a>> {
+        let mut root = TomlTable::new(TableKind::Header);
+        // Keys of the table opened by the last header.
+        let mut path = Vec::new();
+        loop {
+            self.whitespace();
+            match self.peek() {
+                None => return Ok(root),
+                Some(b'#' | b'\r' | b'\n') => {}
+                Some(b'[') => {
+                    let array = self.eat("[[");
+                    if !array {
+                        self.at += 1;
+                    }
+                    self.whitespace();
+                    let keys = self.keys()?;
+                    if array && !self.eat("]]") {
+                        return Err(self.expected(&[Expected::Tag("]]")]));
+                    }
+                    if !array {
+                        self.expect(']')?;
+                    }
+                    self.open(&mut root, &keys, array)?;
+                    path = keys;
+                }
+                Some(_) => {
+                    let keys = self.keys()?;
+                    self.expect('=')?;
+                    self.whitespace();
+                    let value = self.value()?;
+                    let table = current(&mut root, &path);
+                    self.insert(table, keys, value)?;
+                }
+            }
+            self.line_end()?;
+        }
+    }
+
+    /// Opens the table named by a header, creating parent tables as needed.
+    fn open(
+        &self,
+        root: &mut TomlTable<'a>,
+        keys: &[TomlKey<'a>],
+        array: bool,
+    ) -> TomlResult<'a, ()> {
+        let (last, parents) = keys.split_last().expect("at least one key");
+        let mut table = root;
+        for key in parents {
+            let index = table.entry_or_table(key, TableKind::Implicit);
+            let (first, value) = &mut table.entries[index];
+            table = match value {
+                TomlValue::Table(inner) if inner.kind != TableKind::Inline => inner,
+                TomlValue::ArrayOfTables(tables) => {
+                    tables.last_mut().expect("arrays of tables are never empty")
+                }
+                TomlValue::Table(_) => return Err(self.conflict(closed_table(key), key, first)),
+                _ => return Err(self.conflict(duplicate_key(key), key, first)),
+            };
+        }
+
+        let Some(index) = table.position(last.name()) else {
+            let value = if array {
+                TomlValue::ArrayOfTables(Vec::from([TomlTable::new(TableKind::Header)]))
+            } else {
+                TomlValue::Table(TomlTable::new(TableKind::Header))
+            };
+            table.entries.push((last.clone(), value));
+            return Ok(());
+        };
+        let (first, value) = &mut table.entries[index];
+        match value {
+            TomlValue::Table(inner) if !array && inner.kind == TableKind::Implicit => {
+                // The table is defined here, rather than where it was implied.
+                inner.kind = TableKind::Header;
+                *first = last.clone();
+            }
+            TomlValue::ArrayOfTables(tables) if array => {
+                tables.push(TomlTable::new(TableKind::Header))
+            }
+            TomlValue::Table(_) | TomlValue::ArrayOfTables(_) => {
+                return Err(self.conflict(duplicate_table(last), last, first));
+            }
+            _ => return Err(self.conflict(duplicate_key(last), last, first)),
+        }
+        Ok(())
+    }
+
+    /// Inserts `value` into `table` under the dotted `keys`, creating tables
+    /// for all but the last key.
+    fn insert(
+        &self,
+        mut table: &mut TomlTable<'a>,
+        mut keys: Vec<TomlKey<'a>>,
+        value: TomlValue<'a>,
+    ) -> TomlResult<'a, ()> {
+        let last = keys.pop().expect("at least one key");
+        for key in &keys {
+            let index = table.entry_or_table(key, TableKind::Dotted);
+            let (first, existing) = &mut table.entries[index];
+            table = match existing {
+                TomlValue::Table(inner) if inner.kind == TableKind::Dotted => inner,
+                TomlValue::Table(_) => return Err(self.conflict(closed_table(key), key, first)),
+                _ => return Err(self.conflict(duplicate_key(key), key, first)),
+            };
+        }
+        if let Some(first) = table.key(last.name()) {
+            return Err(self.conflict(duplicate_key(&last), &last, first));
+        }
+        table.entries.push((last, value));
+        Ok(())
+    }
+
+    /// Parses a dotted key, along with the whitespace after it.
+    fn keys(&mut self) -> TomlResult<'a, Vec<TomlKey<'a>>> {
+        let mut keys = Vec::new();
+        loop {
+            keys.push(self.key()?);
+            self.whitespace();
+            if !self.eat(".") {
+                return Ok(keys);
+            }
+            self.whitespace();
+        }
+    }
+
+    fn key(&mut self) -> TomlResult<'a, TomlKey<'a>> {
+        let start = self.at;
+        let name = match self.peek() {
+            Some(b'"') => self.basic_string(false)?,
+            Some(b'\'') => self.literal_string(false)?,
+            Some(byte) if is_bare_key(byte) => {
+                let length = self.bytes().iter().take_while(|&&b| is_bare_key(b)).count();
+                self.at += length;
+                Cow::Borrowed(&self.source[start..self.at])
+            }
+            _ => return Err(self.expected(&[Expected::Rule("key")])),
+        };
+        Ok(TomlKey {
+            name,
+            span: self.span(start, self.at),
+        })
+    }
+
+    fn value(&mut self) -> TomlResult<'a, TomlValue<'a>> {
+        match self.peek() {
+            Some(b'"') => {
+                let multiline = self.starts_with("\"\"\"");
+                self.basic_string(multiline).map(TomlValue::String)
+            }
+            Some(b'\'') => {
+                let multiline = self.starts_with("'''");
+                self.literal_string(multiline).map(TomlValue::String)
+            }
+            Some(b'[') => self.nested(Self::array),
+            Some(b'{') => self.nested(Self::inline_table),
+            Some(b't' | b'f') => self.boolean(),
+            Some(b'0'..=b'9') if self.is_datetime() => self.datetime().map(TomlValue::Datetime),
+            Some(b'0'..=b'9' | b'+' | b'-' | b'i' | b'n') => self.number(),
+            _ => Err(self.expected(&[Expected::Rule("value")])),
+        }
+    }
+
+    fn boolean(&mut self) -> TomlResult<'a, TomlValue<'a>> {
+        if self.eat("true") {
+            Ok(TomlValue::Boolean(true))
+        } else if self.eat("false") {
+            Ok(TomlValue::Boolean(false))
+        } else {
+            Err(self.expected(&[Expected::Rule("value")]))
+        }
+    }
+
+    fn array(&mut self) -> TomlResult<'a, TomlValue<'a>> {
+        self.at += 1;
+        let mut items = Vec::new();
+        loop {
+            self.blank()?;
+            if self.eat("]") {
+                return Ok(TomlValue::Array(items));
+            }
+            items.push(self.value()?);
+            self.blank()?;
+            if self.eat("]") {
+                return Ok(TomlValue::Array(items));
+            }
+            if !self.eat(",") {
+                return Err(self.expected(&[Expected::Char(','), Expected::Char(']')]));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> TomlResult<'a, TomlValue<'a>> {
+        self.at += 1;
+        let mut table = TomlTable::new(TableKind::Dotted);
+        self.whitespace();
+        if !self.eat("}") {
+            loop {
+                let keys = self.keys()?;
+                self.expect('=')?;
+                self.whitespace();
+                let value = self.value()?;
+                self.insert(&mut table, keys, value)?;
+                self.whitespace();
+                if self.eat("}") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(self.expected(&[Expected::Char(','), Expected::Char('}')]));
+                }
+                self.whitespace();
+            }
+        }
+        table.kind = TableKind::Inline;
+        Ok(TomlValue::Table(table))
+    }
+
+    /// Parses a basic string, whose opening quote is next.
+    fn basic_string(&mut self, multiline: bool) -> TomlResult<'a, Cow<'a, str>> {
+        let open = self.at;
+        self.at += if multiline { 3 } else { 1 };
+        if multiline {
+            self.newline();
+        }
+        let mut owned: Option<String> = None;
+        let mut chunk = self.at;
+        loop {
+            let Some(byte) = self.peek() else {
+                let message = with_error!(ErrorKind::EndOfInput, "unterminated string");
+                return Err(self.error(open, message));
+            };
+            match byte {
+                b'"' => {
+                    let quotes = self.bytes().iter().take_while(|&&b| b == b'"').count();
+                    if !multiline || quotes >= 3 {
+                        // Up to two quotes may precede the closing delimiter.
+                        let extra = if multiline { quotes - 3 } else { 0 };
+                        if extra > 2 {
+                            return Err(self.error(
+                                self.at + 5,
+                                with_error!(ErrorKind::MalformedData, "too many quotes"),
+                            ));
+                        }
+                        let end = self.at + extra;
+                        self.at += extra + if multiline { 3 } else { 1 };
+                        return Ok(match owned {
+                            None => Cow::Borrowed(&self.source[chunk..end]),
+                            Some(mut owned) => {
+                                owned.push_str(&self.source[chunk..end]);
+                                Cow::Owned(owned)
+                            }
+                        });
+                    }
+                    self.at += quotes;
+                }
+                b'\\' => {
+                    let owned = owned.get_or_insert_with(String::new);
+                    owned.push_str(&self.source[chunk..self.at]);
+                    if multiline && self.is_line_ending_backslash() {
+                        self.at += 1;
+                        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+                            self.at += 1;
+                        }
+                    } else {
+                        owned.push(self.escape()?);
+                    }
+                    chunk = self.at;
+                }
+                b'\n' if multiline => self.at += 1,
+                b'\r' if multiline && self.starts_with("\r\n") => self.at += 2,
+                b'\n' => {
+                    let message = with_error!(ErrorKind::MalformedData, "unterminated string");
+                    return Err(self.error(open, message));
+                }
+                byte if is_control(byte) => return Err(self.control_character(byte)),
+                _ => self.at += 1,
+            }
+        }
+    }
+
+    /// Returns `true` if the backslash that is next is followed by nothing
+    /// but whitespace up to the end of the line.
+    fn is_line_ending_backslash(&self) -> bool {
+        let rest = &self.bytes()[1..];
+        let spaces = rest
+            .iter()
+            .take_while(|&&b| matches!(b, b' ' | b'\t'))
+            .count();
+        matches!(rest[spaces..], [b'\n', ..] | [b'\r', b'\n', ..])
+    }
+
+    fn control_character(&self, byte: u8) -> TomlError<'a> {
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "control character U+{byte:04X} must be escaped"
+        );
+        self.error(self.at, message)
+    }
+
+    /// Decodes the escape sequence that is next.
+    fn escape(&mut self) -> TomlResult<'a, char> {
+        let start = self.at;
+        let bytes = self.bytes();
+        let (c, length) = match bytes.get(1) {
+            Some(b'b') => ('\u{8}', 2),
+            Some(b't') => ('\t', 2),
+            Some(b'n') => ('\n', 2),
+            Some(b'f') => ('\u{c}', 2),
+            Some(b'r') => ('\r', 2),
+            Some(b'"') => ('"', 2),
+            Some(b'\\') => ('\\', 2),
+            Some(&kind @ (b'u' | b'U')) => {
+                let digits = if kind == b'u' { 4 } else { 8 };
+                let scalar = bytes
+                    .get(2..2 + digits)
+                    .and_then(|hex| core::str::from_utf8(hex).ok())
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                match scalar.and_then(char::from_u32) {
+                    Some(c) => (c, 2 + digits),
+                    None => {
+                        let message = with_error!(
+                            ErrorKind::MalformedData,
+                            "expected a Unicode scalar value as {} hexadecimal digits",
+                            digits
+                        );
+                        return Err(self.error(start, message));
+                    }
+                }
+            }
+            _ => {
+                let found = self.source[start + 1..].chars().next().unwrap_or_default();
+                let message = with_error!(ErrorKind::MalformedData, "invalid escape `\\{found}`");
+                return Err(self.error(start, message));
+            }
+        };
+        self.at += length;
+        Ok(c)
+    }
+
+    /// Parses a literal string, whose opening quote is next.
+    fn literal_string(&mut self, multiline: bool) -> TomlResult<'a, Cow<'a, str>> {
+        let open = self.at;
+        self.at += if multiline { 3 } else { 1 };
+        if multiline {
+            self.newline();
+        }
+        let start = self.at;
+        loop {
+            match self.peek() {
+                None => {
+                    let message = with_error!(ErrorKind::EndOfInput, "unterminated string");
+                    return Err(self.error(open, message));
+                }
+                Some(b'\'') => {
+                    let quotes = self.bytes().iter().take_while(|&&b| b == b'\'').count();
+                    if !multiline || quotes >= 3 {
+                        let extra = if multiline { quotes - 3 } else { 0 };
+                        if extra > 2 {
+                            return Err(self.error(
+                                self.at + 5,
+                                with_error!(ErrorKind::MalformedData, "too many quotes"),
+                            ));
+                        }
+                        let end = self.at + extra;
+                        self.at += extra + if multiline { 3 } else { 1 };
+                        return Ok(Cow::Borrowed(&self.source[start..end]));
+                    }
+                    self.at += quotes;
+                }
+                Some(b'\n') if multiline => self.at += 1,
+                Some(b'\r') if multiline && self.starts_with("\r\n") => self.at += 2,
+                Some(b'\n') => {
+                    let message = with_error!(ErrorKind::MalformedData, "unterminated string");
+                    return Err(self.error(open, message));
+                }
+                Some(byte) if is_control(byte) => return Err(self.control_character(byte)),
+                Some(_) => self.at += 1,
+            }
+        }
+    }
+
+    /// Parses an integer or a float.
+    fn number(&mut self) -> TomlResult<'a, TomlValue<'a>> {
+        let start = self.at;
+        let length = self
+            .bytes()
+            .iter()
+            .take_while(|&&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'+' | b'-'))
+            .count();
+        let token = &self.source[start..start + length];
+        let invalid = || {
+            let message = with_error!(ErrorKind::MalformedData, "invalid number `{token}`");
+            self.error(start, message)
+        };
+
+        let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+        let negative = token.starts_with('-');
+        let value = match unsigned {
+            "inf" => TomlValue::Float(if negative {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            }),
+            "nan" => TomlValue::Float(f64::NAN),
+            _ if unsigned.len() == token.len() && unsigned.starts_with("0x") => {
+                self.integer(start, &unsigned[2..], 16, false)?
+            }
+            _ if unsigned.len() == token.len() && unsigned.starts_with("0o") => {
+                self.integer(start, &unsigned[2..], 8, false)?
+            }
+            _ if unsigned.len() == token.len() && unsigned.starts_with("0b") => {
+                self.integer(start, &unsigned[2..], 2, false)?
+            }
+            _ if unsigned.contains(['.', 'e', 'E']) => {
+                let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+                    Some(index) => (&unsigned[..index], Some(&unsigned[index + 1..])),
+                    None => (unsigned, None),
+                };
+                let (integer, fraction) = match mantissa.split_once('.') {
+                    Some((integer, fraction)) => (integer, Some(fraction)),
+                    None => (mantissa, None),
+                };
+                let exponent =
+                    exponent.map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent));
+                let valid = is_digits(integer, 10)
+                    && fraction.is_none_or(|fraction| is_digits(fraction, 10))
+                    && exponent.is_none_or(|exponent| is_digits(exponent, 10));
+                if !valid {
+                    return Err(invalid());
+                }
+                self.leading_zeros(start, integer)?;
+                let digits: String = token.chars().filter(|&c| c != '_').collect();
+                TomlValue::Float(digits.parse().map_err(|_| invalid())?)
+            }
+            _ if is_digits(unsigned, 10) => {
+                self.leading_zeros(start, unsigned)?;
+                self.integer(start, unsigned, 10, negative)?
+            }
+            _ => return Err(invalid()),
+        };
+        self.at += length;
+        Ok(value)
+    }
+
+    fn leading_zeros(&self, start: usize, integer: &str) -> TomlResult<'a, ()> {
+        if integer.len() > 1 && integer.starts_with('0') {
+            let message = with_error!(ErrorKind::MalformedData, "leading zeros are not allowed");
+            return Err(self.error(start, message));
+        }
+        Ok(())
+    }
+
+    /// Converts `digits`, which may contain underscores, to an integer.
+    fn integer(
+        &self,
+        start: usize,
+        digits: &str,
+        radix: u32,
+        negative: bool,
+    ) -> TomlResult<'a, TomlValue<'a>> {
+        if !is_digits(digits, radix) {
+            let message = with_error!(ErrorKind::MalformedData, "invalid digits in integer");
+            return Err(self.error(start, message));
+        }
+        let mut value: i64 = 0;
+        for digit in digits.chars().filter_map(|c| c.to_digit(radix)) {
+            let digit = i64::from(digit);
+            // Negative values are accumulated downwards to reach `i64::MIN`.
+            let next = value.checked_mul(i64::from(radix)).and_then(|value| {
+                if negative {
+                    value.checked_sub(digit)
+                } else {
+                    value.checked_add(digit)
+                }
+            });
+            let Some(next) = next else {
+                let message = with_error!(ErrorKind::MalformedData, "integer is out of range");
+                return Err(self.error(start, message));
+            };
+            value = next;
+        }
+        Ok(TomlValue::Integer(value))
+    }
+
+    /// Returns `true` if the digits that are next start a date or a time.
+    fn is_datetime(&self) -> bool {
+        let bytes = self.bytes();
+        let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+        matches!(
+            (digits, bytes.get(digits)),
+            (4, Some(b'-')) | (2, Some(b':'))
+        )
+    }
+
+    /// Reads `count` digits, returning their value.
+    fn fixed(&mut self, count: usize) -> TomlResult<'a, u32> {
+        let digits = self
+            .bytes()
+            .get(..count)
+            .filter(|digits| digits.iter().all(u8::is_ascii_digit));
+        let Some(digits) = digits else {
+            return Err(self.expected(&[Expected::Class("digit")]));
+        };
+        let value = digits
+            .iter()
+            .fold(0, |value, &digit| value * 10 + u32::from(digit - b'0'));
+        self.at += count;
+        Ok(value)
+    }
+
+    fn datetime(&mut self) -> TomlResult<'a, TomlDatetime> {
+        let mut datetime = TomlDatetime {
+            date: None,
+            time: None,
+            offset: None,
+        };
+        if self.bytes().get(2) != Some(&b':') {
+            datetime.date = Some(self.date()?);
+            let bytes = self.bytes();
+            let separated = match bytes.first() {
+                Some(b'T' | b't') => true,
+                // A space only separates the date from a time that follows.
+                Some(b' ') => {
+                    bytes.len() > 3
+                        && bytes[1..3].iter().all(u8::is_ascii_digit)
+                        && bytes.get(3) == Some(&b':')
+                }
+                _ => false,
+            };
+            if !separated {
+                return Ok(datetime);
+            }
+            self.at += 1;
+        }
+        datetime.time = Some(self.time()?);
+        if datetime.date.is_some() {
+            datetime.offset = self.offset()?;
+        }
+        Ok(datetime)
+    }
+
+    fn date(&mut self) -> TomlResult<'a, TomlDate> {
+        let start = self.at;
+        let year = self.fixed(4)? as u16;
+        self.expect('-')?;
+        let month = self.fixed(2)? as u8;
+        self.expect('-')?;
+        let day = self.fixed(2)? as u8;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            let message = with_error!(ErrorKind::MalformedData, "invalid date");
+            return Err(self.error(start, message));
+        }
+        Ok(TomlDate { year, month, day })
+    }
+
+    fn time(&mut self) -> TomlResult<'a, TomlTime> {
+        let start = self.at;
+        let hour = self.fixed(2)? as u8;
+        self.expect(':')?;
+        let minute = self.fixed(2)? as u8;
+        self.expect(':')?;
+        let second = self.fixed(2)? as u8;
+        let mut nanosecond = 0;
+        if self.eat(".") {
+            let digits = self
+                .bytes()
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            if digits == 0 {
+                return Err(self.expected(&[Expected::Class("digit")]));
+            }
+            // Digits beyond nanosecond precision are truncated.
+            for (index, &digit) in self.bytes()[..digits].iter().enumerate().take(9) {
+                nanosecond += u32::from(digit - b'0') * 10u32.pow(8 - index as u32);
+            }
+            self.at += digits;
+        }
+        if hour > 23 || minute > 59 || second > 60 {
+            let message = with_error!(ErrorKind::MalformedData, "invalid time");
+            return Err(self.error(start, message));
+        }
+        Ok(TomlTime {
+            hour,
+            minute,
+            second,
+            nanosecond,
+        })
+    }
+
+    fn offset(&mut self) -> TomlResult<'a, Option<TomlOffset>> {
+        let start = self.at;
+        let sign = match self.peek() {
+            Some(b'Z' | b'z') => {
+                self.at += 1;
+                return Ok(Some(TomlOffset::Z));
+            }
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Ok(None),
+        };
+        self.at += 1;
+        let hours = self.fixed(2)?;
+        self.expect(':')?;
+        let minutes = self.fixed(2)?;
+        if hours > 23 || minutes > 59 {
+            let message = with_error!(ErrorKind::MalformedData, "invalid time offset");
+            return Err(self.error(start, message));
+        }
+        let minutes = sign * (hours * 60 + minutes) as i16;
+        Ok(Some(TomlOffset::Custom { minutes }))
+    }
+}
+
+/// Returns the table opened by the header with the keys `path`.
+fn current<'t, 'a>(
+    mut table: &'t mut TomlTable<'a>,
+    path: &[TomlKey<'a>],
+) -> &'t mut TomlTable<'a> {
+    for key in path {
+        // Headers are validated when they are opened.
+        table = match table.value_mut(key.name()) {
+            Some(TomlValue::Table(inner)) => inner,
+            Some(TomlValue::ArrayOfTables(tables)) => {
+                tables.last_mut().expect("arrays of tables are never empty")
+            }
+            _ => unreachable!("the current table exists"),
+        };
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(text: &str) -> TomlDatetime {
+        let source = alloc::format!("x = {text}");
+        let document = parse(&source).unwrap();
+        *document.get("x").and_then(TomlValue::as_datetime).unwrap()
+    }
+
+    #[test]
+    fn parses_keys_and_tables() {
+        let source = r#"
+            # This is a TOML document
+            title = "TOML Example"
+            site."google.com" = true
+            3.14159 = "pi"
+
+            [owner]
+            name = "Tom Preston-Werner"
+
+            [servers.alpha]
+            ip = "10.0.0.1"
+
+            [servers]
+            count = 1
+
+            [fruit]
+            apple.color = "red"
+            apple.taste.sweet = true
+
+            [fruit.apple.texture]
+            smooth = true
+
+            [[products]]
+            name = "Hammer"
+
+            [[products]]
+
+            [[products]]
+            name = "Nail"
+            point = { x = 1, y.z = 2 }
+        "#;
+        let document = parse(source).unwrap();
+        let get = |path: &[&str]| {
+            let (first, rest) = path.split_first().unwrap();
+            rest.iter()
+                .fold(document.get(first), |value, key| value?.get(key))
+                .unwrap_or_else(|| panic!("missing {path:?}"))
+        };
+        assert_eq!(get(&["title"]).as_str(), Some("TOML Example"));
+        assert_eq!(get(&["site", "google.com"]).as_bool(), Some(true));
+        assert_eq!(get(&["3", "14159"]).as_str(), Some("pi"));
+        assert_eq!(get(&["servers", "alpha", "ip"]).as_str(), Some("10.0.0.1"));
+        assert_eq!(get(&["servers", "count"]).as_integer(), Some(1));
+        assert_eq!(
+            get(&["fruit", "apple", "taste", "sweet"]).as_bool(),
+            Some(true)
+        );
+        assert_eq!(
+            get(&["fruit", "apple", "texture", "smooth"]).as_bool(),
+            Some(true)
+        );
+
+        let products = get(&["products"]).as_tables().unwrap();
+        assert_eq!(products.len(), 3);
+        assert!(products[1].is_empty());
+        let point = products[2].get("point").unwrap();
+        assert_eq!(
+            point.get("y").and_then(|y| y.get("z")),
+            Some(&TomlValue::Integer(2))
+        );
+
+        let key = document.key("title").unwrap();
+        assert_eq!(&source[key.span().start()..key.span().end()], "title");
+        let key = get(&["site"])
+            .as_table()
+            .unwrap()
+            .key("google.com")
+            .unwrap();
+        assert_eq!(
+            &source[key.span().start()..key.span().end()],
+            "\"google.com\""
+        );
+        // A table implied by a header is spanned by the header defining it.
+        let key = document.key("servers").unwrap();
+        assert_eq!(
+            &source[key.span().start() - 1..key.span().end() + 1],
+            "[servers]"
+        );
+    }
+
+    #[test]
+    fn parses_strings() {
+        let source = concat!(
+            "basic = \"tab\\there \\u00E9 \\U0001F600 \\\"q\\\"\"\n",
+            "literal = 'C:\\Users\\nodejs'\n",
+            "multi = \"\"\"\nRoses\\n\\\n    are red\"\"\"\"\"\n",
+            "raw = '''\nfirst\n  second'''\n",
+            "empty = \"\"\n",
+        );
+        let document = parse(source).unwrap();
+        let string = |key| document.get(key).and_then(TomlValue::as_str).unwrap();
+        assert_eq!(string("basic"), "tab\there é 😀 \"q\"");
+        assert_eq!(string("literal"), "C:\\Users\\nodejs");
+        assert_eq!(string("multi"), "Roses\nare red\"\"");
+        assert_eq!(string("raw"), "first\n  second");
+        assert_eq!(string("empty"), "");
+        assert!(matches!(
+            document.get("literal"),
+            Some(TomlValue::String(Cow::Borrowed(_)))
+        ));
+
+        let error = parse("a = \"\\x\"").unwrap_err();
+        assert_eq!(error.to_string(), "1:6: invalid escape `\\x`");
+        let error = parse("a = \"open\nb = 1").unwrap_err();
+        assert_eq!(error.to_string(), "1:5: unterminated string");
+        let error = parse("a = \"\\uD800\"").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MalformedData);
+    }
+
+    #[test]
+    fn parses_numbers() {
+        let document = parse(
+            "a = +99\nb = -17\nc = 1_000\nd = 0xDEAD_beef\ne = 0o755\nf = 0b1101\n\
+             g = -9_223_372_036_854_775_808\nh = 6.626e-34\ni = -0.01\nj = 5e+22\n\
+             k = -inf\nl = nan\nm = [1, 2.5, \"mixed\",]\n",
+        )
+        .unwrap();
+        let integer = |key| document.get(key).and_then(TomlValue::as_integer).unwrap();
+        let float = |key| document.get(key).and_then(TomlValue::as_float).unwrap();
+        assert_eq!(integer("a"), 99);
+        assert_eq!(integer("b"), -17);
+        assert_eq!(integer("c"), 1000);
+        assert_eq!(integer("d"), 0xDEAD_BEEF);
+        assert_eq!(integer("e"), 0o755);
+        assert_eq!(integer("f"), 0b1101);
+        assert_eq!(integer("g"), i64::MIN);
+        assert_eq!(float("h"), 6.626e-34);
+        assert_eq!(float("i"), -0.01);
+        assert_eq!(float("j"), 5e22);
+        assert_eq!(float("k"), f64::NEG_INFINITY);
+        assert!(float("l").is_nan());
+        assert_eq!(
+            document
+                .get("m")
+                .and_then(TomlValue::as_array)
+                .map(<[_]>::len),
+            Some(3)
+        );
+
+        for invalid in [
+            "01",
+            "1__0",
+            "_1",
+            "1_",
+            "0x_1",
+            "+0x1",
+            "1.",
+            ".5",
+            "1e",
+            "1.e5",
+            "9223372036854775808",
+        ] {
+            assert!(
+                parse(&alloc::format!("x = {invalid}")).is_err(),
+                "{invalid}"
+            );
+        }
+        let error = parse("x = 012").unwrap_err();
+        assert_eq!(error.to_string(), "1:5: leading zeros are not allowed");
+    }
+
+    #[test]
+    fn parses_datetimes() {
+        let value = datetime("1979-05-27T00:32:00.999999-07:00");
+        assert_eq!(
+            value.date,
+            Some(TomlDate {
+                year: 1979,
+                month: 5,
+                day: 27
+            })
+        );
+        assert_eq!(value.time.unwrap().nanosecond, 999_999_000);
+        assert_eq!(value.offset, Some(TomlOffset::Custom { minutes: -420 }));
+        assert_eq!(value.to_string(), "1979-05-27T00:32:00.999999-07:00");
+
+        assert_eq!(
+            datetime("1979-05-27 07:32:00Z").to_string(),
+            "1979-05-27T07:32:00Z"
+        );
+        assert_eq!(datetime("1979-05-27T07:32:00").offset, None);
+        assert_eq!(datetime("1979-05-27").time, None);
+        assert_eq!(datetime("07:32:00").date, None);
+        assert_eq!(datetime("2000-02-29").to_string(), "2000-02-29");
+
+        let error = parse("x = 2023-02-29").unwrap_err();
+        assert_eq!(error.to_string(), "1:5: invalid date");
+        let error = parse("x = 07:60:00").unwrap_err();
+        assert_eq!(error.to_string(), "1:5: invalid time");
+    }
+
+    #[test]
+    fn redefinitions_point_at_both_definitions() {
+        let error = parse("[a]\nb = 1\n\n[a]\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "4:2: duplicate table `a` (first defined at 1:2)"
+        );
+        let previous = error.previous().unwrap();
+        assert_eq!(
+            (previous.line(), previous.column(), previous.offset()),
+            (1, 2, 1)
+        );
+
+        let error = parse("[fruit]\napple.color = 'red'\n[fruit.apple]\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "3:8: duplicate table `apple` (first defined at 2:1)"
+        );
+
+        let error = parse("a = {b = 1}\n[a.c]\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2:2: cannot extend table `a` outside its definition (first defined at 1:1)"
+        );
+
+        let error = parse("[a.b.c]\nz = 9\n[a]\nb.c.t = 1\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "4:1: cannot extend table `b` outside its definition (first defined at 1:4)"
+        );
+
+        let error = parse("a = [1]\n[[a]]\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2:3: duplicate key `a` (first defined at 1:1)"
+        );
+
+        let error = parse("x = { a = 1, \"a\" = 2 }").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:14: duplicate key `a` (first defined at 1:7)"
+        );
+
+        assert!(parse("[[a]]\n[a]\n").is_err());
+        assert!(parse("a.b = 1\na = 2\n").is_err());
+        assert!(parse("[a.b]\n[a]\n[a.c]\n").is_ok());
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let error = parse("a = 1 b = 2").unwrap_err();
+        assert_eq!(error.to_string(), "1:7: expected end of line, found `b`");
+
+        let error = parse("a = [1 2]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:8: expected one of `,`, `]`, found `2`"
+        );
+
+        let error = parse("x = { a = 1, }").unwrap_err();
+        assert_eq!(error.to_string(), "1:14: expected key, found `}`");
+
+        let error = parse("a =\n").unwrap_err();
+        assert_eq!(error.to_string(), "1:4: expected value, found `\n`");
+
+        let error = parse("# bell \u{7}\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:8: control character U+0007 is not allowed in comments"
+        );
+
+        let nested = alloc::format!("x = {}{}", "[".repeat(129), "]".repeat(129));
+        let error = parse(&nested).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:133: exceeded the maximum nesting depth of 128"
+        );
+    }
+}
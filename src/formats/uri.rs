@@ -0,0 +1,627 @@
+//! This module contains a parser for URI references, as specified by RFC
+//! 3986.
+//!
+//! A reference is split into its scheme, authority, path, query and fragment
+//! without copying; each component is a [`StrSpan`] into the input and keeps
+//! its percent-encoding, which [`percent_decode`] removes. Relative references
+//! are resolved against a base URI with [`Uri::resolve`].
+//!
+//! ```
+//! use recoil::formats::uri;
+//!
+//! let base = uri::parse("https://user@example.com:8443/a/b?q=1#top").unwrap();
+//! let authority = base.authority().unwrap();
+//! assert_eq!(base.scheme().map(|scheme| scheme.as_str()), Some("https"));
+//! assert_eq!(authority.host().as_str(), "example.com");
+//! assert_eq!(authority.port_number(), Some(8443));
+//! assert_eq!(base.path().as_str(), "/a/b");
+//!
+//! let reference = uri::parse("../c%20d").unwrap();
+//! assert_eq!(base.resolve(&reference), "https://user@example.com:8443/c%20d");
+//! assert_eq!(uri::percent_decode(reference.path().as_str()), &b"../c d"[..]);
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::Ipv6Addr;
+
+use super::SyntaxError;
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext};
+use crate::located::Located;
+use crate::span::{Span, StrSpan};
+use crate::with_error;
+
+/// The authority component of a URI, as in `user@host:port`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Authority<'a> {
+    span: StrSpan<'a>,
+    userinfo: Option<StrSpan<'a>>,
+    host: StrSpan<'a>,
+    port: Option<StrSpan<'a>>,
+}
+
+impl<'a> Authority<'a> {
+    /// Returns the span of the whole authority, without the leading `//`.
+    pub fn span(&self) -> StrSpan<'a> {
+        self.span
+    }
+
+    pub fn userinfo(&self) -> Option<StrSpan<'a>> {
+        self.userinfo
+    }
+
+    /// Returns the host, which keeps the brackets of IP literals.
+    pub fn host(&self) -> StrSpan<'a> {
+        self.host
+    }
+
+    /// Returns the contents of an IP literal host, such as an IPv6 address,
+    /// without the brackets.
+    pub fn ip_literal(&self) -> Option<&'a str> {
+        self.host
+            .as_str()
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+    }
+
+    /// Returns the port, which may be empty, as in `host:`.
+    pub fn port(&self) -> Option<StrSpan<'a>> {
+        self.port
+    }
+
+    /// Returns the port as a number, if it is present and in range.
+    pub fn port_number(&self) -> Option<u16> {
+        self.port?.as_str().parse().ok()
+    }
+}
+
+/// A URI reference, which is either a URI or a relative reference.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Uri<'a> {
+    source: &'a str,
+    scheme: Option<StrSpan<'a>>,
+    authority: Option<Authority<'a>>,
+    path: StrSpan<'a>,
+    query: Option<StrSpan<'a>>,
+    fragment: Option<StrSpan<'a>>,
+}
+
+impl<'a> Uri<'a> {
+    /// Returns the reference exactly as it appears in the input.
+    pub fn as_str(&self) -> &'a str {
+        self.source
+    }
+
+    pub fn scheme(&self) -> Option<StrSpan<'a>> {
+        self.scheme
+    }
+
+    pub fn authority(&self) -> Option<Authority<'a>> {
+        self.authority
+    }
+
+    /// Returns the path, which is empty rather than missing.
+    pub fn path(&self) -> StrSpan<'a> {
+        self.path
+    }
+
+    /// Returns the query, without the leading `?`.
+    pub fn query(&self) -> Option<StrSpan<'a>> {
+        self.query
+    }
+
+    /// Returns the fragment, without the leading `#`.
+    pub fn fragment(&self) -> Option<StrSpan<'a>> {
+        self.fragment
+    }
+
+    /// Returns `true` if the reference has a scheme, making it a URI rather
+    /// than a relative reference.
+    pub fn is_absolute(&self) -> bool {
+        self.scheme.is_some()
+    }
+
+    /// Resolves `reference` against this URI as its base, following section
+    /// 5.2 of RFC 3986, and returns the target URI.
+    ///
+    /// The base should be absolute. Dot segments are removed from the path of
+    /// the target, but no other normalization is applied.
+    pub fn resolve(&self, reference: &Uri<'_>) -> String {
+        fn text(span: Option<StrSpan<'_>>) -> Option<&str> {
+            span.map(|span| span.as_str())
+        }
+        fn authority<'s>(uri: &Uri<'s>) -> Option<&'s str> {
+            uri.authority.map(|authority| authority.span.as_str())
+        }
+
+        let (scheme, authority, path, query) = if reference.scheme.is_some() {
+            let path = remove_dot_segments(&reference.path);
+            (
+                text(reference.scheme),
+                authority(reference),
+                path,
+                text(reference.query),
+            )
+        } else if reference.authority.is_some() {
+            let path = remove_dot_segments(&reference.path);
+            (
+                text(self.scheme),
+                authority(reference),
+                path,
+                text(reference.query),
+            )
+        } else if reference.path.is_empty() {
+            let query = text(reference.query).or(text(self.query));
+            (
+                text(self.scheme),
+                authority(self),
+                String::from(self.path.as_str()),
+                query,
+            )
+        } else if reference.path.starts_with('/') {
+            let path = remove_dot_segments(&reference.path);
+            (
+                text(self.scheme),
+                authority(self),
+                path,
+                text(reference.query),
+            )
+        } else {
+            let path = remove_dot_segments(&self.merge(&reference.path));
+            (
+                text(self.scheme),
+                authority(self),
+                path,
+                text(reference.query),
+            )
+        };
+
+        let mut target = String::new();
+        if let Some(scheme) = scheme {
+            target.push_str(scheme);
+            target.push(':');
+        }
+        if let Some(authority) = authority {
+            target.push_str("//");
+            target.push_str(authority);
+        }
+        target.push_str(&path);
+        if let Some(query) = query {
+            target.push('?');
+            target.push_str(query);
+        }
+        if let Some(fragment) = reference.fragment {
+            target.push('#');
+            target.push_str(&fragment);
+        }
+        target
+    }
+
+    /// Merges the relative `path` of a reference with the path of this URI.
+    fn merge(&self, path: &str) -> String {
+        if self.authority.is_some() && self.path.is_empty() {
+            return alloc::format!("/{path}");
+        }
+        let directory = self
+            .path
+            .rfind('/')
+            .map_or("", |index| &self.path[..=index]);
+        alloc::format!("{directory}{path}")
+    }
+}
+
+impl fmt::Display for Uri<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.source)
+    }
+}
+
+/// Removes the `.` and `..` segments of `path`, following section 5.2.4 of
+/// RFC 3986.
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    while !input.is_empty() {
+        if let Some(rest) = input
+            .strip_prefix("../")
+            .or_else(|| input.strip_prefix("./"))
+        {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") || input == "/.." {
+            input = if input.len() == 3 { "/" } else { &input[3..] };
+            let last = output.rfind('/').unwrap_or(0);
+            output.truncate(last);
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            // Move the first segment, with its leading slash, to the output.
+            let end = input[1..].find('/').map_or(input.len(), |index| index + 1);
+            output.push_str(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output
+}
+
+/// Error returned when parsing a URI reference fails.
+pub type UriError<'a> = SyntaxError<'a>;
+
+type ParseError<'a> = ErrorWithContext<Located<&'a str>>;
+
+/// Parses a URI reference, which may be relative.
+pub fn parse(input: &str) -> Result<Uri<'_>, UriError<'_>> {
+    let span = |start: usize, end: usize| StrSpan::with_offsets(&input[start..end], start, end);
+    let bytes = input.as_bytes();
+    let find = |start: usize, delimiters: &[u8]| {
+        bytes[start..]
+            .iter()
+            .position(|b| delimiters.contains(b))
+            .map_or(input.len(), |index| start + index)
+    };
+
+    let mut at = 0;
+    let scheme_length = match bytes.first() {
+        Some(b) if b.is_ascii_alphabetic() => bytes
+            .iter()
+            .take_while(|&&b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+            .count(),
+        _ => 0,
+    };
+    let scheme = if scheme_length > 0 && bytes.get(scheme_length) == Some(&b':') {
+        at = scheme_length + 1;
+        Some(span(0, scheme_length))
+    } else {
+        None
+    };
+
+    let authority = if input[at..].starts_with("//") {
+        let start = at + 2;
+        at = find(start, b"/?#");
+        Some(authority(input, start, at)?)
+    } else {
+        None
+    };
+
+    let end = find(at, b"?#");
+    validate(input, at, end, b":@/", "path")?;
+    if scheme.is_none() && authority.is_none() {
+        let segment = find(at, b"/");
+        if let Some(colon) = input[at..segment.min(end)].find(':') {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "the first segment of a relative path cannot contain `:`"
+            );
+            return Err(failure(input, at + colon, message));
+        }
+    }
+    let path = span(at, end);
+    at = end;
+
+    let query = if bytes.get(at) == Some(&b'?') {
+        let end = find(at + 1, b"#");
+        validate(input, at + 1, end, b":@/?", "query")?;
+        let query = span(at + 1, end);
+        at = end;
+        Some(query)
+    } else {
+        None
+    };
+    let fragment = if bytes.get(at) == Some(&b'#') {
+        validate(input, at + 1, input.len(), b":@/?", "fragment")?;
+        Some(span(at + 1, input.len()))
+    } else {
+        None
+    };
+
+    Ok(Uri {
+        source: input,
+        scheme,
+        authority,
+        path,
+        query,
+        fragment,
+    })
+}
+
+fn failure(input: &str, at: usize, message: ErrorMessage) -> UriError<'_> {
+    let location = Located::new(input).over(at..input.len());
+    SyntaxError::new(ParseError::from_message(location, message))
+}
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+fn is_sub_delim(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+    )
+}
+
+/// Checks that `input[start..end]` holds only unreserved characters,
+/// sub-delimiters, percent-encoded bytes and the `extra` characters.
+fn validate<'a>(
+    input: &'a str,
+    start: usize,
+    end: usize,
+    extra: &[u8],
+    component: &str,
+) -> Result<(), UriError<'a>> {
+    let bytes = input.as_bytes();
+    let mut at = start;
+    while at < end {
+        let byte = bytes[at];
+        if byte == b'%' {
+            let hex = bytes.get(at + 1..at + 3).filter(|_| at + 3 <= end);
+            if !hex.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+                let message = with_error!(ErrorKind::MalformedData, "invalid percent-encoding");
+                return Err(failure(input, at, message));
+            }
+            at += 3;
+        } else if is_unreserved(byte) || is_sub_delim(byte) || extra.contains(&byte) {
+            at += 1;
+        } else {
+            let message = with_error!(ErrorKind::MalformedData, "invalid character in {component}");
+            return Err(failure(input, at, message));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the authority at `input[start..end]`.
+fn authority(input: &str, start: usize, end: usize) -> Result<Authority<'_>, UriError<'_>> {
+    let span = |start: usize, end: usize| StrSpan::with_offsets(&input[start..end], start, end);
+    let text = &input[start..end];
+
+    let (userinfo, host_start) = match text.find('@') {
+        Some(index) => {
+            validate(input, start, start + index, b":", "userinfo")?;
+            (Some(span(start, start + index)), start + index + 1)
+        }
+        None => (None, start),
+    };
+
+    let (host_end, port_start) = if input[host_start..end].starts_with('[') {
+        let Some(close) = input[host_start..end].find(']') else {
+            let message = with_error!(ErrorKind::MalformedData, "unterminated IP literal");
+            return Err(failure(input, host_start, message));
+        };
+        let host_end = host_start + close + 1;
+        ip_literal(input, host_start + 1, host_end - 1)?;
+        match input.as_bytes().get(host_end) {
+            _ if host_end == end => (host_end, None),
+            Some(b':') => (host_end, Some(host_end + 1)),
+            _ => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "expected a port after the IP literal"
+                );
+                return Err(failure(input, host_end, message));
+            }
+        }
+    } else {
+        match input[host_start..end].rfind(':') {
+            Some(colon) => (host_start + colon, Some(host_start + colon + 1)),
+            None => (end, None),
+        }
+    };
+    if !input[host_start..].starts_with('[') {
+        validate(input, host_start, host_end, b"", "host")?;
+    }
+
+    let port = match port_start {
+        Some(port_start) => {
+            if let Some(index) = input[port_start..end]
+                .bytes()
+                .position(|b| !b.is_ascii_digit())
+            {
+                let message = with_error!(ErrorKind::MalformedData, "invalid character in port");
+                return Err(failure(input, port_start + index, message));
+            }
+            Some(span(port_start, end))
+        }
+        None => None,
+    };
+
+    Ok(Authority {
+        span: span(start, end),
+        userinfo,
+        host: span(host_start, host_end),
+        port,
+    })
+}
+
+/// Checks the contents of an IP literal at `input[start..end]`, which is
+/// either an IPv6 address or an `IPvFuture` address.
+fn ip_literal(input: &str, start: usize, end: usize) -> Result<(), UriError<'_>> {
+    let text = &input[start..end];
+    let valid = match text.strip_prefix(['v', 'V']) {
+        Some(future) => future.split_once('.').is_some_and(|(version, address)| {
+            !version.is_empty()
+                && version.bytes().all(|b| b.is_ascii_hexdigit())
+                && !address.is_empty()
+                && address
+                    .bytes()
+                    .all(|b| is_unreserved(b) || is_sub_delim(b) || b == b':')
+        }),
+        None => text.parse::<Ipv6Addr>().is_ok(),
+    };
+    if valid {
+        Ok(())
+    } else {
+        let message = with_error!(ErrorKind::MalformedData, "invalid IP literal");
+        Err(failure(input, start, message))
+    }
+}
+
+/// Decodes the percent-encoded bytes of `text`, allocating only if there are
+/// any.
+///
+/// A `%` that is not followed by two hexadecimal digits is kept as it is.
+pub fn percent_decode(text: &str) -> Cow<'_, [u8]> {
+    let bytes = text.as_bytes();
+    if !bytes.contains(&b'%') {
+        return Cow::Borrowed(bytes);
+    }
+    let hex = |byte: u8| char::from(byte).to_digit(16);
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        let escape = match bytes[at..] {
+            [b'%', high, low, ..] => hex(high).zip(hex(low)),
+            _ => None,
+        };
+        match escape {
+            Some((high, low)) => {
+                decoded.push((high * 16 + low) as u8);
+                at += 3;
+            }
+            None => {
+                decoded.push(bytes[at]);
+                at += 1;
+            }
+        }
+    }
+    Cow::Owned(decoded)
+}
+
+/// Decodes the percent-encoded bytes of `text` like [`percent_decode`],
+/// returning `None` if the result is not valid UTF-8.
+pub fn percent_decode_str(text: &str) -> Option<Cow<'_, str>> {
+    match percent_decode(text) {
+        Cow::Borrowed(_) => Some(Cow::Borrowed(text)),
+        Cow::Owned(bytes) => String::from_utf8(bytes).ok().map(Cow::Owned),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_components() {
+        let uri = parse("foo://user:pw@example.com:8042/over/there?name=ferret#nose").unwrap();
+        let authority = uri.authority().unwrap();
+        assert_eq!(uri.scheme().unwrap().as_str(), "foo");
+        assert_eq!(authority.span().as_str(), "user:pw@example.com:8042");
+        assert_eq!(authority.userinfo().unwrap().as_str(), "user:pw");
+        assert_eq!(authority.host().as_str(), "example.com");
+        assert_eq!(authority.port_number(), Some(8042));
+        assert_eq!(uri.path().as_str(), "/over/there");
+        assert_eq!(uri.query().unwrap().as_str(), "name=ferret");
+        assert_eq!(uri.fragment().unwrap().as_str(), "nose");
+        let path = uri.path();
+        assert_eq!((path.start(), path.end()), (30, 41));
+
+        let uri = parse("urn:example:animal:ferret:nose").unwrap();
+        assert!(uri.authority().is_none());
+        assert_eq!(uri.path().as_str(), "example:animal:ferret:nose");
+
+        let uri = parse("http://[2001:db8::7]:/c=GB?").unwrap();
+        let authority = uri.authority().unwrap();
+        assert_eq!(authority.host().as_str(), "[2001:db8::7]");
+        assert_eq!(authority.ip_literal(), Some("2001:db8::7"));
+        assert_eq!(authority.port().map(|port| port.as_str()), Some(""));
+        assert_eq!(authority.port_number(), None);
+        assert_eq!(uri.query().unwrap().as_str(), "");
+
+        let uri = parse("//[v7.fe80::a+en1]/").unwrap();
+        assert!(!uri.is_absolute());
+        assert_eq!(
+            uri.authority().unwrap().ip_literal(),
+            Some("v7.fe80::a+en1")
+        );
+
+        let uri = parse("").unwrap();
+        assert!(uri.path().is_empty() && uri.query().is_none());
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let error = parse("http://exa mple.com/").unwrap_err();
+        assert_eq!(error.to_string(), "1:11: invalid character in host");
+
+        let error = parse("http://[::1/").unwrap_err();
+        assert_eq!(error.to_string(), "1:8: unterminated IP literal");
+
+        let error = parse("http://[12345::]/").unwrap_err();
+        assert_eq!(error.to_string(), "1:9: invalid IP literal");
+
+        let error = parse("http://host:80a/").unwrap_err();
+        assert_eq!(error.offset(), 14);
+
+        let error = parse("/a%2/b").unwrap_err();
+        assert_eq!(error.to_string(), "1:3: invalid percent-encoding");
+
+        assert!(parse("a:b/c").unwrap().is_absolute());
+        let error = parse("1a:b").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:3: the first segment of a relative path cannot contain `:`"
+        );
+
+        let error = parse("/path?q#frag#ment").unwrap_err();
+        assert_eq!(error.to_string(), "1:13: invalid character in fragment");
+    }
+
+    #[test]
+    fn resolves_references() {
+        let base = parse("http://a/b/c/d;p?q").unwrap();
+        let examples = [
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            (";x", "http://a/b/c/;x"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../g", "http://a/g"),
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            ("..g", "http://a/b/c/..g"),
+            ("./../g", "http://a/b/g"),
+            ("./g/.", "http://a/b/c/g/"),
+            ("g/./h", "http://a/b/c/g/h"),
+            ("g/../h", "http://a/b/c/h"),
+            ("g;x=1/./y", "http://a/b/c/g;x=1/y"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+            ("g?y/./x", "http://a/b/c/g?y/./x"),
+            ("g#s/../x", "http://a/b/c/g#s/../x"),
+            ("http:g", "http:g"),
+        ];
+        for (reference, target) in examples {
+            let resolved = base.resolve(&parse(reference).unwrap());
+            assert_eq!(resolved, target, "{reference}");
+        }
+
+        let base = parse("http://example.com").unwrap();
+        assert_eq!(base.resolve(&parse("a").unwrap()), "http://example.com/a");
+    }
+
+    #[test]
+    fn decodes_percent_encoding() {
+        assert!(matches!(percent_decode("plain"), Cow::Borrowed(b"plain")));
+        assert_eq!(percent_decode("a%20b%2Fc%zz%4"), &b"a b/c%zz%4"[..]);
+        assert_eq!(percent_decode_str("caf%C3%A9").as_deref(), Some("café"));
+        assert_eq!(percent_decode_str("%FF"), None);
+    }
+}
@@ -0,0 +1,1092 @@
+//! This module contains a parser for the subset of YAML used by configuration
+//! files, such as Docker Compose files.
+//!
+//! The supported subset covers block mappings and sequences (including
+//! compact nested collections), single-line flow collections, plain, quoted
+//! and block scalars, comments, anchors, aliases and `<<` merge keys. Tags,
+//! multi-line flow collections and multiple documents are not supported.
+//!
+//! Every node records the span of the source it was parsed from. Scalars are
+//! not resolved to booleans or numbers, since configuration formats disagree
+//! on which plain scalars mean what.
+//!
+//! ```
+//! use recoil::formats::yaml::{self, YamlNode};
+//!
+//! let source = "services:\n  web:\n    image: nginx\n    ports: [\"80:80\"]\n";
+//! let document = yaml::parse(source).unwrap();
+//! let web = document.get("services").and_then(|services| services.get("web")).unwrap();
+//! let image = web.get("image").unwrap();
+//! assert_eq!(image.as_str(), Some("nginx"));
+//! assert_eq!(&source[image.span().start()..image.span().end()], "nginx");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::SyntaxError;
+use crate::error::{Error, ErrorKind, ErrorMessage, ErrorWithContext, Expected};
+use crate::located::Located;
+use crate::span::{Span, StrSpan};
+use crate::with_error;
+
+/// Maximum number of nodes that aliases may expand to in a single document.
+///
+/// Aliases are replaced by copies of the anchored node, so this guards
+/// against documents that nest aliases to grow exponentially.
+pub const MAX_ALIAS_EXPANSION: usize = 1 << 16;
+
+/// Maximum nesting depth of collections.
+const MAX_DEPTH: usize = 128;
+
+/// How a scalar was written in the source.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ScalarStyle {
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    /// A block scalar introduced by `|`.
+    Literal,
+    /// A block scalar introduced by `>`.
+    Folded,
+}
+
+/// The value of a YAML node.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum YamlValue<'a> {
+    /// An empty value, such as `key:` with nothing after it.
+    Null,
+    Scalar(Cow<'a, str>, ScalarStyle),
+    Sequence(Vec<YamlNode<'a>>),
+    /// Entries of a mapping, in the order they appear in the input.
+    Mapping(Vec<(YamlNode<'a>, YamlNode<'a>)>),
+}
+
+/// A YAML node, along with the span of the source it was parsed from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YamlNode<'a> {
+    value: YamlValue<'a>,
+    span: StrSpan<'a>,
+    anchor: Option<&'a str>,
+}
+
+impl<'a> YamlNode<'a> {
+    fn new(value: YamlValue<'a>, span: StrSpan<'a>) -> Self {
+        Self {
+            value,
+            span,
+            anchor: None,
+        }
+    }
+
+    pub fn value(&self) -> &YamlValue<'a> {
+        &self.value
+    }
+
+    /// Returns the span of the source this node was parsed from.
+    ///
+    /// Aliases are replaced by a copy of the anchored node, which spans the
+    /// alias itself.
+    pub fn span(&self) -> StrSpan<'a> {
+        self.span
+    }
+
+    /// Returns the name of the anchor defined on this node, if any.
+    pub fn anchor(&self) -> Option<&'a str> {
+        self.anchor
+    }
+
+    /// Returns `true` for empty values and the plain scalars `null` and `~`.
+    pub fn is_null(&self) -> bool {
+        match &self.value {
+            YamlValue::Null => true,
+            YamlValue::Scalar(text, ScalarStyle::Plain) => {
+                matches!(text.as_ref(), "~" | "null" | "Null" | "NULL")
+            }
+            _ => false,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            YamlValue::Scalar(text, _) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_sequence(&self) -> Option<&[YamlNode<'a>]> {
+        match &self.value {
+            YamlValue::Sequence(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_mapping(&self) -> Option<&[(YamlNode<'a>, YamlNode<'a>)]> {
+        match &self.value {
+            YamlValue::Mapping(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of `key`, if this is a mapping.
+    ///
+    /// Keys merged into the mapping with `<<` are found as well, with keys
+    /// defined by the mapping itself taking precedence.
+    pub fn get(&self, key: &str) -> Option<&YamlNode<'a>> {
+        let entries = self.as_mapping()?;
+        let own = entries
+            .iter()
+            .find(|(name, _)| !name.is_merge_key() && name.as_str() == Some(key));
+        if let Some((_, value)) = own {
+            return Some(value);
+        }
+        let (_, merged) = entries.iter().find(|(name, _)| name.is_merge_key())?;
+        match &merged.value {
+            YamlValue::Sequence(items) => items.iter().find_map(|item| item.get(key)),
+            _ => merged.get(key),
+        }
+    }
+
+    fn is_merge_key(&self) -> bool {
+        matches!(&self.value, YamlValue::Scalar(text, ScalarStyle::Plain) if text == "<<")
+    }
+
+    /// Returns the number of nodes in this subtree.
+    fn size(&self) -> usize {
+        1 + match &self.value {
+            YamlValue::Sequence(items) => items.iter().map(Self::size).sum(),
+            YamlValue::Mapping(entries) => entries.iter().map(|(k, v)| k.size() + v.size()).sum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Error returned when parsing YAML fails.
+pub type YamlError<'a> = SyntaxError<'a>;
+
+/// Parses a single YAML document.
+///
+/// An empty document produces a [`YamlValue::Null`] node.
+pub fn parse(input: &str) -> Result<YamlNode<'_>, YamlError<'_>> {
+    Reader::new(input).document().map_err(SyntaxError::new)
+}
+
+type ParseError<'a> = ErrorWithContext<Located<&'a str>>;
+type YamlResult<'a, T> = Result<T, ParseError<'a>>;
+
+/// A line of the source, as absolute byte offsets.
+#[derive(Clone, Copy, Debug)]
+struct Line {
+    begin: usize,
+    /// Start of the content, after the indentation.
+    start: usize,
+    /// End of the content, excluding the line terminator.
+    end: usize,
+}
+
+impl Line {
+    fn indent(&self) -> usize {
+        self.start - self.begin
+    }
+}
+
+/// Where a block value appears, which decides the forms it may take.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Context {
+    Document,
+    MappingValue,
+    SequenceItem,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Chomping {
+    Strip,
+    Clip,
+    Keep,
+}
+
+/// Returns `true` if `text` holds nothing but whitespace and comments.
+fn is_blank(text: &str) -> bool {
+    let text = text.trim_start_matches([' ', '\t']);
+    text.is_empty() || text.starts_with('#')
+}
+
+/// Returns `true` if `text` starts a block sequence entry.
+fn is_entry(text: &str) -> bool {
+    text == "-" || text.starts_with("- ") || text.starts_with("-\t")
+}
+
+/// Returns `true` if `text` consists of the document `marker` alone.
+fn is_marker(text: &str, marker: &str) -> bool {
+    text.strip_prefix(marker).is_some_and(is_blank)
+}
+
+/// Returns `true` if `text` cannot start a plain scalar.
+fn starts_with_indicator(text: &str) -> bool {
+    let indicator = text.starts_with([
+        '[', ']', '{', '}', ',', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`',
+    ]);
+    indicator || is_entry(text) || text.starts_with("? ") || text.starts_with(": ")
+}
+
+/// Returns the offset of the `:` that separates a plain key from its value.
+fn plain_colon(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    for (index, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'#' if index > 0 && matches!(bytes[index - 1], b' ' | b'\t') => return None,
+            b':' if matches!(bytes.get(index + 1), None | Some(b' ' | b'\t')) => {
+                return (index > 0).then_some(index);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the offset of a trailing comment in `text`, if any.
+fn comment_start(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    (0..bytes.len()).find(|&index| {
+        bytes[index] == b'#' && (index == 0 || matches!(bytes[index - 1], b' ' | b'\t'))
+    })
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    lines: Vec<Line>,
+    /// Index of the line being parsed.
+    index: usize,
+    depth: usize,
+    anchors: Vec<(&'a str, YamlNode<'a>)>,
+    /// Number of nodes produced by aliases so far.
+    expanded: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut lines = Vec::new();
+        let mut begin = 0;
+        for raw in source.split_inclusive('\n') {
+            let text = raw.strip_suffix('\n').unwrap_or(raw);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            let indent = text.bytes().take_while(|&b| b == b' ').count();
+            lines.push(Line {
+                begin,
+                start: begin + indent,
+                end: begin + text.len(),
+            });
+            begin += raw.len();
+        }
+        Self {
+            source,
+            lines,
+            index: 0,
+            depth: 0,
+            anchors: Vec::new(),
+            expanded: 0,
+        }
+    }
+
+    fn text(&self, start: usize, end: usize) -> &'a str {
+        &self.source[start..end]
+    }
+
+    fn byte(&self, at: usize, end: usize) -> Option<u8> {
+        (at < end).then(|| self.source.as_bytes()[at])
+    }
+
+    fn skip_spaces(&self, mut at: usize, end: usize) -> usize {
+        while matches!(self.byte(at, end), Some(b' ' | b'\t')) {
+            at += 1;
+        }
+        at
+    }
+
+    fn span(&self, start: usize, end: usize) -> StrSpan<'a> {
+        StrSpan::with_offsets(self.text(start, end), start, end)
+    }
+
+    fn located(&self, at: usize) -> Located<&'a str> {
+        Located::new(self.source).over(at..self.source.len())
+    }
+
+    fn error(&self, at: usize, message: ErrorMessage) -> ParseError<'a> {
+        Error::from_message(self.located(at), message)
+    }
+
+    fn expected(&self, at: usize, items: &[Expected]) -> ParseError<'a> {
+        super::expected(self.located(at), items)
+    }
+
+    fn unexpected_indentation(&self, line: Line) -> ParseError<'a> {
+        self.error(
+            line.start,
+            with_error!(ErrorKind::MalformedData, "unexpected indentation"),
+        )
+    }
+
+    /// Runs `parse` one nesting level deeper, enforcing the depth limit.
+    fn nested<T>(
+        &mut self,
+        at: usize,
+        parse: impl FnOnce(&mut Self) -> YamlResult<'a, T>,
+    ) -> YamlResult<'a, T> {
+        if self.depth >= MAX_DEPTH {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "exceeded the maximum nesting depth of {}",
+                MAX_DEPTH
+            );
+            return Err(self.error(at, message));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Returns the next line holding content, skipping blank lines and
+    /// comments.
+    fn next_line(&mut self) -> YamlResult<'a, Option<Line>> {
+        while let Some(&line) = self.lines.get(self.index) {
+            let text = self.text(line.start, line.end);
+            if is_blank(text) {
+                self.index += 1;
+            } else if text.starts_with('\t') {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "tabs are not allowed in indentation"
+                );
+                return Err(self.error(line.start, message));
+            } else {
+                return Ok(Some(line));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_document_marker(&self, line: Line) -> bool {
+        let text = self.text(line.start, line.end);
+        line.indent() == 0 && (is_marker(text, "---") || is_marker(text, "..."))
+    }
+
+    fn document(&mut self) -> YamlResult<'a, YamlNode<'a>> {
+        if let Some(line) = self.next_line()? {
+            if line.indent() == 0 && is_marker(self.text(line.start, line.end), "---") {
+                self.index += 1;
+            }
+        }
+        let root = match self.next_line()? {
+            Some(line) => self.block(line)?,
+            None => {
+                let end = self.source.len();
+                YamlNode::new(YamlValue::Null, self.span(end, end))
+            }
+        };
+        if let Some(line) = self.next_line()? {
+            if line.indent() == 0 && is_marker(self.text(line.start, line.end), "...") {
+                self.index += 1;
+            }
+        }
+        match self.next_line()? {
+            None => Ok(root),
+            Some(line) if is_marker(self.text(line.start, line.end), "---") => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "multiple documents are not supported"
+                );
+                Err(self.error(line.start, message))
+            }
+            Some(line) => Err(self.expected(line.start, &[Expected::Rule("end of document")])),
+        }
+    }
+
+    /// Parses the block node starting on `line`.
+    fn block(&mut self, line: Line) -> YamlResult<'a, YamlNode<'a>> {
+        self.nested(line.start, |reader| {
+            let text = reader.text(line.start, line.end);
+            if is_entry(text) {
+                reader.sequence(line.indent())
+            } else if reader.key(line.start, line.end)?.is_some() {
+                reader.mapping(line.indent())
+            } else {
+                reader.value(line.indent(), Context::Document, line.start, line.end)
+            }
+        })
+    }
+
+    fn sequence(&mut self, indent: usize) -> YamlResult<'a, YamlNode<'a>> {
+        let mut items = Vec::new();
+        let mut start = None;
+        let mut end = 0;
+        while let Some(line) = self.next_line()? {
+            let text = self.text(line.start, line.end);
+            if line.indent() < indent
+                || (line.indent() == indent && !is_entry(text))
+                || self.is_document_marker(line)
+            {
+                break;
+            }
+            if line.indent() > indent {
+                return Err(self.unexpected_indentation(line));
+            }
+            start.get_or_insert(line.start);
+            let item = self.value(indent, Context::SequenceItem, line.start + 1, line.end)?;
+            end = item.span.end();
+            items.push(item);
+        }
+        let span = self.span(start.unwrap_or(end), end);
+        Ok(YamlNode::new(YamlValue::Sequence(items), span))
+    }
+
+    fn mapping(&mut self, indent: usize) -> YamlResult<'a, YamlNode<'a>> {
+        let mut entries: Vec<(YamlNode<'a>, YamlNode<'a>)> = Vec::new();
+        let mut start = None;
+        let mut end = 0;
+        while let Some(line) = self.next_line()? {
+            if line.indent() < indent || self.is_document_marker(line) {
+                break;
+            }
+            if line.indent() > indent {
+                return Err(self.unexpected_indentation(line));
+            }
+            let Some((key, colon)) = self.key(line.start, line.end)? else {
+                return Err(self.expected(line.start, &[Expected::Rule("mapping key")]));
+            };
+            self.check_duplicate(&entries, &key)?;
+            start.get_or_insert(line.start);
+            let value = self.value(indent, Context::MappingValue, colon + 1, line.end)?;
+            end = value.span.end().max(colon + 1);
+            entries.push((key, value));
+        }
+        let span = self.span(start.unwrap_or(end), end);
+        Ok(YamlNode::new(YamlValue::Mapping(entries), span))
+    }
+
+    fn check_duplicate(
+        &self,
+        entries: &[(YamlNode<'a>, YamlNode<'a>)],
+        key: &YamlNode<'a>,
+    ) -> YamlResult<'a, ()> {
+        let Some((previous, _)) = entries.iter().find(|(name, _)| name.value == key.value) else {
+            return Ok(());
+        };
+        let (line, _) = self.located(previous.span.start()).line_column();
+        let message = with_error!(
+            ErrorKind::MalformedData,
+            "duplicate key `{}`, first defined on line {}",
+            key.as_str().unwrap_or_default(),
+            line
+        );
+        Err(self.error(key.span.start(), message))
+    }
+
+    /// Parses a mapping key at `start`, returning it with the offset of the
+    /// `:` that follows it, or `None` if the line does not start with a key.
+    fn key(&self, start: usize, end: usize) -> YamlResult<'a, Option<(YamlNode<'a>, usize)>> {
+        if matches!(self.byte(start, end), Some(b'"' | b'\'')) {
+            let (key, after) = self.quoted(start, end)?;
+            let colon = self.skip_spaces(after, end);
+            let separated = matches!(self.byte(colon + 1, end), None | Some(b' ' | b'\t'));
+            return Ok((self.byte(colon, end) == Some(b':') && separated).then_some((key, colon)));
+        }
+        let text = self.text(start, end);
+        if starts_with_indicator(text) {
+            return Ok(None);
+        }
+        Ok(plain_colon(text).map(|colon| {
+            let key_end = start + text[..colon].trim_end().len();
+            let key =
+                YamlValue::Scalar(Cow::Borrowed(self.text(start, key_end)), ScalarStyle::Plain);
+            (YamlNode::new(key, self.span(start, key_end)), start + colon)
+        }))
+    }
+
+    /// Parses the value following `-` or `key:` at `start`, which may continue
+    /// on the following lines.
+    fn value(
+        &mut self,
+        parent: usize,
+        context: Context,
+        start: usize,
+        end: usize,
+    ) -> YamlResult<'a, YamlNode<'a>> {
+        let mut start = self.skip_spaces(start, end);
+        let mut anchor = None;
+        if self.byte(start, end) == Some(b'&') {
+            let name_end = self.name_end(start + 1, end);
+            if name_end == start + 1 {
+                return Err(self.expected(name_end, &[Expected::Rule("anchor name")]));
+            }
+            anchor = Some(self.text(start + 1, name_end));
+            start = self.skip_spaces(name_end, end);
+        }
+
+        let rest = self.text(start, end);
+        let mut node = if is_blank(rest) {
+            self.index += 1;
+            self.indented(parent, context, start)?
+        } else if rest.starts_with(['|', '>']) {
+            self.block_scalar(parent, start, end)?
+        } else if context == Context::SequenceItem
+            && (is_entry(rest) || self.key(start, end)?.is_some())
+        {
+            // A compact collection, such as `- key: value`, is indented to the
+            // column at which it starts.
+            let line = &mut self.lines[self.index];
+            line.start = start;
+            let line = *line;
+            self.block(line)?
+        } else {
+            let node = self.inline(start, end)?;
+            self.index += 1;
+            node
+        };
+
+        if let Some(name) = anchor {
+            node.anchor = Some(name);
+            self.anchors.push((name, node.clone()));
+        }
+        Ok(node)
+    }
+
+    /// Parses a value written on the lines following its key or `-`.
+    fn indented(
+        &mut self,
+        parent: usize,
+        context: Context,
+        at: usize,
+    ) -> YamlResult<'a, YamlNode<'a>> {
+        match self.next_line()? {
+            Some(line) if line.indent() > parent => self.block(line),
+            // Sequences may be indented at the same level as their key.
+            Some(line)
+                if line.indent() == parent
+                    && context == Context::MappingValue
+                    && is_entry(self.text(line.start, line.end)) =>
+            {
+                self.nested(line.start, |reader| reader.sequence(parent))
+            }
+            _ => Ok(YamlNode::new(YamlValue::Null, self.span(at, at))),
+        }
+    }
+
+    /// Parses a value that must end on the current line.
+    fn inline(&mut self, start: usize, end: usize) -> YamlResult<'a, YamlNode<'a>> {
+        let (node, after) = match self.byte(start, end) {
+            Some(b'[' | b'{' | b'*' | b'"' | b'\'') => self.flow(start, end)?,
+            _ => self.plain(start, end)?,
+        };
+        let after = self.skip_spaces(after, end);
+        if after < end && self.byte(after, end) != Some(b'#') {
+            return Err(self.expected(after, &[Expected::Rule("end of line")]));
+        }
+        Ok(node)
+    }
+
+    fn plain(&self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        let text = self.text(start, end);
+        if starts_with_indicator(text) {
+            let found = text.chars().next().unwrap_or_default();
+            let message = with_error!(ErrorKind::MalformedData, "unexpected `{found}`");
+            return Err(self.error(start, message));
+        }
+        let text = text[..comment_start(text).unwrap_or(text.len())].trim_end();
+        if let Some(colon) = plain_colon(text) {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "mapping values are not allowed here"
+            );
+            return Err(self.error(start + colon, message));
+        }
+        let end = start + text.len();
+        let value = YamlValue::Scalar(Cow::Borrowed(text), ScalarStyle::Plain);
+        Ok((YamlNode::new(value, self.span(start, end)), end))
+    }
+
+    /// Returns the end of the anchor or alias name starting at `start`.
+    fn name_end(&self, mut at: usize, end: usize) -> usize {
+        while let Some(byte) = self.byte(at, end) {
+            if matches!(byte, b' ' | b'\t' | b',' | b'[' | b']' | b'{' | b'}') {
+                break;
+            }
+            at += 1;
+        }
+        at
+    }
+
+    fn alias(&mut self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        let name_end = self.name_end(start + 1, end);
+        let name = self.text(start + 1, name_end);
+        if name.is_empty() {
+            return Err(self.expected(name_end, &[Expected::Rule("alias name")]));
+        }
+        let Some(index) = self.anchors.iter().rposition(|(anchor, _)| *anchor == name) else {
+            let message = with_error!(ErrorKind::MalformedData, "undefined alias `*{name}`");
+            return Err(self.error(start, message));
+        };
+        self.expanded += self.anchors[index].1.size();
+        if self.expanded > MAX_ALIAS_EXPANSION {
+            let message = with_error!(
+                ErrorKind::MalformedData,
+                "aliases expand to more than {} nodes",
+                MAX_ALIAS_EXPANSION
+            );
+            return Err(self.error(start, message));
+        }
+        let mut node = self.anchors[index].1.clone();
+        node.span = self.span(start, name_end);
+        Ok((node, name_end))
+    }
+
+    /// Parses a flow node, a quoted scalar or an alias.
+    fn flow(&mut self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        match self.byte(start, end) {
+            Some(b'[') => self.nested(start, |reader| reader.flow_sequence(start, end)),
+            Some(b'{') => self.nested(start, |reader| reader.flow_mapping(start, end)),
+            Some(b'"' | b'\'') => self.quoted(start, end),
+            Some(b'*') => self.alias(start, end),
+            _ => self.flow_plain(start, end),
+        }
+    }
+
+    fn flow_plain(&self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        let bytes = self.source.as_bytes();
+        let mut at = start;
+        while let Some(byte) = self.byte(at, end) {
+            let stop = match byte {
+                b',' | b'[' | b']' | b'{' | b'}' => true,
+                b':' => matches!(
+                    self.byte(at + 1, end),
+                    None | Some(b' ' | b'\t' | b',' | b']' | b'}')
+                ),
+                b'#' => at > start && matches!(bytes[at - 1], b' ' | b'\t'),
+                _ => false,
+            };
+            if stop {
+                break;
+            }
+            at += 1;
+        }
+        let text = self.text(start, at).trim_end();
+        if text.is_empty() {
+            return Err(self.expected(start, &[Expected::Rule("value")]));
+        }
+        let end = start + text.len();
+        let value = YamlValue::Scalar(Cow::Borrowed(text), ScalarStyle::Plain);
+        Ok((YamlNode::new(value, self.span(start, end)), end))
+    }
+
+    fn unterminated(&self, start: usize, what: &str) -> ParseError<'a> {
+        let message = with_error!(ErrorKind::EndOfInput, "unterminated {what}");
+        self.error(start, message)
+    }
+
+    fn flow_sequence(&mut self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        let mut items = Vec::new();
+        let mut at = self.skip_spaces(start + 1, end);
+        loop {
+            match self.byte(at, end) {
+                Some(b']') => {
+                    let node = YamlNode::new(YamlValue::Sequence(items), self.span(start, at + 1));
+                    return Ok((node, at + 1));
+                }
+                None => return Err(self.unterminated(start, "flow sequence")),
+                _ => {}
+            }
+            let (item, after) = self.flow(at, end)?;
+            items.push(item);
+            at = self.skip_spaces(after, end);
+            match self.byte(at, end) {
+                Some(b',') => at = self.skip_spaces(at + 1, end),
+                Some(b']') => {}
+                None => return Err(self.unterminated(start, "flow sequence")),
+                Some(_) => {
+                    return Err(self.expected(at, &[Expected::Char(','), Expected::Char(']')]))
+                }
+            }
+        }
+    }
+
+    fn flow_mapping(&mut self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        let mut entries = Vec::new();
+        let mut at = self.skip_spaces(start + 1, end);
+        loop {
+            match self.byte(at, end) {
+                Some(b'}') => {
+                    let node = YamlNode::new(YamlValue::Mapping(entries), self.span(start, at + 1));
+                    return Ok((node, at + 1));
+                }
+                None => return Err(self.unterminated(start, "flow mapping")),
+                _ => {}
+            }
+            let (key, after) = match self.byte(at, end) {
+                Some(b'"' | b'\'') => self.quoted(at, end)?,
+                _ => self.flow_plain(at, end)?,
+            };
+            self.check_duplicate(&entries, &key)?;
+            at = self.skip_spaces(after, end);
+            let value = if self.byte(at, end) == Some(b':') {
+                at = self.skip_spaces(at + 1, end);
+                if matches!(self.byte(at, end), Some(b',' | b'}')) {
+                    YamlNode::new(YamlValue::Null, self.span(at, at))
+                } else {
+                    let (value, after) = self.flow(at, end)?;
+                    at = self.skip_spaces(after, end);
+                    value
+                }
+            } else {
+                YamlNode::new(YamlValue::Null, self.span(at, at))
+            };
+            entries.push((key, value));
+            match self.byte(at, end) {
+                Some(b',') => at = self.skip_spaces(at + 1, end),
+                Some(b'}') => {}
+                None => return Err(self.unterminated(start, "flow mapping")),
+                Some(_) => {
+                    return Err(self.expected(at, &[Expected::Char(','), Expected::Char('}')]))
+                }
+            }
+        }
+    }
+
+    fn quoted(&self, start: usize, end: usize) -> YamlResult<'a, (YamlNode<'a>, usize)> {
+        let quote = self.source.as_bytes()[start];
+        let mut owned: Option<String> = None;
+        let mut chunk = start + 1;
+        let mut at = start + 1;
+        loop {
+            let Some(byte) = self.byte(at, end) else {
+                return Err(self.unterminated(start, "quoted scalar"));
+            };
+            match byte {
+                b'\'' if quote == b'\'' => {
+                    if self.byte(at + 1, end) != Some(b'\'') {
+                        break;
+                    }
+                    // A doubled quote stands for a single one.
+                    let owned = owned.get_or_insert_with(String::new);
+                    owned.push_str(self.text(chunk, at + 1));
+                    at += 2;
+                    chunk = at;
+                }
+                b'"' if quote == b'"' => break,
+                b'\\' if quote == b'"' => {
+                    let owned = owned.get_or_insert_with(String::new);
+                    owned.push_str(self.text(chunk, at));
+                    let (c, length) = self.escape(at, end)?;
+                    owned.push(c);
+                    at += length;
+                    chunk = at;
+                }
+                _ => at += 1,
+            }
+        }
+        let text = match owned {
+            None => Cow::Borrowed(self.text(start + 1, at)),
+            Some(mut owned) => {
+                owned.push_str(self.text(chunk, at));
+                Cow::Owned(owned)
+            }
+        };
+        let style = if quote == b'"' {
+            ScalarStyle::DoubleQuoted
+        } else {
+            ScalarStyle::SingleQuoted
+        };
+        let node = YamlNode::new(YamlValue::Scalar(text, style), self.span(start, at + 1));
+        Ok((node, at + 1))
+    }
+
+    /// Decodes the escape sequence at `at`, returning the character and the
+    /// length of the sequence in bytes.
+    fn escape(&self, at: usize, end: usize) -> YamlResult<'a, (char, usize)> {
+        let c = match self.byte(at + 1, end) {
+            Some(b'0') => '\0',
+            Some(b'a') => '\u{7}',
+            Some(b'b') => '\u{8}',
+            Some(b't' | b'\t') => '\t',
+            Some(b'n') => '\n',
+            Some(b'v') => '\u{b}',
+            Some(b'f') => '\u{c}',
+            Some(b'r') => '\r',
+            Some(b'e') => '\u{1b}',
+            Some(b' ') => ' ',
+            Some(b'"') => '"',
+            Some(b'/') => '/',
+            Some(b'\\') => '\\',
+            Some(b'N') => '\u{85}',
+            Some(b'_') => '\u{a0}',
+            Some(b'L') => '\u{2028}',
+            Some(b'P') => '\u{2029}',
+            Some(b'x') => return self.hex_escape(at, end, 2),
+            Some(b'u') => return self.hex_escape(at, end, 4),
+            Some(b'U') => return self.hex_escape(at, end, 8),
+            Some(_) => {
+                let found = self.text(at + 1, end).chars().next().unwrap_or_default();
+                let message = with_error!(ErrorKind::MalformedData, "invalid escape `\\{found}`");
+                return Err(self.error(at, message));
+            }
+            None => return Err(self.unterminated(at, "escape sequence")),
+        };
+        Ok((c, 2))
+    }
+
+    fn hex_escape(&self, at: usize, end: usize, digits: usize) -> YamlResult<'a, (char, usize)> {
+        let scalar = self
+            .source
+            .get(at + 2..at + 2 + digits)
+            .filter(|_| at + 2 + digits <= end)
+            .and_then(|hex| {
+                hex.chars()
+                    .try_fold(0u32, |value, digit| Some(value * 16 + digit.to_digit(16)?))
+            })
+            .and_then(char::from_u32);
+        match scalar {
+            Some(c) => Ok((c, 2 + digits)),
+            None => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "expected {} hexadecimal digits forming a valid character",
+                    digits
+                );
+                Err(self.error(at, message))
+            }
+        }
+    }
+
+    /// Parses a literal or folded block scalar whose header is at `start`.
+    fn block_scalar(
+        &mut self,
+        parent: usize,
+        start: usize,
+        end: usize,
+    ) -> YamlResult<'a, YamlNode<'a>> {
+        let style = if self.byte(start, end) == Some(b'|') {
+            ScalarStyle::Literal
+        } else {
+            ScalarStyle::Folded
+        };
+        let mut chomping = Chomping::Clip;
+        let mut indent = None;
+        let mut at = start + 1;
+        while let Some(byte) = self.byte(at, end) {
+            match byte {
+                b'-' => chomping = Chomping::Strip,
+                b'+' => chomping = Chomping::Keep,
+                b'1'..=b'9' => indent = Some(parent + usize::from(byte - b'0')),
+                _ => break,
+            }
+            at += 1;
+        }
+        if !is_blank(self.text(at, end)) {
+            let at = self.skip_spaces(at, end);
+            return Err(self.expected(at, &[Expected::Rule("end of line")]));
+        }
+        self.index += 1;
+
+        // Content lines with their indentation removed; blank lines are empty.
+        let mut lines = Vec::new();
+        let mut span_end = at;
+        while let Some(&line) = self.lines.get(self.index) {
+            if self.text(line.start, line.end).is_empty() {
+                lines.push("");
+                self.index += 1;
+                continue;
+            }
+            let content = *indent.get_or_insert(line.indent());
+            if line.indent() <= parent || line.indent() < content {
+                break;
+            }
+            lines.push(self.text(line.begin + content, line.end));
+            span_end = line.end;
+            self.index += 1;
+        }
+
+        let trailing = lines
+            .iter()
+            .rev()
+            .take_while(|line| line.is_empty())
+            .count();
+        let body = &lines[..lines.len() - trailing];
+        let mut text = String::new();
+        for (index, line) in body.iter().enumerate() {
+            if index > 0 {
+                let previous = body[index - 1];
+                let indented = |line: &str| line.starts_with([' ', '\t']);
+                match style {
+                    ScalarStyle::Folded if line.is_empty() && !previous.is_empty() => {}
+                    ScalarStyle::Folded
+                        if !previous.is_empty() && !indented(previous) && !indented(line) =>
+                    {
+                        text.push(' ');
+                    }
+                    _ => text.push('\n'),
+                }
+            }
+            text.push_str(line);
+        }
+        if !body.is_empty() && chomping != Chomping::Strip {
+            text.push('\n');
+        }
+        if chomping == Chomping::Keep {
+            text.extend(core::iter::repeat_n('\n', trailing));
+        }
+
+        let value = YamlValue::Scalar(Cow::Owned(text), style);
+        Ok(YamlNode::new(value, self.span(start, span_end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text<'a>(source: &'a str, node: &YamlNode<'_>) -> &'a str {
+        &source[node.span().start()..node.span().end()]
+    }
+
+    #[test]
+    fn parses_compose_fixture() {
+        let source = include_str!("../../mock/docker-compose.yml");
+        let document = parse(source).unwrap();
+        let version = document.get("version").unwrap();
+        assert_eq!(version.as_str(), Some("3.9"));
+        assert_eq!(text(source, version), "'3.9'");
+
+        let services = document.get("services").and_then(YamlNode::as_mapping);
+        for (name, service) in services.unwrap() {
+            assert_eq!(Some(text(source, name)), name.as_str());
+            assert!(
+                service.as_mapping().is_some(),
+                "service `{}`",
+                text(source, name)
+            );
+        }
+    }
+
+    #[test]
+    fn parses_block_and_flow_collections() {
+        let source = r#"# Services
+x-defaults: &defaults
+  restart: always
+  labels: {tier: "back\tend", 'owner': 'it''s me'}
+services:
+  api:
+    <<: *defaults
+    restart: "no"
+    command: ["npm", run, start]
+    ports:
+    - 8080:80
+    - - nested
+      - items
+    environment:
+      - KEY: value
+        OTHER: ~
+      -
+    script: |
+      echo one
+        echo two
+
+    note: >-
+      folded
+      text
+
+      paragraph
+"#;
+        let document = parse(source).unwrap();
+        let api = document.get("services").unwrap().get("api").unwrap();
+
+        assert_eq!(api.get("restart").unwrap().as_str(), Some("no"));
+        let labels = api.get("labels").unwrap();
+        assert_eq!(text(source, &api.as_mapping().unwrap()[0].1), "*defaults");
+        assert!(text(source, labels).starts_with("{tier"));
+        assert_eq!(labels.get("tier").unwrap().as_str(), Some("back\tend"));
+        assert_eq!(labels.get("owner").unwrap().as_str(), Some("it's me"));
+        assert_eq!(
+            document.get("x-defaults").unwrap().anchor(),
+            Some("defaults")
+        );
+
+        let command: Vec<_> = api
+            .get("command")
+            .unwrap()
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|item| item.as_str().unwrap())
+            .collect();
+        assert_eq!(command, ["npm", "run", "start"]);
+
+        let ports = api.get("ports").unwrap().as_sequence().unwrap();
+        assert_eq!(ports[0].as_str(), Some("8080:80"));
+        assert_eq!(ports[1].as_sequence().unwrap().len(), 2);
+        assert_eq!(text(source, &ports[1]), "- nested\n      - items");
+
+        let environment = api.get("environment").unwrap().as_sequence().unwrap();
+        assert_eq!(environment[0].get("KEY").unwrap().as_str(), Some("value"));
+        assert!(environment[0].get("OTHER").unwrap().is_null());
+        assert_eq!(environment[1].value(), &YamlValue::Null);
+
+        let script = api.get("script").unwrap();
+        assert_eq!(script.as_str(), Some("echo one\n  echo two\n"));
+        assert_eq!(
+            api.get("note").unwrap().as_str(),
+            Some("folded text\nparagraph")
+        );
+    }
+
+    #[test]
+    fn errors_report_positions() {
+        let cases = [
+            ("a:\n\tb: 1", "2:1: tabs are not allowed in indentation"),
+            ("a: 1\n  b: 2", "2:3: unexpected indentation"),
+            ("a: 'open", "1:4: unterminated quoted scalar"),
+            ("a: *missing", "1:4: undefined alias `*missing`"),
+            (
+                "a: 1\nb: 2\na: 3",
+                "3:1: duplicate key `a`, first defined on line 1",
+            ),
+            ("a: [1, 2", "1:4: unterminated flow sequence"),
+            ("a: [1 [2]]", "1:7: expected one of `,`, `]`, found `[`"),
+            ("a: b: c", "1:5: mapping values are not allowed here"),
+            ("a: 1\n- b", "2:1: expected mapping key, found `-`"),
+            (
+                "a: 1\n---\nb: 2",
+                "2:1: multiple documents are not supported",
+            ),
+        ];
+        for (source, message) in cases {
+            assert_eq!(
+                parse(source).unwrap_err().to_string(),
+                message,
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn limits_alias_expansion_and_depth() {
+        let mut source = String::from("a: &a [x, x, x, x, x, x, x, x]\n");
+        for (name, previous) in ["b", "c", "d", "e", "f", "g"]
+            .iter()
+            .zip(["a", "b", "c", "d", "e", "f"])
+        {
+            source += &alloc::format!("{name}: &{name} [*{previous}, *{previous}, *{previous}, *{previous}, *{previous}, *{previous}, *{previous}, *{previous}]\n");
+        }
+        let error = parse(&source).unwrap_err();
+        assert!(
+            error.to_string().contains("aliases expand to more than"),
+            "{error}"
+        );
+
+        let deep = "[".repeat(1000);
+        let error = parse(&deep).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:128: exceeded the maximum nesting depth of 128"
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! This module contains combinators and a decoder for length-prefixed frames.
+//!
+//! [`length_data`] and [`length_value`] parse a frame whose length is given
+//! by any parser, such as one of the common [`LengthPrefix`] encodings. When
+//! frames arrive from a stream in arbitrary pieces, a [`FrameDecoder`]
+//! buffers them and returns each frame once it is complete.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::error::{Error, ErrorKind};
+#[cfg(feature = "alloc")]
+use crate::error::{ErrorMessage, MinimalError};
+use crate::span::Span;
+use crate::{with_error, AResult, Parser};
+
+/// Encoding of the length that prefixes a frame.
+///
+/// Length prefixes are parsers of byte inputs returning the length as a
+/// `u64`, and can be passed to [`length_data`] and [`length_value`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LengthPrefix {
+    U8,
+    U16Be,
+    U16Le,
+    U32Be,
+    U32Le,
+    U64Be,
+    U64Le,
+    /// An unsigned LEB128 varint of at most 10 bytes, as used by Protocol
+    /// Buffers.
+    Varint,
+}
+
+impl LengthPrefix {
+    /// Decodes a length prefix at the start of `bytes`, returning the length
+    /// and the size of the prefix, or `None` if `bytes` is too short.
+    fn decode(self, bytes: &[u8]) -> Result<Option<(u64, usize)>, ErrorKind> {
+        let (size, big_endian) = match self {
+            LengthPrefix::U8 => (1, true),
+            LengthPrefix::U16Be => (2, true),
+            LengthPrefix::U16Le => (2, false),
+            LengthPrefix::U32Be => (4, true),
+            LengthPrefix::U32Le => (4, false),
+            LengthPrefix::U64Be => (8, true),
+            LengthPrefix::U64Le => (8, false),
+            LengthPrefix::Varint => {
+                return match varint(bytes) {
+                    Ok(decoded) => Ok(Some(decoded)),
+                    Err(VarintError::Truncated) => Ok(None),
+                    Err(_) => Err(ErrorKind::MalformedData),
+                }
+            }
+        };
+        let Some(prefix) = bytes.get(..size) else {
+            return Ok(None);
+        };
+        let fold = |length, &byte| length << 8 | u64::from(byte);
+        let length = match big_endian {
+            true => prefix.iter().fold(0, fold),
+            false => prefix.iter().rev().fold(0, fold),
+        };
+        Ok(Some((length, size)))
+    }
+}
+
+/// Failure to decode a varint.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum VarintError {
+    /// The input ended before the last byte of the varint.
+    Truncated,
+    /// The tenth byte holds bits beyond the 64th.
+    Overflow,
+    /// The tenth byte is followed by another.
+    TooLong,
+}
+
+/// Decodes an unsigned LEB128 varint of at most 10 bytes at the start of
+/// `bytes`, returning its value and size.
+pub(crate) fn varint(bytes: &[u8]) -> Result<(u64, usize), VarintError> {
+    let mut value = 0;
+    for (index, &byte) in bytes.iter().enumerate().take(10) {
+        let bits = u64::from(byte & 0x7F);
+        if index == 9 && bits > 1 {
+            return Err(VarintError::Overflow);
+        }
+        value |= bits << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+    match bytes.len() < 10 {
+        true => Err(VarintError::Truncated),
+        false => Err(VarintError::TooLong),
+    }
+}
+
+impl<I, E> Parser<I, u64, E> for LengthPrefix
+where
+    I: Span<RefSlice = I, Member = u8>,
+    E: Error<I>,
+{
+    fn exec(&mut self, input: I) -> AResult<I, u64, E> {
+        match self.decode(input.as_byte_slice()) {
+            Ok(Some((length, size))) => Ok((input.over(size..input.byte_len()), length)),
+            Ok(None) => {
+                let message = with_error!(ErrorKind::EndOfInput, "truncated length prefix");
+                Err(E::from_message(input, message))
+            }
+            Err(kind) => {
+                let message = with_error!(kind, "length prefix overflows 64 bits");
+                Err(E::from_message(input, message))
+            }
+        }
+    }
+}
+
+/// Parses a length with `length`, then returns that many bytes.
+///
+/// If fewer bytes remain, fails with an `EndOfInput` error at the start of
+/// the data.
+///
+/// ```
+/// use recoil::prelude::*;
+///
+/// let input = &b"\x00\x03abcdef"[..];
+/// let mut frame = length_data::<_, _, MinimalError, _>(LengthPrefix::U16Be);
+/// assert_eq!(frame(input), Ok((&b"def"[..], &b"abc"[..])));
+///
+/// let truncated = &input[..4];
+/// let error = frame(truncated).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::EndOfInput);
+/// assert_eq!(error.offset(&truncated), 2);
+/// ```
+pub fn length_data<I, N, E, L>(mut length: L) -> impl FnMut(I) -> AResult<I, I, E>
+where
+    I: Span<RefSlice = I, Member = u8>,
+    N: TryInto<usize>,
+    E: Error<I>,
+    L: Parser<I, N, E>,
+{
+    move |input: I| {
+        let (rest, length) = length.exec(input)?;
+        match length.try_into() {
+            Ok(length) if length <= rest.byte_len() => {
+                Ok((rest.over(length..rest.byte_len()), rest.to(length)))
+            }
+            _ => {
+                let message = with_error!(ErrorKind::EndOfInput, "frame is longer than the input");
+                Err(E::from_message(rest, message))
+            }
+        }
+    }
+}
+
+/// Parses a length with `length`, then parses that many bytes with `inner`.
+///
+/// The output of `inner` is returned and parsing resumes after the frame,
+/// whether or not `inner` consumed the whole of it.
+pub fn length_value<I, N, O, E, L, P>(length: L, mut inner: P) -> impl FnMut(I) -> AResult<I, O, E>
+where
+    I: Span<RefSlice = I, Member = u8>,
+    N: TryInto<usize>,
+    E: Error<I>,
+    L: Parser<I, N, E>,
+    P: Parser<I, O, E>,
+{
+    let mut data = length_data(length);
+    move |input: I| {
+        let (rest, frame) = data(input)?;
+        let (_, output) = inner.exec(frame)?;
+        Ok((rest, output))
+    }
+}
+
+/// Decoder of length-prefixed frames received in pieces.
+///
+/// Bytes are appended with [`extend`] as they are received, and complete
+/// frames are taken with [`decode`] until it returns `None`. Frame lengths
+/// are checked against the maximum before waiting for the frame, so a peer
+/// cannot make the decoder wait for an oversized frame. The decoder does not
+/// limit how many bytes [`extend`] buffers, so frames should be taken as they
+/// complete.
+///
+/// ```
+/// use recoil::prelude::*;
+///
+/// let mut decoder = FrameDecoder::new(LengthPrefix::U8, 1024);
+/// decoder.extend(b"\x02hi\x05wor");
+/// assert_eq!(decoder.decode(), Ok(Some(&b"hi"[..])));
+/// assert_eq!(decoder.decode(), Ok(None));
+///
+/// decoder.extend(b"ld");
+/// assert_eq!(decoder.decode(), Ok(Some(&b"world"[..])));
+/// ```
+///
+/// [`extend`]: FrameDecoder::extend
+/// [`decode`]: FrameDecoder::decode
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct FrameDecoder {
+    prefix: LengthPrefix,
+    max_frame: usize,
+    buffer: Vec<u8>,
+    /// Offset in the buffer of the bytes not yet decoded.
+    start: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl FrameDecoder {
+    /// Creates a decoder of frames prefixed with `prefix`, whose data is at
+    /// most `max_frame` bytes long.
+    pub fn new(prefix: LengthPrefix, max_frame: usize) -> Self {
+        Self {
+            prefix,
+            max_frame,
+            buffer: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// Appends received bytes, discarding the frames already decoded.
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete frame, or `None` if more bytes are needed.
+    ///
+    /// Fails if the length prefix is malformed or the frame is larger than
+    /// the maximum. The stream cannot be resynchronized afterwards, and
+    /// further calls fail the same way.
+    pub fn decode(&mut self) -> Result<Option<&[u8]>, ErrorMessage> {
+        let available = &self.buffer[self.start..];
+        let result: AResult<&[u8], u64, MinimalError> = self.prefix.exec(available);
+        let (rest, length) = match result {
+            Ok(result) => result,
+            Err(error) if error.kind() == ErrorKind::EndOfInput => return Ok(None),
+            Err(error) => return Err(with_error!(error.kind(), "length prefix overflows 64 bits")),
+        };
+        let length = match usize::try_from(length) {
+            Ok(length) if length <= self.max_frame => length,
+            _ => {
+                return Err(with_error!(
+                    ErrorKind::MalformedData,
+                    "frame length {length} exceeds the limit of {}",
+                    self.max_frame
+                ))
+            }
+        };
+        if rest.len() < length {
+            return Ok(None);
+        }
+        let data = self.start + available.len() - rest.len();
+        self.start = data + length;
+        Ok(Some(&self.buffer[data..self.start]))
+    }
+
+    /// Returns the bytes received but not yet decoded as frames.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.start..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorWithContext, MinimalError};
+    use crate::located::Located;
+
+    type In = Located<&'static [u8]>;
+
+    #[test]
+    fn length_prefixes() {
+        let cases = [
+            (LengthPrefix::U8, &b"\x05"[..], 5),
+            (LengthPrefix::U16Be, b"\x01\x02", 0x0102),
+            (LengthPrefix::U16Le, b"\x01\x02", 0x0201),
+            (LengthPrefix::U32Be, b"\x00\x01\x00\x00", 0x10000),
+            (LengthPrefix::U32Le, b"\x00\x01\x00\x00", 0x100),
+            (LengthPrefix::U64Be, b"\0\0\0\0\0\0\x01\x00", 0x100),
+            (
+                LengthPrefix::U64Le,
+                b"\xFF\0\0\0\0\0\0\x80",
+                0x8000_0000_0000_00FF,
+            ),
+            (LengthPrefix::Varint, b"\xAC\x02", 300),
+            (
+                LengthPrefix::Varint,
+                b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01",
+                u64::MAX,
+            ),
+        ];
+        for (mut prefix, input, expected) in cases {
+            let (rest, length) = Parser::<_, _, MinimalError>::exec(&mut prefix, input).unwrap();
+            assert_eq!((rest, length), (&b""[..], expected), "{prefix:?}");
+            let truncated = &input[..input.len() - 1];
+            let error: MinimalError = prefix.exec(truncated).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::EndOfInput);
+        }
+
+        let mut prefix = LengthPrefix::Varint;
+        let input = Located::new(&b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x02"[..]);
+        let error: ErrorWithContext<In> = prefix.exec(input).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MalformedData);
+        assert_eq!(error.to_string(), "length prefix overflows 64 bits");
+    }
+
+    #[test]
+    fn length_combinators() {
+        let input = Located::new(&b"\x03abcd"[..]);
+        let mut frame = length_data::<In, _, ErrorWithContext<In>, _>(LengthPrefix::Varint);
+        let (rest, data) = frame(input).unwrap();
+        assert_eq!((data.fragment(), data.offset()), (&b"abc"[..], 1));
+        assert_eq!(rest.offset(), 4);
+
+        let error = frame(input.over(0..3)).unwrap_err();
+        assert_eq!(error.offset(), Some((1, 1)));
+        assert_eq!(error.to_string(), "frame is longer than the input");
+
+        let first = |input: In| -> AResult<In, u8> {
+            match input.fragment().first() {
+                Some(&byte) => Ok((input.over(1..input.byte_len()), byte)),
+                None => Err(ErrorWithContext::from_error_kind(
+                    input,
+                    ErrorKind::EndOfInput,
+                )),
+            }
+        };
+        let mut value = length_value(LengthPrefix::U8, first);
+        let (rest, byte) = value(Located::new(&b"\x02xyz"[..])).unwrap();
+        assert_eq!((rest.fragment(), byte), (&b"z"[..], b'x'));
+        let error = value(Located::new(&b"\x00xyz"[..])).unwrap_err();
+        assert_eq!(error.offset(), Some((1, 1)));
+
+        // Lengths can come from any parser, here one that counts the prefix.
+        let mut inclusive =
+            length_data::<&[u8], _, MinimalError, _>(Parser::<&[u8], u64, MinimalError>::map(
+                LengthPrefix::U16Be,
+                |length| length.saturating_sub(2),
+            ));
+        assert_eq!(
+            inclusive(&b"\x00\x04hiya"[..]),
+            Ok((&b"ya"[..], &b"hi"[..]))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn frame_decoder() {
+        let mut decoder = FrameDecoder::new(LengthPrefix::U16Be, 8);
+        assert_eq!(decoder.decode(), Ok(None));
+        for &byte in b"\x00\x03one\x00\x00\x00" {
+            decoder.extend(&[byte]);
+        }
+        assert_eq!(decoder.decode(), Ok(Some(&b"one"[..])));
+        assert_eq!(decoder.decode(), Ok(Some(&b""[..])));
+        assert_eq!(decoder.decode(), Ok(None));
+        assert_eq!(decoder.buffered(), b"\x00");
+
+        decoder.extend(b"\x05three\x00\x09");
+        assert_eq!(decoder.decode(), Ok(Some(&b"three"[..])));
+        let error = decoder.decode().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MalformedData);
+        assert_eq!(error.message(), "frame length 9 exceeds the limit of 8");
+        assert_eq!(decoder.buffered(), b"\x00\x09");
+    }
+}
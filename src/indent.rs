@@ -0,0 +1,337 @@
+//! This module contains combinators for indentation-sensitive grammars.
+//!
+//! The current indentation level is carried as the [`Indentation`] state of a
+//! [`Stateful`] input. [`indented_block`] opens a deeper level, [`same_indent`]
+//! recognizes a line at the current level and [`dedent`] detects the end of a
+//! block, which together take the place of the INDENT, NEWLINE and DEDENT
+//! tokens of Python-like grammars.
+//!
+//! Parsers run by these combinators are responsible for consuming the line
+//! terminators themselves. Blank lines are skipped before measuring a line.
+
+use crate::error::{Error, ErrorKind, ErrorMessage, Expected};
+use crate::span::Span;
+use crate::stateful::Stateful;
+use crate::{with_error, AResult, Parser};
+
+/// How tab characters in indentation are handled.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Tabs {
+    /// Tabs in indentation are an error.
+    Reject,
+    /// Tabs advance to the next multiple of the given width, as in Python.
+    Stop(usize),
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Tabs::Stop(8)
+    }
+}
+
+/// Indentation context, carried as the state of a [`Stateful`] input.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Indentation {
+    level: usize,
+    tabs: Tabs,
+}
+
+impl Indentation {
+    /// Creates a context at indentation level zero.
+    pub const fn new(tabs: Tabs) -> Self {
+        Self { level: 0, tabs }
+    }
+
+    /// Returns the width of the current indentation level, in columns.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn tabs(&self) -> Tabs {
+        self.tabs
+    }
+}
+
+/// Skips lines consisting only of spaces and tabs.
+fn skip_blank_lines<I>(mut input: Stateful<I, Indentation>) -> Stateful<I, Indentation>
+where
+    I: Span<RefSlice = I>,
+{
+    loop {
+        let bytes = input.as_byte_slice();
+        let width = bytes
+            .iter()
+            .take_while(|&&b| matches!(b, b' ' | b'\t'))
+            .count();
+        let terminator = match &bytes[width..] {
+            [b'\r', b'\n', ..] => 2,
+            [b'\n' | b'\r', ..] => 1,
+            _ => return input,
+        };
+        input = input.over(width + terminator..input.byte_len());
+    }
+}
+
+/// Measures the indentation of the line at the start of `input`, returning its
+/// width in columns and its length in bytes.
+fn measure<I, E>(input: &Stateful<I, Indentation>) -> Result<(usize, usize), E>
+where
+    I: Span<RefSlice = I>,
+    E: Error<Stateful<I, Indentation>>,
+{
+    let mut width = 0;
+    for (index, &byte) in input.as_byte_slice().iter().enumerate() {
+        match (byte, input.state().tabs) {
+            (b' ', _) => width += 1,
+            (b'\t', Tabs::Stop(stop)) => {
+                let stop = stop.max(1);
+                width = (width / stop + 1) * stop;
+            }
+            (b'\t', Tabs::Reject) => {
+                let message = with_error!(
+                    ErrorKind::MalformedData,
+                    "tabs are not allowed in indentation"
+                );
+                return Err(E::from_message(
+                    input.over(index..input.byte_len()),
+                    message,
+                ));
+            }
+            _ => return Ok((width, index)),
+        }
+    }
+    Ok((width, input.byte_len()))
+}
+
+#[cfg(feature = "alloc")]
+fn inconsistent(expected: usize, actual: usize) -> ErrorMessage {
+    with_error!(
+        ErrorKind::MalformedData,
+        "inconsistent indentation: expected column {}, found column {}",
+        expected + 1,
+        actual + 1
+    )
+}
+
+#[cfg(not(feature = "alloc"))]
+fn inconsistent(_expected: usize, _actual: usize) -> ErrorMessage {
+    with_error!(ErrorKind::MalformedData, "inconsistent indentation")
+}
+
+#[cfg(feature = "alloc")]
+fn not_deeper(level: usize, actual: usize) -> ErrorMessage {
+    with_error!(
+        ErrorKind::MalformedData,
+        "expected indentation deeper than column {}, found column {}",
+        level + 1,
+        actual + 1
+    )
+}
+
+#[cfg(not(feature = "alloc"))]
+fn not_deeper(_level: usize, _actual: usize) -> ErrorMessage {
+    with_error!(ErrorKind::MalformedData, "expected a deeper indentation")
+}
+
+/// Runs `parser` one indentation level deeper.
+///
+/// The new level is the indentation of the next non-blank line, which must be
+/// deeper than the current level. No input is consumed before running
+/// `parser`, which typically parses each line of the block with
+/// [`same_indent`]. The outer level is restored afterwards.
+pub fn indented_block<I, O, E, P>(
+    mut parser: P,
+) -> impl FnMut(Stateful<I, Indentation>) -> AResult<Stateful<I, Indentation>, O, E>
+where
+    I: Span<RefSlice = I>,
+    E: Error<Stateful<I, Indentation>>,
+    P: Parser<Stateful<I, Indentation>, O, E>,
+{
+    move |input: Stateful<I, Indentation>| {
+        let outer = *input.state();
+        let input = skip_blank_lines(input);
+        let (width, length) = measure(&input)?;
+        if width <= outer.level {
+            let found = input.over(length..input.byte_len());
+            return Err(E::from_message(found, not_deeper(outer.level, width)));
+        }
+        let inner = Indentation {
+            level: width,
+            ..outer
+        };
+        let (rest, output) = parser.exec(input.with_state(inner))?;
+        Ok((rest.with_state(outer), output))
+    }
+}
+
+/// Recognizes the indentation of a line at the current level, then runs
+/// `parser` on the rest of the line.
+///
+/// Lines indented differently fail with an error reporting both the expected
+/// and the actual column.
+pub fn same_indent<I, O, E, P>(
+    mut parser: P,
+) -> impl FnMut(Stateful<I, Indentation>) -> AResult<Stateful<I, Indentation>, O, E>
+where
+    I: Span<RefSlice = I>,
+    E: Error<Stateful<I, Indentation>>,
+    P: Parser<Stateful<I, Indentation>, O, E>,
+{
+    move |input: Stateful<I, Indentation>| {
+        let input = skip_blank_lines(input);
+        let (width, length) = measure(&input)?;
+        let content = input.over(length..input.byte_len());
+        let level = input.state().level;
+        if width != level {
+            return Err(E::from_message(content, inconsistent(level, width)));
+        }
+        parser.exec(content)
+    }
+}
+
+/// Succeeds without consuming input if the next non-blank line is indented
+/// less than the current level, or if the input is exhausted.
+pub fn dedent<I, E>(
+) -> impl FnMut(Stateful<I, Indentation>) -> AResult<Stateful<I, Indentation>, (), E>
+where
+    I: Span<RefSlice = I> + Copy,
+    E: Error<Stateful<I, Indentation>>,
+{
+    move |input: Stateful<I, Indentation>| {
+        let next = skip_blank_lines(input);
+        if next.byte_len() == 0 {
+            return Ok((input, ()));
+        }
+        let (width, length) = measure(&next)?;
+        if width < input.state().level {
+            Ok((input, ()))
+        } else {
+            let found = next.over(length..next.byte_len());
+            Err(E::from_expected(found, Expected::Rule("dedent")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorWithContext;
+    use crate::located::Located;
+
+    type In = Stateful<Located<&'static str>, Indentation>;
+    type ParseError = ErrorWithContext<Located<&'static str>>;
+
+    #[derive(Debug, PartialEq)]
+    enum Statement {
+        Simple(&'static str),
+        Block(&'static str, Vec<Statement>),
+    }
+
+    /// Parses the rest of the line, including its terminator.
+    fn line(input: In) -> AResult<In, &'static str, ParseError> {
+        let text = input.input().fragment();
+        let end = text.find('\n').unwrap_or(text.len());
+        let next = (end + 1).min(text.len());
+        Ok((input.over(next..input.byte_len()), &text[..end]))
+    }
+
+    fn statement(input: In) -> AResult<In, Statement, ParseError> {
+        let (rest, text) = line(input)?;
+        match text.strip_suffix(':') {
+            Some(header) => {
+                let (rest, body) = indented_block(block)(rest)?;
+                Ok((rest, Statement::Block(header, body)))
+            }
+            None => Ok((rest, Statement::Simple(text))),
+        }
+    }
+
+    fn block(mut input: In) -> AResult<In, Vec<Statement>, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            let (rest, statement) = same_indent(statement)(input)?;
+            statements.push(statement);
+            input = rest;
+            if dedent::<_, ParseError>()(input).is_ok() {
+                return Ok((input, statements));
+            }
+        }
+    }
+
+    fn parse(source: &'static str, tabs: Tabs) -> Result<Vec<Statement>, ParseError> {
+        let input = Stateful::new(Located::new(source), Indentation::new(tabs));
+        block(input).map(|(_, statements)| statements)
+    }
+
+    #[test]
+    fn blocks_follow_indentation() {
+        let source = "if a:\n    b\n    while c:\n        d\n\n    e\nf\n";
+        let statements = parse(source, Tabs::Reject).unwrap();
+        assert_eq!(
+            statements,
+            [
+                Statement::Block(
+                    "if a",
+                    vec![
+                        Statement::Simple("b"),
+                        Statement::Block("while c", vec![Statement::Simple("d")]),
+                        Statement::Simple("e"),
+                    ]
+                ),
+                Statement::Simple("f"),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn errors_report_expected_and_actual_columns() {
+        let error = parse("if a:\n    b\n  c\n", Tabs::Reject).unwrap_err();
+        assert_eq!(error.input().line_column(), (3, 3));
+        assert_eq!(
+            error.to_string(),
+            "inconsistent indentation: expected column 1, found column 3"
+        );
+
+        let error = parse("if a:\nb\n", Tabs::Reject).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected indentation deeper than column 1, found column 1"
+        );
+
+        let error = parse("if a:\n    b\n      c\n", Tabs::Reject).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "inconsistent indentation: expected column 5, found column 7"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "alloc"))]
+    fn errors_without_alloc_omit_columns() {
+        let error = parse("if a:\n    b\n  c\n", Tabs::Reject).unwrap_err();
+        assert_eq!(error.input().line_column(), (3, 3));
+        assert_eq!(error.to_string(), "inconsistent indentation");
+
+        let error = parse("if a:\nb\n", Tabs::Reject).unwrap_err();
+        assert_eq!(error.to_string(), "expected a deeper indentation");
+    }
+
+    #[test]
+    fn tabs_are_configurable() {
+        let source = "if a:\n\tb\n    c\n";
+        let error = parse(source, Tabs::Reject).unwrap_err();
+        assert_eq!(error.offset(), Some((6, 6)));
+        assert_eq!(error.to_string(), "tabs are not allowed in indentation");
+
+        let statements = parse(source, Tabs::Stop(4)).unwrap();
+        assert_eq!(
+            statements,
+            [Statement::Block(
+                "if a",
+                vec![Statement::Simple("b"), Statement::Simple("c")]
+            )]
+        );
+        assert!(parse(source, Tabs::Stop(8)).is_err());
+    }
+}
@@ -1,14 +1,36 @@
 //! # [`RECOIL`][https://docs.rs/recoil/latest/recoil]
 //!
 //! Recoil offers users with a new approach to building and assembling parsers.
+//!
+//! The crate is `no_std` compatible. Features that require allocations, such
+//! as formatted error messages, source maps and error recovery, are gated
+//! behind the `alloc` feature, which is enabled by default.
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 use prelude::Input;
 
 mod error;
 use crate::error::{Error, ErrorWithContext};
 
+mod checksum;
 mod collection;
+mod combinator;
+pub mod formats;
+mod framing;
+mod indent;
+mod located;
+mod recovery;
+mod registry;
+#[cfg(feature = "alloc")]
+mod source;
 mod span;
+mod stateful;
+
+use crate::combinator::Named;
+use crate::recovery::{RecoverWith, Strategy};
 
 pub type AResult<I, O = I, E = ErrorWithContext<I>> = core::result::Result<(I, O), E>;
 
@@ -26,6 +48,38 @@ pub trait Parser<I, O, E = ErrorWithContext<I>> {
             phantom: core::marker::PhantomData,
         }
     }
+
+    /// Reports failures of this parser as the grammar rule `name`.
+    ///
+    /// Errors raised at the start of the rule's input expect the rule as a
+    /// whole, producing messages such as "expected value" instead of listing
+    /// every token the rule could begin with.
+    fn named(self, name: &'static str) -> Named<Self>
+    where
+        Self: Sized,
+    {
+        Named { parser: self, name }
+    }
+
+    /// Recovers from failures of this parser using the given `strategy`.
+    ///
+    /// On failure, the error is reported to the input's error accumulator, the
+    /// strategy skips over the erroneous input and a placeholder is produced
+    /// using `O::default()`. If the strategy cannot recover, the original error
+    /// is returned. See [`parse_recovering`] for running a parser in recovery
+    /// mode.
+    ///
+    /// [`parse_recovering`]: crate::prelude::parse_recovering
+    fn recover_with<S>(self, strategy: S) -> RecoverWith<Self, S>
+    where
+        S: Strategy<I>,
+        Self: Sized,
+    {
+        RecoverWith {
+            parser: self,
+            strategy,
+        }
+    }
 }
 
 impl<'parser, I, O, E, F> Parser<I, O, E> for F
@@ -74,19 +128,61 @@ where
     }
 }
 
+/// Create and return an `ErrorMessage` for a given `ErrorKind` and message.
+///
+/// The message accepts the same syntax as `format!`:
+///
+/// ```
+/// use recoil::prelude::*;
+/// use recoil::with_error;
+///
+/// let (expected, found) = ("`]`", "`}`");
+/// let error = with_error!(ErrorKind::MalformedData, "expected {} but found {}", expected, found);
+/// assert_eq!(error.message(), "expected `]` but found `}`");
+/// ```
+///
+/// Messages without formatting arguments are never allocated.
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! with_error {
+    ($kind:expr, $($arg:tt)+) => {
+        $crate::prelude::ErrorMessage::from_args($kind, format_args!($($arg)+))
+    };
+}
+
 /// Create and return an `ErrorMessage` for a given `ErrorKind` and constant
 /// message.
+///
+/// Formatted messages require the `alloc` feature.
+#[cfg(not(feature = "alloc"))]
 #[macro_export]
 macro_rules! with_error {
-    ($kind:expr, $message:expr $(,)?) => {
-        $crate::error::ErrorMessage::from_static_message($kind, $message)
+    ($kind:expr, $message:literal $(,)?) => {
+        $crate::prelude::ErrorMessage::from_static_message($kind, $message)
     };
 }
 
 pub mod prelude {
-    pub use crate::collection::{Collection, Input};
-    pub use crate::error::{Error, ErrorKind, ErrorMessage, ErrorSpan, ErrorWithContext};
+    pub use crate::checksum::{checksummed, Checksum};
+    pub use crate::collection::{Bytes, Collection, Input};
+    pub use crate::combinator::{alt, be_u16, be_u32, char, le_u16, le_u32, satisfy, tag, Alt};
+    pub use crate::error::{
+        Error, ErrorKind, ErrorMessage, ErrorSpan, ErrorWithContext, Expected, ExpectedSet,
+        MinimalError, StatusCode,
+    };
+    #[cfg(feature = "alloc")]
+    pub use crate::framing::FrameDecoder;
+    pub use crate::framing::{length_data, length_value, LengthPrefix};
+    pub use crate::indent::{dedent, indented_block, same_indent, Indentation, Tabs};
+    pub use crate::located::{Located, Location};
+    #[cfg(feature = "alloc")]
+    pub use crate::recovery::{parse_recovering, Errors};
+    pub use crate::recovery::{recover_nested_delimiters, skip_until, Recover, Strategy};
+    pub use crate::registry::{CodeTable, ErrorCode, Registry, Severity, BUILTIN_CODES};
+    #[cfg(feature = "alloc")]
+    pub use crate::source::{DisplayPosition, FileId, Position, SourceFile, SourceMap};
     pub use crate::span::{ByteSpan, Span, StrSpan};
+    pub use crate::stateful::Stateful;
 }
 
 #[cfg(test)]
@@ -0,0 +1,294 @@
+//! This module contains the `Located` input wrapper and offset tracking.
+
+use core::ops::Range;
+
+use crate::collection::{Collection, Input};
+use crate::span::{ByteSpan, Span, StrSpan};
+
+/// Input types that know their absolute position within the original input.
+///
+/// Plain slices, such as `&str` and `&[u8]`, have no knowledge of where they
+/// were sliced from and always return `None`. Wrap the input in [`Located`] to
+/// track the absolute offset as the input is consumed.
+pub trait Location {
+    /// Returns the absolute byte offset of the first element, if known.
+    fn location(&self) -> Option<usize>;
+}
+
+impl Location for &str {
+    fn location(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl Location for &[u8] {
+    fn location(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Input wrapper that tracks the absolute offset across slicing.
+///
+/// `Located` carries the original input alongside the fragment that has not
+/// been consumed yet. Every slicing operation updates the offset, so parsers
+/// and errors always know where they are in the original buffer without
+/// resorting to pointer arithmetic.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Located<I> {
+    /// The original, complete input.
+    original: I,
+    /// The remaining fragment of the input.
+    fragment: I,
+    /// Absolute byte offset of the fragment within the original input.
+    offset: usize,
+}
+
+impl<I: Copy> Located<I> {
+    /// Wraps `input`, starting at offset zero.
+    pub fn new(input: I) -> Self {
+        Self {
+            original: input,
+            fragment: input,
+            offset: 0,
+        }
+    }
+
+    /// Returns the original, complete input.
+    pub fn original(&self) -> I {
+        self.original
+    }
+
+    /// Returns the fragment of the input that has not been consumed.
+    pub fn fragment(&self) -> I {
+        self.fragment
+    }
+
+    /// Returns the absolute byte offset of the fragment.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<I> Located<I>
+where
+    I: Span<RefSlice = I> + Copy,
+{
+    /// Returns the part of the original input that has already been consumed.
+    pub fn consumed(&self) -> I {
+        self.original.to(self.offset)
+    }
+
+    /// Returns the absolute byte range covered by the fragment.
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.fragment.byte_len()
+    }
+
+    /// Returns the 1-based line and column of the fragment's first element.
+    ///
+    /// Columns are counted in UTF-8 characters, so this is equally correct for
+    /// byte input holding text.
+    pub fn line_column(&self) -> (usize, usize) {
+        let consumed = self.original.over(0..self.offset);
+        let consumed = consumed.as_byte_slice();
+        let line_start = consumed
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |index| index + 1);
+        let line = consumed[..line_start]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count();
+        let column = consumed[line_start..]
+            .iter()
+            .filter(|&&b| b & 0xC0 != 0x80)
+            .count();
+        (line + 1, column + 1)
+    }
+
+    /// Returns a new `Located` positioned at the fragment's `range`.
+    fn slice(&self, range: Range<usize>) -> Self {
+        Self {
+            original: self.original,
+            fragment: self.fragment.over(range.clone()),
+            offset: self.offset + range.start,
+        }
+    }
+}
+
+impl<I> Location for Located<I> {
+    fn location(&self) -> Option<usize> {
+        Some(self.offset)
+    }
+}
+
+impl<'a> From<Located<&'a [u8]>> for ByteSpan<'a> {
+    fn from(located: Located<&'a [u8]>) -> Self {
+        let Range { start, end } = located.range();
+        ByteSpan::with_offsets(located.fragment, start, end)
+    }
+}
+
+impl<'a> From<Located<&'a str>> for StrSpan<'a> {
+    fn from(located: Located<&'a str>) -> Self {
+        let Range { start, end } = located.range();
+        StrSpan::with_offsets(located.fragment, start, end)
+    }
+}
+
+/// Enumerating iterator that yields absolute offsets.
+#[derive(Clone, Debug)]
+pub struct LocatedEnum<E> {
+    inner: E,
+    offset: usize,
+}
+
+impl<E, T> Iterator for LocatedEnum<E>
+where
+    E: Iterator<Item = (usize, T)>,
+{
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(index, item)| (self.offset + index, item))
+    }
+}
+
+impl<I> Collection for Located<I>
+where
+    I: Collection + Copy,
+{
+    type Item = I::Item;
+
+    type Items = I::Items;
+
+    type EnumItems = LocatedEnum<I::EnumItems>;
+
+    fn as_iter(&self) -> Self::Items {
+        self.fragment.as_iter()
+    }
+
+    /// Returns an enumerating iterator. Each index is absolute, relative to the
+    /// start of the original input.
+    fn as_enum(&self) -> Self::EnumItems {
+        LocatedEnum {
+            inner: self.fragment.as_enum(),
+            offset: self.offset,
+        }
+    }
+}
+
+impl<I> Span for Located<I>
+where
+    I: Span<RefSlice = I> + Copy,
+{
+    type Member = I::Member;
+
+    type RefSlice = Located<I>;
+
+    fn over(&self, range: Range<usize>) -> Self::RefSlice {
+        self.slice(range)
+    }
+
+    fn to(&self, index: usize) -> Self::RefSlice {
+        Self {
+            fragment: self.fragment.to(index),
+            ..*self
+        }
+    }
+
+    fn split_when<W>(&self, when: W) -> Option<(Self::RefSlice, Self::RefSlice)>
+    where
+        W: Fn(Self::Member) -> bool,
+    {
+        self.fragment.split_when(when).map(|(head, tail)| {
+            let split = self.offset + head.byte_len();
+            (
+                Self {
+                    fragment: head,
+                    ..*self
+                },
+                Self {
+                    fragment: tail,
+                    offset: split,
+                    ..*self
+                },
+            )
+        })
+    }
+
+    fn byte_len(&self) -> usize {
+        self.fragment.byte_len()
+    }
+
+    fn as_byte_slice(&self) -> &[u8] {
+        self.fragment.as_byte_slice()
+    }
+}
+
+impl<T, I> Input<T> for Located<I>
+where
+    I: Input<T> + Span<RefSlice = I> + Copy,
+{
+    type Token = I::Token;
+
+    type Slice = Located<I>;
+
+    fn as_slice(&self) -> Self::Slice {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_survive_slicing() {
+        let input = Located::new("version: '3.9'\nservices:");
+        assert_eq!(input.offset(), 0);
+
+        let tail = input.over(9..14);
+        assert_eq!(tail.fragment(), "'3.9'");
+        assert_eq!(tail.offset(), 9);
+        assert_eq!(tail.consumed(), "version: ");
+
+        let inner = tail.over(1..4);
+        assert_eq!(inner.fragment(), "3.9");
+        assert_eq!(inner.range(), 10..13);
+        assert_eq!(inner.location(), Some(10));
+    }
+
+    #[test]
+    fn line_column_counts_characters() {
+        let input = Located::new("a\n☃☃ b\nc");
+        assert_eq!(input.line_column(), (1, 1));
+        assert_eq!(input.over(9..10).line_column(), (2, 4));
+        assert_eq!(input.over(11..12).line_column(), (3, 1));
+    }
+
+    #[test]
+    fn split_when_reports_absolute_offsets() {
+        let input = Located::new(&b"key = value"[..]).over(4..11);
+        let (head, tail) = input.split_when(|b| b == b'v').unwrap();
+        assert_eq!(head.fragment(), b"= ");
+        assert_eq!(head.offset(), 4);
+        assert_eq!(tail.fragment(), b"value");
+        assert_eq!(tail.offset(), 6);
+    }
+
+    #[test]
+    fn enumeration_is_absolute() {
+        let input = Located::new("a☃b").over(1..5);
+        let indices: Vec<(usize, char)> = input.as_enum().collect();
+        assert_eq!(indices, vec![(1, '☃'), (4, 'b')]);
+    }
+
+    #[test]
+    fn spans_carry_true_offsets() {
+        let span = ByteSpan::from(Located::new(&b"hello world"[..]).over(6..11));
+        assert_eq!(span.as_bytes(), b"world");
+        assert_eq!((span.start(), span.end()), (6, 11));
+    }
+}
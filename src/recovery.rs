@@ -0,0 +1,272 @@
+//! This module contains error recovery strategies and combinators.
+//!
+//! In recovery mode, a failing parser subroutine does not abort the whole
+//! parse. Instead, its error is recorded in an [`Errors`] accumulator, a
+//! [`Strategy`] skips over the offending input and a placeholder output is
+//! inserted in place of the missing value. This produces a best-effort result
+//! together with every error encountered, which is what editor integrations
+//! typically need.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(feature = "alloc")]
+use core::cell::RefCell;
+
+use crate::span::Span;
+#[cfg(feature = "alloc")]
+use crate::stateful::Stateful;
+use crate::{AResult, Parser};
+
+/// Accumulator for errors recorded while recovering from parser failures.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default)]
+pub struct Errors<E> {
+    errors: RefCell<Vec<E>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<E> Errors<E> {
+    pub fn new() -> Self {
+        Self {
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Records an `error`.
+    pub fn push(&self, error: E) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    /// Returns the number of errors recorded so far.
+    pub fn len(&self) -> usize {
+        self.errors.borrow().len()
+    }
+
+    /// Returns `true` if no errors have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.errors.borrow().is_empty()
+    }
+
+    /// Consumes the accumulator, returning the recorded errors in order.
+    pub fn into_vec(self) -> Vec<E> {
+        self.errors.into_inner()
+    }
+}
+
+/// Input types that can record errors when a parser recovers from a failure.
+pub trait Recover<E> {
+    /// Records a recovered `error`.
+    fn report(&self, error: E);
+}
+
+#[cfg(feature = "alloc")]
+impl<I, E> Recover<E> for Stateful<I, &Errors<E>> {
+    fn report(&self, error: E) {
+        self.state().push(error);
+    }
+}
+
+/// Strategy used to skip over erroneous input during recovery.
+pub trait Strategy<I> {
+    /// Skips the erroneous part of `input`, returning the remaining input, or
+    /// `None` if the strategy cannot recover at this position.
+    fn skip(&mut self, input: I) -> Option<I>;
+}
+
+/// Skips input until `token` is found, leaving the token unconsumed.
+///
+/// If the token never occurs, the rest of the input is skipped.
+pub fn skip_until<T>(token: T) -> SkipUntil<T> {
+    SkipUntil { token }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SkipUntil<T> {
+    token: T,
+}
+
+impl<I, T> Strategy<I> for SkipUntil<T>
+where
+    I: Span<Member = T, RefSlice = I>,
+    T: PartialEq + Copy,
+{
+    fn skip(&mut self, input: I) -> Option<I> {
+        let token = self.token;
+        match input.split_when(|member| member == token) {
+            Some((_, tail)) => Some(tail),
+            None => Some(input.over(input.byte_len()..input.byte_len())),
+        }
+    }
+}
+
+/// Skips a balanced group of delimiters, including the closing delimiter.
+///
+/// Recovery only succeeds if the input starts with `open`. Nested groups are
+/// skipped as a whole, so that a failure inside `[1, [2, ?], 3]` resumes after
+/// the outermost `]`. An unterminated group skips the rest of the input.
+pub fn recover_nested_delimiters<T>(open: T, close: T) -> NestedDelimiters<T> {
+    NestedDelimiters { open, close }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NestedDelimiters<T> {
+    open: T,
+    close: T,
+}
+
+impl<I, T> Strategy<I> for NestedDelimiters<T>
+where
+    I: Span<Member = T, RefSlice = I>,
+    T: PartialEq + Copy,
+{
+    fn skip(&mut self, input: I) -> Option<I> {
+        let Self { open, close } = *self;
+        let depth = Cell::new(0usize);
+        let closed = Cell::new(false);
+        let split = input.split_when(|member| {
+            if closed.get() {
+                return true;
+            }
+            if member == open {
+                depth.set(depth.get() + 1);
+            } else if member == close && depth.get() > 0 {
+                depth.set(depth.get() - 1);
+                closed.set(depth.get() == 0);
+            } else if depth.get() == 0 {
+                return true;
+            }
+            false
+        });
+        match split {
+            // Input that does not start with `open` cannot be recovered.
+            Some((head, _)) if head.byte_len() == 0 => None,
+            Some((_, tail)) => Some(tail),
+            None => Some(input.over(input.byte_len()..input.byte_len())),
+        }
+    }
+}
+
+/// Parser subroutine that recovers from failures of the subparser.
+///
+/// See [`Parser::recover_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct RecoverWith<P, S> {
+    pub(crate) parser: P,
+    pub(crate) strategy: S,
+}
+
+impl<I, O, E, P, S> Parser<I, O, E> for RecoverWith<P, S>
+where
+    I: Recover<E> + Clone,
+    O: Default,
+    P: Parser<I, O, E>,
+    S: Strategy<I>,
+{
+    fn exec(&mut self, input: I) -> AResult<I, O, E> {
+        match self.parser.exec(input.clone()) {
+            Ok(result) => Ok(result),
+            Err(error) => match self.strategy.skip(input) {
+                Some(rest) => {
+                    rest.report(error);
+                    Ok((rest, O::default()))
+                }
+                None => Err(error),
+            },
+        }
+    }
+}
+
+/// Runs `parser` in recovery mode.
+///
+/// The input is wrapped in a [`Stateful`] carrying the error accumulator, which
+/// recovering subroutines report to. Returns the best-effort output, or `None`
+/// if the parser failed in a way it could not recover from, together with all
+/// errors in the order they were encountered.
+#[cfg(feature = "alloc")]
+pub fn parse_recovering<I, O, E, P>(mut parser: P, input: I) -> (Option<O>, Vec<E>)
+where
+    P: for<'e> Parser<Stateful<I, &'e Errors<E>>, O, E>,
+{
+    let errors = Errors::new();
+    let output = match parser.exec(Stateful::new(input, &errors)) {
+        Ok((_, output)) => Some(output),
+        Err(error) => {
+            errors.push(error);
+            None
+        }
+    };
+    (output, errors.into_vec())
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::error::{Error, ErrorKind, ErrorWithContext};
+    use crate::located::Located;
+
+    type In<'e> = Stateful<Located<&'static str>, &'e Errors<ParseError>>;
+    type ParseError = ErrorWithContext<Located<&'static str>>;
+
+    fn char_<'e>(expected: char) -> impl Fn(In<'e>) -> AResult<In<'e>, (), ParseError> {
+        move |input: In<'e>| match input.input().fragment().chars().next() {
+            Some(c) if c == expected => Ok((input.over(c.len_utf8()..input.byte_len()), ())),
+            _ => Err(Error::from_error_kind(input, ErrorKind::MissingData)),
+        }
+    }
+
+    fn number(input: In<'_>) -> AResult<In<'_>, u32, ParseError> {
+        let (digits, rest) = input
+            .split_when(|c| !c.is_ascii_digit())
+            .unwrap_or((input, input.over(input.byte_len()..input.byte_len())));
+        match digits.input().fragment().parse() {
+            Ok(value) => Ok((rest, value)),
+            Err(_) => Err(Error::from_error_kind(input, ErrorKind::MalformedData)),
+        }
+    }
+
+    /// Parses `[n, n, ...]`, recovering from malformed items.
+    fn list(input: In<'_>) -> AResult<In<'_>, Vec<u32>, ParseError> {
+        let (mut input, _) = char_('[')(input)?;
+        let mut item = number.recover_with(skip_until(','));
+        let mut items = Vec::new();
+        loop {
+            let (rest, value) = item.exec(input)?;
+            items.push(value);
+            match char_(',')(rest) {
+                Ok((rest, _)) => input = rest,
+                Err(_) => return char_(']')(rest).map(|(rest, _)| (rest, items)),
+            }
+        }
+    }
+
+    #[test]
+    fn collects_every_error() {
+        let (output, errors) = parse_recovering(list, Located::new("[1,x,3,?,5]"));
+        assert_eq!(output, Some(vec![1, 0, 3, 0, 5]));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].offset(), Some((3, 3)));
+        assert_eq!(errors[1].offset(), Some((7, 7)));
+        assert_eq!(errors[1].kind(), ErrorKind::MalformedData);
+    }
+
+    #[test]
+    fn unrecoverable_errors_are_reported() {
+        let (output, errors) = parse_recovering(list, Located::new("1,2]"));
+        assert_eq!(output, None);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), ErrorKind::MissingData);
+    }
+
+    #[test]
+    fn nested_delimiters_are_skipped() {
+        let mut strategy = recover_nested_delimiters('[', ']');
+        let input = Located::new("[1, [2, ?], 3], 4");
+        assert_eq!(strategy.skip(input).unwrap().fragment(), ", 4");
+        assert_eq!(strategy.skip(Located::new("1]")), None);
+        assert_eq!(strategy.skip(Located::new("[[1]")).unwrap().fragment(), "");
+
+        let mut strategy = skip_until(b';');
+        assert_eq!(strategy.skip(&b"a b; c"[..]), Some(&b"; c"[..]));
+    }
+}
@@ -0,0 +1,280 @@
+//! This module contains the registry of error codes, including
+//! application-defined codes.
+
+use core::fmt;
+
+use crate::error::{ErrorKind, StatusCode};
+
+/// How serious an error is.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// Informational note attached to other diagnostics.
+    Note,
+    /// Suspicious input that does not prevent parsing.
+    Warning,
+    /// Input that could not be parsed.
+    #[default]
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// Description of a single error code.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ErrorCode {
+    pub code: StatusCode,
+    /// Short identifier, such as `duplicate-key`.
+    pub name: &'static str,
+    /// Human-readable description of the error.
+    pub message: &'static str,
+    pub severity: Severity,
+}
+
+impl ErrorCode {
+    /// Describes an application-defined error code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `code` is below [`StatusCode::FIRST_CUSTOM`], which would
+    /// collide with the built-in codes. When used in a `const` or `static`
+    /// item, this is reported at compile time.
+    pub const fn new(
+        code: u16,
+        name: &'static str,
+        message: &'static str,
+        severity: Severity,
+    ) -> Self {
+        let Some(code) = StatusCode::custom(code) else {
+            panic!("custom error codes must not be below `StatusCode::FIRST_CUSTOM`");
+        };
+        Self {
+            code,
+            name,
+            message,
+            severity,
+        }
+    }
+
+    /// Returns the error kind identified by this code.
+    pub const fn kind(&self) -> ErrorKind {
+        ErrorKind::Custom(self.code)
+    }
+
+    const fn builtin(code: u16, name: &'static str, message: &'static str) -> Self {
+        Self {
+            code: StatusCode::new(code),
+            name,
+            message,
+            severity: Severity::Error,
+        }
+    }
+}
+
+/// Descriptions of the error codes of the built-in [`ErrorKind`] variants.
+pub const BUILTIN_CODES: [ErrorCode; 5] = [
+    ErrorCode::builtin(
+        0,
+        "end-of-input",
+        "Reached end of input, or EOF, while waiting on data.",
+    ),
+    ErrorCode::builtin(
+        1,
+        "incompatible-types",
+        "Input and output types must be compatible.",
+    ),
+    ErrorCode::builtin(2, "malformed-data", "Received invalid or malformed data."),
+    ErrorCode::builtin(3, "missing-data", "Received incomplete or missing data."),
+    ErrorCode::builtin(
+        4,
+        "unknown",
+        "Failure caused by unknown or unexpected error.",
+    ),
+];
+
+/// Table of error codes known to an application.
+///
+/// A registry combines the built-in codes with a slice of application-defined
+/// codes, which is typically a `static` item:
+///
+/// ```
+/// use recoil::prelude::*;
+///
+/// static CODES: [ErrorCode; 1] = [ErrorCode::new(
+///     1000,
+///     "duplicate-key",
+///     "A key was defined more than once.",
+///     Severity::Error,
+/// )];
+///
+/// let registry = Registry::new(&CODES);
+/// let code = registry.get(CODES[0].kind().as_code()).unwrap();
+/// assert_eq!(code.name, "duplicate-key");
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Registry<'a> {
+    custom: &'a [ErrorCode],
+}
+
+impl<'a> Registry<'a> {
+    pub const fn new(custom: &'a [ErrorCode]) -> Self {
+        Self { custom }
+    }
+
+    /// Returns the description of `code`, if it is registered.
+    pub fn get(&self, code: StatusCode) -> Option<&'a ErrorCode> {
+        self.codes().find(|entry| entry.code == code)
+    }
+
+    /// Returns the description of the error `kind`, if it is registered.
+    pub fn describe(&self, kind: ErrorKind) -> Option<&'a ErrorCode> {
+        self.get(kind.as_code())
+    }
+
+    /// Returns an iterator over all codes, built-in codes first.
+    pub fn codes(&self) -> impl Iterator<Item = &'a ErrorCode> {
+        BUILTIN_CODES.iter().chain(self.custom)
+    }
+
+    /// Checks that every application-defined code is custom and unique.
+    ///
+    /// Returns the first offending code on failure.
+    pub fn validate(&self) -> Result<(), StatusCode> {
+        for (index, entry) in self.custom.iter().enumerate() {
+            let duplicate = self.custom[..index]
+                .iter()
+                .any(|other| other.code == entry.code);
+            if !entry.code.is_custom() || duplicate {
+                return Err(entry.code);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a value rendering the registry as a Markdown table, suitable for
+    /// generating documentation.
+    pub fn table(&self) -> CodeTable<'a> {
+        CodeTable { registry: *self }
+    }
+}
+
+/// Markdown rendering of a [`Registry`].
+#[derive(Clone, Copy, Debug)]
+pub struct CodeTable<'a> {
+    registry: Registry<'a>,
+}
+
+impl fmt::Display for CodeTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "| Code | Name | Severity | Description |")?;
+        writeln!(f, "| ---- | ---- | -------- | ----------- |")?;
+        for entry in self.registry.codes() {
+            writeln!(
+                f,
+                "| {} | `{}` | {} | {} |",
+                entry.code,
+                entry.name,
+                entry.severity.as_str(),
+                entry.message,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, ErrorWithContext};
+
+    static CODES: [ErrorCode; 2] = [
+        ErrorCode::new(
+            1000,
+            "duplicate-key",
+            "A key was defined more than once.",
+            Severity::Error,
+        ),
+        ErrorCode::new(
+            1001,
+            "trailing-comma",
+            "Trailing commas are not portable.",
+            Severity::Warning,
+        ),
+    ];
+
+    #[test]
+    fn custom_codes_round_trip() {
+        let kind = CODES[1].kind();
+        let error = ErrorWithContext::from_error_kind("a,", kind);
+        assert_eq!(error.kind(), kind);
+        assert_eq!(error.status_code().get(), 1001);
+
+        let registry = Registry::new(&CODES);
+        let entry = registry.describe(error.kind()).unwrap();
+        assert_eq!(entry.name, "trailing-comma");
+        assert_eq!(entry.severity, Severity::Warning);
+
+        assert_eq!(
+            registry.describe(ErrorKind::MissingData).unwrap().message,
+            ErrorKind::MissingData.as_str()
+        );
+        assert!(registry.get(StatusCode::new(1002)).is_none());
+        assert_eq!(ErrorKind::from(StatusCode::new(7)), ErrorKind::Unknown);
+    }
+
+    #[test]
+    fn status_codes_round_trip_through_kinds() {
+        assert_eq!(StatusCode::custom(0), None);
+        assert_eq!(StatusCode::custom(StatusCode::FIRST_CUSTOM - 1), None);
+        for code in [StatusCode::FIRST_CUSTOM, 4242, u16::MAX] {
+            let code = StatusCode::custom(code).unwrap();
+            let kind = ErrorKind::Custom(code);
+            assert_eq!(ErrorKind::from(kind.as_code()), kind);
+            assert_eq!(kind.as_code(), code);
+        }
+        for entry in &BUILTIN_CODES {
+            assert_eq!(ErrorKind::from(entry.code).as_code(), entry.code);
+        }
+    }
+
+    #[test]
+    fn builtin_codes_match_error_kinds() {
+        for entry in &BUILTIN_CODES {
+            let kind = ErrorKind::from(entry.code);
+            assert_eq!(kind.as_code(), entry.code);
+            assert_eq!(kind.as_str(), entry.message);
+        }
+    }
+
+    #[test]
+    fn validation_rejects_duplicates() {
+        assert_eq!(Registry::new(&CODES).validate(), Ok(()));
+        let duplicated = [CODES[0], CODES[1], CODES[0]];
+        assert_eq!(
+            Registry::new(&duplicated).validate(),
+            Err(StatusCode::new(1000))
+        );
+    }
+
+    #[test]
+    fn renders_code_table() {
+        let table = Registry::new(&CODES[..1]).table().to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(
+            lines[2],
+            "| E0000 | `end-of-input` | error | Reached end of input, or EOF, while waiting on data. |"
+        );
+        assert_eq!(
+            lines[7],
+            "| E1000 | `duplicate-key` | error | A key was defined more than once. |"
+        );
+    }
+}
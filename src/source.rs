@@ -0,0 +1,264 @@
+//! This module contains the `SourceMap`, used to track multiple input files.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use crate::span::ByteSpan;
+
+/// Unique identifier of a file registered with a [`SourceMap`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// Returns the index of the file within its `SourceMap`.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A human-readable position within a source file.
+///
+/// Lines and columns are both 1-based. Columns are counted in characters, not
+/// bytes, so multi-byte UTF-8 sequences occupy a single column.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Position {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A single named source registered with a [`SourceMap`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceFile<'a> {
+    id: FileId,
+    name: &'a str,
+    contents: &'a str,
+    /// Offset of the first byte of the file in the global offset space.
+    base: usize,
+    /// Local byte offsets at which each line begins.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceFile<'a> {
+    fn new(id: FileId, name: &'a str, contents: &'a str, base: usize) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(memchr_iter(b'\n', contents.as_bytes()).map(|index| index + 1))
+            .collect();
+        Self {
+            id,
+            name,
+            contents,
+            base,
+            line_starts,
+        }
+    }
+
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn contents(&self) -> &'a str {
+        self.contents
+    }
+
+    /// Returns the range this file occupies in the global offset space.
+    pub fn range(&self) -> Range<usize> {
+        self.base..self.base + self.contents.len()
+    }
+
+    /// Returns the number of lines in the file.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the contents of the 1-based `line`, without its line ending.
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.contents.len(), |&next| next - 1);
+        let text = &self.contents[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Converts a local byte offset into a 1-based line and column pair.
+    ///
+    /// Returns `None` if `offset` is past the end of the file or does not fall
+    /// on a character boundary.
+    pub fn line_column(&self, offset: usize) -> Option<(usize, usize)> {
+        if !self.contents.is_char_boundary(offset) {
+            return None;
+        }
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let start = self.line_starts[line];
+        let column = self.contents[start..offset].chars().count() + 1;
+        Some((line + 1, column))
+    }
+}
+
+/// Registry of source files sharing a single global offset space.
+///
+/// Each registered file is assigned a [`FileId`] and a contiguous range of
+/// global offsets. Ranges never overlap, and a one byte gap separates
+/// consecutive files so that an offset pointing just past the end of a file is
+/// never mistaken for the start of the next one. This allows a [`ByteSpan`] to
+/// uniquely identify a position in any file of a project.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourceMap<'a> {
+    files: Vec<SourceFile<'a>>,
+    next_base: usize,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a new source with the given `name` and `contents`.
+    pub fn add(&mut self, name: &'a str, contents: &'a str) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        let base = self.next_base;
+        self.next_base = base + contents.len() + 1;
+        self.files.push(SourceFile::new(id, name, contents, base));
+        id
+    }
+
+    /// Returns the file registered under `id`.
+    pub fn get(&self, id: FileId) -> Option<&SourceFile<'a>> {
+        self.files.get(id.index())
+    }
+
+    /// Returns an iterator over all registered files.
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile<'a>> {
+        self.files.iter()
+    }
+
+    /// Returns the file containing the global `offset`.
+    ///
+    /// The offset directly past the end of a file belongs to that file.
+    pub fn lookup(&self, offset: usize) -> Option<&SourceFile<'a>> {
+        let index = match self.files.binary_search_by_key(&offset, |file| file.base) {
+            Ok(index) => index,
+            Err(index) => index.checked_sub(1)?,
+        };
+        let file = &self.files[index];
+        (offset <= file.range().end).then_some(file)
+    }
+
+    /// Creates a span over the local byte `range` of a file, using global
+    /// offsets.
+    pub fn span(&self, id: FileId, range: Range<usize>) -> Option<ByteSpan<'a>> {
+        let file = self.get(id)?;
+        let slice = file.contents.as_bytes().get(range.clone())?;
+        Some(ByteSpan::with_offsets(
+            slice,
+            file.base + range.start,
+            file.base + range.end,
+        ))
+    }
+
+    /// Resolves a global `offset` to its file, line and column.
+    pub fn position(&self, offset: usize) -> Option<Position> {
+        let file = self.lookup(offset)?;
+        let (line, column) = file.line_column(offset - file.base)?;
+        Some(Position {
+            file: file.id,
+            line,
+            column,
+        })
+    }
+
+    /// Resolves the start and end of a `span` to their positions.
+    pub fn resolve(&self, span: &ByteSpan<'_>) -> Option<(Position, Position)> {
+        let start = self.position(span.start())?;
+        let end = self.position(span.end())?;
+        (start.file == end.file).then_some((start, end))
+    }
+
+    /// Returns a value displaying a position as `name:line:column`.
+    pub fn display(&self, position: Position) -> DisplayPosition<'_, 'a> {
+        DisplayPosition {
+            sources: self,
+            position,
+        }
+    }
+}
+
+/// Helper for rendering a [`Position`] with its file name.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayPosition<'map, 'a> {
+    sources: &'map SourceMap<'a>,
+    position: Position,
+}
+
+impl fmt::Display for DisplayPosition<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Position { file, line, column } = self.position;
+        let name = self.sources.get(file).map_or("<unknown>", |file| file.name);
+        write!(f, "{name}:{line}:{column}")
+    }
+}
+
+#[cfg(feature = "binary")]
+fn memchr_iter(needle: u8, haystack: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    memchr::memchr_iter(needle, haystack)
+}
+
+#[cfg(not(feature = "binary"))]
+fn memchr_iter(needle: u8, haystack: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    haystack
+        .iter()
+        .enumerate()
+        .filter_map(move |(index, &b)| (b == needle).then_some(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_occupy_disjoint_ranges() {
+        let mut sources = SourceMap::new();
+        let main = sources.add("main.conf", "include 'a.conf'\nname = main\n");
+        let include = sources.add("a.conf", "port = 80\n");
+
+        let main_range = sources.get(main).unwrap().range();
+        let include_range = sources.get(include).unwrap().range();
+        assert!(main_range.end < include_range.start);
+
+        assert_eq!(sources.lookup(main_range.end).unwrap().id(), main);
+        assert_eq!(sources.lookup(include_range.start).unwrap().id(), include);
+        assert!(sources.lookup(include_range.end + 1).is_none());
+    }
+
+    #[test]
+    fn spans_resolve_to_line_and_column() {
+        let mut sources = SourceMap::new();
+        sources.add("first", "a\nb\n");
+        let second = sources.add("second", "key = ☃\nvalue = 1\r\nlast");
+
+        let span = sources.span(second, 18..19).unwrap();
+        assert_eq!(span.as_bytes(), b"1");
+
+        let (start, end) = sources.resolve(&span).unwrap();
+        assert_eq!((start.file, start.line, start.column), (second, 2, 9));
+        assert_eq!((end.line, end.column), (2, 10));
+        assert_eq!(sources.display(start).to_string(), "second:2:9");
+
+        let file = sources.get(second).unwrap();
+        assert_eq!(file.line_column(9), Some((1, 8)));
+        assert_eq!(file.line_column(7), None);
+        assert_eq!(file.line(2), Some("value = 1"));
+        assert_eq!(file.line(3), Some("last"));
+        assert_eq!(file.line(4), None);
+    }
+}
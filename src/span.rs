@@ -23,6 +23,13 @@ impl<'a> ByteSpan<'a> {
         }
     }
 
+    /// Creates a span over `slice` located at `start..end` in the original
+    /// input.
+    pub fn with_offsets(slice: &'a [u8], start: usize, end: usize) -> Self {
+        debug_assert!(start <= end);
+        Self { slice, start, end }
+    }
+
     /// Returns the inner slice of the Span as a `&[u8]`, or byte slice.
     pub fn as_bytes(&self) -> &[u8] {
         self.slice
@@ -113,13 +120,20 @@ impl<'a> StrSpan<'a> {
         }
     }
 
+    /// Creates a span over `slice` located at `start..end` in the original
+    /// input.
+    pub fn with_offsets(slice: &'a str, start: usize, end: usize) -> Self {
+        debug_assert!(start <= end);
+        Self { slice, start, end }
+    }
+
     /// Returns the inner slice of the Span as a `&[u8]`, or byte slice.
     pub fn as_bytes(&self) -> &[u8] {
         self.slice.as_bytes()
     }
 
     /// Returns the inner slice of the Span as a `&str`.
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'a str {
         self.slice
     }
 
@@ -193,6 +207,12 @@ pub trait Span: Collection {
     fn split_when<W>(&self, when: W) -> Option<(Self::RefSlice, Self::RefSlice)>
     where
         W: Fn(Self::Member) -> bool;
+
+    /// Returns the length of the span in bytes.
+    fn byte_len(&self) -> usize;
+
+    /// Returns the memory of the span as a byte slice.
+    fn as_byte_slice(&self) -> &[u8];
 }
 
 impl<'a> Span for &'a str {
@@ -205,9 +225,6 @@ impl<'a> Span for &'a str {
 
     fn to(&self, index: usize) -> Self::RefSlice {
         let end = index.min(self.len());
-        let start = 0.max(self.as_ptr() as usize);
-        debug_assert!(start <= end, "Out of bounds! `start` must be <= `end`");
-        debug_assert!(index == end, "Out of bounds! `to` must be == `end`");
         &self[0..end]
     }
 
@@ -215,10 +232,17 @@ impl<'a> Span for &'a str {
     where
         W: Fn(char) -> bool,
     {
-        self.as_iter()
-            .position(when)
-            .map(|index| self.split_at(index))
-        // self.as_iter().position(f).map(|index| self.over(0..index))
+        self.as_enum()
+            .find(|&(_, c)| when(c))
+            .map(|(index, _)| self.split_at(index))
+    }
+
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+
+    fn as_byte_slice(&self) -> &[u8] {
+        self.as_bytes()
     }
 }
 
@@ -244,4 +268,12 @@ impl<'a> Span for &'a [u8] {
             .position(|b| when(*b))
             .map(|index| self.split_at(index))
     }
+
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+
+    fn as_byte_slice(&self) -> &[u8] {
+        self
+    }
 }
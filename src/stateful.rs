@@ -0,0 +1,142 @@
+//! This module contains the `Stateful` input wrapper, used to thread
+//! user-defined state through parser subroutines.
+
+use core::ops::Range;
+
+use crate::collection::{Collection, Input};
+use crate::located::Location;
+use crate::span::Span;
+
+/// Input wrapper that carries additional parser state alongside the input.
+///
+/// The state is copied along with the input whenever it is sliced, which makes
+/// it a natural fit for references to shared accumulators or for small values
+/// that follow the nesting of the grammar, such as the current indentation.
+///
+/// Only the wrapped input takes part in comparisons; the state is ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct Stateful<I, S> {
+    input: I,
+    state: S,
+}
+
+impl<I, S> Stateful<I, S> {
+    pub fn new(input: I, state: S) -> Self {
+        Self { input, state }
+    }
+
+    /// Returns a reference to the wrapped input.
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a reference to the state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Returns the input paired with a different `state`.
+    pub fn with_state<T>(self, state: T) -> Stateful<I, T> {
+        Stateful {
+            input: self.input,
+            state,
+        }
+    }
+
+    /// Returns the wrapped input and the state.
+    pub fn into_parts(self) -> (I, S) {
+        (self.input, self.state)
+    }
+}
+
+impl<I: PartialEq, S> PartialEq for Stateful<I, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.input == other.input
+    }
+}
+
+impl<I: Location, S> Location for Stateful<I, S> {
+    fn location(&self) -> Option<usize> {
+        self.input.location()
+    }
+}
+
+impl<I: Collection, S> Collection for Stateful<I, S> {
+    type Item = I::Item;
+
+    type Items = I::Items;
+
+    type EnumItems = I::EnumItems;
+
+    fn as_iter(&self) -> Self::Items {
+        self.input.as_iter()
+    }
+
+    fn as_enum(&self) -> Self::EnumItems {
+        self.input.as_enum()
+    }
+}
+
+impl<I: Span, S: Copy> Span for Stateful<I, S> {
+    type Member = I::Member;
+
+    type RefSlice = Stateful<I::RefSlice, S>;
+
+    fn over(&self, range: Range<usize>) -> Self::RefSlice {
+        Stateful::new(self.input.over(range), self.state)
+    }
+
+    fn to(&self, index: usize) -> Self::RefSlice {
+        Stateful::new(self.input.to(index), self.state)
+    }
+
+    fn split_when<W>(&self, when: W) -> Option<(Self::RefSlice, Self::RefSlice)>
+    where
+        W: Fn(Self::Member) -> bool,
+    {
+        self.input.split_when(when).map(|(head, tail)| {
+            (
+                Stateful::new(head, self.state),
+                Stateful::new(tail, self.state),
+            )
+        })
+    }
+
+    fn byte_len(&self) -> usize {
+        self.input.byte_len()
+    }
+
+    fn as_byte_slice(&self) -> &[u8] {
+        self.input.as_byte_slice()
+    }
+}
+
+impl<T, I, S> Input<T> for Stateful<I, S>
+where
+    I: Input<T>,
+    I::Slice: Span,
+    S: Copy,
+{
+    type Token = I::Token;
+
+    type Slice = Stateful<I::Slice, S>;
+
+    fn as_slice(&self) -> Self::Slice {
+        Stateful::new(self.input.as_slice(), self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::located::Located;
+
+    #[test]
+    fn state_follows_slicing() {
+        let input = Stateful::new(Located::new("indent: 4"), 4u8);
+        let (head, tail) = input.split_when(|c| c == ':').unwrap();
+        assert_eq!(head.input().fragment(), "indent");
+        assert_eq!(*tail.over(2..3).state(), 4);
+        assert_eq!(tail.over(2..3).location(), Some(8));
+    }
+}